@@ -36,6 +36,8 @@ impl Component for GameInstanceLabel {
 #[derive(Clone, Debug)]
 pub struct Transform {
     pub position: Pt2,
+    /// Clockwise rotation in degrees, applied before translating
+    pub rotation: f64,
 }
 
 impl Component for Transform {
@@ -44,7 +46,13 @@ impl Component for Transform {
 
 impl Transform {
     pub fn new(position: Pt2) -> Self {
-        Self { position }
+        Self { position, rotation: 0.0 }
+    }
+
+    /// Set the rotation using the builder pattern
+    pub fn with_rotation(mut self, rotation: f64) -> Self {
+        self.rotation = rotation;
+        self
     }
 }
 
@@ -78,7 +86,8 @@ impl<'a> System<'a> for TransformSystem {
 
         for (transform, model, _) in (&transforms, &models, &self.changed).join() {
             let svg = document().get_element_by_id(&model.id).unwrap();
-            svg.set_attribute("transform", &format!("translate({}, {})", transform.position.x, transform.position.y))
+            svg.set_attribute("transform", &format!("translate({}, {}) rotate({})",
+                transform.position.x, transform.position.y, transform.rotation))
                 .expect("Cannot change transform");
         }
     }
@@ -159,6 +168,11 @@ impl Model {
         parent.append_child(elem).expect("Failed to add element");
         Model { id: elem.id(), order, order_changed: true }
     }
+
+    /// The corresponding element
+    pub fn element(&self) -> Element {
+        document().get_element_by_id(&self.id).expect("Missing model element")
+    }
 }
 
 impl Drop for Model {
@@ -530,17 +544,18 @@ impl<'a> System<'a> for PlaceTileSystem {
     fn run(&mut self, mut data: Self::SystemData) {
         if !data.run.0 { return }
 
-        let position = (&data.tile_slots, &data.colliders, &data.transforms).join()
+        // Tiles snap to the location's rotation too, since some boards have tiles in different orientations
+        let slot_transform = (&data.tile_slots, &data.colliders, &data.transforms).join()
             .flat_map(|(_, collider, transform)| {
-                collider.hovered().then(|| transform.position)
+                collider.hovered().then(|| transform.clone())
             })
             .next();
 
         for (_, transform) in (&data.tiles, &mut data.transforms).join() {
-            transform.position = if let Some(position) = position {
-                position
+            *transform = if let Some(slot_transform) = &slot_transform {
+                slot_transform.clone()
             } else {
-                data.input.as_ref().expect("Missing BoardInput").position()
+                Transform::new(data.input.as_ref().expect("Missing BoardInput").position())
             }
         }
 
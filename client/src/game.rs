@@ -1,6 +1,6 @@
 
 
-use common::{message::{Request, Response}};
+use common::{bot::BotLevel, message::{Request, Response}};
 use specs::{Builder, Dispatcher, DispatcherBuilder, Entity, World, WorldExt};
 use wasm_bindgen::JsCast;
 use web_sys::{Element, SvgElement};
@@ -18,7 +18,13 @@ pub struct GameWorld {
     world: World,
     id_counter: u64,
     start_game_entity: Entity,
+    /// Buttons that add a bot of each level in `BotLevel::ALL`
+    add_bot_entities: [Entity; 3],
     leave_game_entity: Entity,
+    view_replay_entity: Entity,
+    replay_back_entity: Entity,
+    replay_play_entity: Entity,
+    replay_forward_entity: Entity,
     dispatcher: Dispatcher<'static, 'static>,
     render_dispatcher: Dispatcher<'static, 'static>,
 }
@@ -83,16 +89,31 @@ impl GameWorld {
             .with(Collider::new(&document().get_element_by_id("start_game").unwrap()))
             .build();
 
+        let add_bot_entities = BotLevel::ALL.map(|level| world.create_entity()
+            .with(Collider::new(&document()
+                .get_element_by_id(&format!("add_bot_{}", level.to_string().to_lowercase())).unwrap()))
+            .build());
+
         let leave_game_entity = world.create_entity()
             .with(Collider::new(&document().get_element_by_id("leave_game").unwrap()))
             .build();
 
+        let [view_replay_entity, replay_back_entity, replay_play_entity, replay_forward_entity] =
+            ["view_replay", "replay_back", "replay_play", "replay_forward"].map(|id| world.create_entity()
+                .with(Collider::new(&document().get_element_by_id(id).unwrap()))
+                .build());
+
         Self {
             state: Some(app::EnterUsername::default().into()),
             world,
             id_counter: 0,
             start_game_entity,
+            add_bot_entities,
             leave_game_entity,
+            view_replay_entity,
+            replay_back_entity,
+            replay_play_entity,
+            replay_forward_entity,
             dispatcher,
             render_dispatcher,
         }
@@ -1,13 +1,15 @@
-use common::{board::{BasePort, BaseTLoc}, game_state::BaseGameState, message::{Request, Response}, player_state::{Looker}, tile::{BaseGAct, BaseKind, BaseTile}, game::GameId, GameInstance, math::Pt2};
+use common::{board::{BasePort, BaseTLoc}, bot::BotLevel, game_state::BaseGameState, message::{Request, Response}, player_state::{Looker}, replay::{Move, Replay}, tile::{BaseGAct, BaseKind, BaseTile}, game::GameId, GameInstance, math::Pt2};
 use format_xml::{spaced, xml};
 use itertools::{Itertools, chain};
 use specs::prelude::*;
 use enum_dispatch::enum_dispatch;
+use wasm_bindgen::JsCast;
+use web_sys::HtmlInputElement;
 use common::game::BaseGame;
 
 
 
-use crate::{SVG_NS, document, ecs::{Model, TileSelect, Transform, Collider, TokenSlot, PortLabel, TokenToPlace, RunSelectGameSystem, SelectedGame}, render::{self, BaseBoardExt, BaseTileExt, TOKEN_RADIUS, BaseGameExt, ScreenState}, window};
+use crate::{SVG_NS, document, prompt_username, save_session_token, ecs::{Model, TileSelect, Transform, Collider, TokenSlot, PortLabel, TokenToPlace, RunSelectGameSystem, SelectedGame}, render::{self, BaseBoardExt, BaseTileExt, TOKEN_RADIUS, BaseGameExt, ScreenState}, window};
 
 use super::GameWorld;
 use gameplay::GameplayStateT;
@@ -58,6 +60,46 @@ pub struct Game {
     pub(crate) board_tile_entities: Vec<Entity>,
     /// None if this is being edited
     pub(crate) gameplay_state: Option<gameplay::State>,
+    /// The last clock update from the server, if the game has a time control
+    pub(crate) clock: Option<ClockDisplay>,
+}
+
+/// User is stepping through the moves of a finished game
+#[derive(Debug)]
+pub struct ReplayViewer {
+    /// The game as of `num_moves` moves in. Its gameplay state is unused.
+    game: Game,
+    replay: Replay,
+    num_moves: usize,
+    /// When the last move was made while playing. None if paused.
+    playing_since: Option<f64>,
+}
+
+/// A clock update from the server, and when it arrived
+#[derive(Debug)]
+pub struct ClockDisplay {
+    remaining_ms: Vec<u64>,
+    running: Vec<u32>,
+    /// Milliseconds since the epoch when the update arrived
+    received_ms: f64,
+}
+
+impl ClockDisplay {
+    /// Time left for a player in milliseconds, as of now
+    fn remaining_ms(&self, player: u32) -> u64 {
+        let remaining = self.remaining_ms[player as usize];
+        if self.running.contains(&player) {
+            let elapsed = (js_sys::Date::now() - self.received_ms).max(0.0) as u64;
+            remaining.saturating_sub(elapsed)
+        } else {
+            remaining
+        }
+    }
+
+    fn text(&self, player: u32) -> String {
+        let seconds = (self.remaining_ms(player) + 999) / 1000;
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
 }
 
 #[enum_dispatch]
@@ -78,10 +120,26 @@ impl AppStateT for EnterUsername {
                 Lobby::new(games, world).into()
             }
 
+            // Resumed a session that was in a game
+            Response::JoinedGame{ game } => {
+                Game::app_state(game, world)
+            }
+
+            Response::Session{ username, token } => {
+                render::set_username(&username);
+                save_session_token(token);
+                self.into()
+            }
+
             Response::RejectedUsername => {
-                let username = window().prompt_with_message("Enter a username. The one you entered is already taken.")
-                    .unwrap_or(None)
-                    .unwrap_or_else(|| "Guest".to_owned());
+                let username = prompt_username("Enter a username. The one you entered is already taken.");
+                render::set_username(&username);
+                requests.push(Request::SetUsername{ username });
+                self.into()
+            }
+
+            Response::RejectedSession => {
+                let username = prompt_username("Enter a username");
                 render::set_username(&username);
                 requests.push(Request::SetUsername{ username });
                 self.into()
@@ -122,6 +180,19 @@ impl AppStateT for Lobby {
                 self.into()
             }
 
+            Response::RemovedGame { id } => {
+                if let Ok(pos) = self.game_entities.binary_search_by_key(&id, |(id, _)| *id) {
+                    world.world.delete_entity(self.game_entities.remove(pos).1).ok();
+                }
+                self.into()
+            }
+
+            Response::RejectedGameSettings { error } => {
+                window().alert_with_message(&format!("Could not create the game: {}", error))
+                    .expect("Cannot show alert");
+                self.into()
+            }
+
             _ => self.into()
         }
     }
@@ -154,7 +225,7 @@ impl AppStateT for WaitJoinGame {
                 } else { self.into() }
             }
 
-            Response::Rejected{ id } => {
+            Response::Rejected{ id, .. } => {
                 if self.id == id {
                     Lobby{ game_entities: self.game_entities }.into()
                 } else { self.into() }
@@ -167,8 +238,12 @@ impl AppStateT for WaitJoinGame {
 
 impl AppStateT for StatelessGame {
     fn update(self, world: &mut GameWorld, requests: &mut Vec<Request>) -> AppState {
+        let add_bot = BotLevel::ALL.into_iter().zip(world.add_bot_entities)
+            .find(|(_, entity)| world.world.read_component::<Collider>().get(*entity).unwrap().clicked());
         if world.world.read_component::<Collider>().get(world.start_game_entity).unwrap().clicked() {
             requests.push(Request::StartGame{ id: self.id });
+        } else if let Some((level, _)) = add_bot {
+            requests.push(Request::AddBot{ id: self.id, level });
         } else if world.world.read_component::<Collider>().get(world.leave_game_entity).unwrap().clicked() {
             requests.push(Request::JoinLobby);
         }
@@ -209,10 +284,16 @@ impl AppStateT for StatelessGame {
 impl StatelessGame {
     fn new(id: GameId, game: BaseGame, players: Vec<String>, world: &mut GameWorld) -> Self {
         render::set_screen_state(ScreenState::StatelessGame);
-        let board_svg = render::parse_svg(&game.board().render());
+        let board = game.board();
+        GameWorld::svg_root().set_attribute("viewBox", &board.bounding_box().padded(0.4).to_viewbox_value())
+            .expect("Cannot set board view box");
+        let board_svg = render::parse_svg(&board.render());
         let board_entity = world.world.create_entity()
             .with(Model::new(&board_svg, Model::ORDER_BOARD, &GameWorld::svg_root(), &mut world.id_counter))
             .build();
+        // Only finished games have replays
+        document().get_element_by_id("view_replay").expect("Missing view replay button")
+            .set_attribute("hidden", "").expect("Cannot hide view replay button");
 
         Self { id, game, player_usernames: players, board_entity }
     }
@@ -243,34 +324,8 @@ impl StatelessGame {
                 // Rejoined game, already placed port
                 (tile_hand_entities, gameplay::WaitPlaceTokens.into())
             } else {
-                let start_ports = game.start_ports_and_positions().into_iter()
-                    .map(|(port, position)| {
-                        let svg = render::render_port_collider();
-                        world.world.create_entity()
-                            .with(Transform::new(position))
-                            .with(Model::new(
-                                &svg,
-                                Collider::ORDER_START_PORT,
-                                &GameWorld::svg_root(),
-                                &mut world.id_counter
-                            ))
-                            .with(Collider::new(&svg))
-                            .with(TokenSlot)
-                            .with(PortLabel(port))
-                            .build()
-                    })
-                    .collect_vec();
-                let token_entity = world.world.create_entity()
-                    .with(Transform::new(Pt2::origin()))
-                    .with(Model::new(
-                        &render::parse_svg(&render::render_token(player, state.num_players(), &mut world.id_counter)),
-                        Model::ORDER_PLAYER_TOKEN, 
-                        &GameWorld::svg_root(), &mut world.id_counter
-                    ))
-                    .with(TokenToPlace)
-                    .build();
-                    
-                (tile_hand_entities, gameplay::PlaceToken{ start_ports, token_entity }.into())
+                // The server says when it's this player's turn to place their token
+                (tile_hand_entities, gameplay::WaitTurnPlaceToken.into())
             }
 
         } else {
@@ -293,6 +348,7 @@ impl StatelessGame {
             tile_hand_entities, 
             board_tile_entities: vec![],
             gameplay_state: Some(gameplay_state),
+            clock: None,
         };
 
         game_state.display_state(world);
@@ -319,29 +375,47 @@ impl AppStateT for Game {
         if world.world.read_component::<Collider>().get(world.leave_game_entity).unwrap().clicked() {
             requests.push(Request::JoinLobby);
         }
+        if world.world.read_component::<Collider>().get(world.view_replay_entity).unwrap().clicked() && self.state.game_over() {
+            requests.push(Request::GetReplay{ id: self.id });
+        }
+        self.update_clock();
         self.into()
     }
 
     fn handle_response(mut self, world: &mut GameWorld, response: Response, requests: &mut Vec<Request>) -> AppState {
         if let Response::JoinedLobby{ games } = response {
-            let to_delete = chain!(
-                [self.board_entity],
-                self.token_entities.drain(..).flatten(),
-                self.tile_hand_entities.drain(..),
-                self.board_tile_entities.drain(..),
-            ).collect_vec();
-
-            world.world.delete_entities(&to_delete).ok();
+            self.delete_entities(world);
             return Lobby::new(games, world).into();
         }
 
+        if let Response::Replay{ id, replay } = response {
+            return if id == self.id {
+                ReplayViewer::new(self, replay, world).into()
+            } else {
+                self.into()
+            };
+        }
+
         match &response {
             Response::PlacedToken{ id, player, port } => if *id == self.id {
                 self.set_token_position(world, *player, port)
             },
 
-            Response::PlacedTile{ id, player, kind, index, action, loc } => if *id == self.id {
-                self.take_turn_placing_tile(world, *player, kind, *index, action, loc)
+            Response::PlacedTile{ id, player, kind, index, action, loc, tile, drawn_tiles } => if *id == self.id {
+                self.take_turn_placing_tile(world, *player, kind, *index, action, loc, tile, drawn_tiles)
+            }
+
+            Response::Forfeited{ id, player, drawn_tiles } => if *id == self.id {
+                self.forfeit(world, *player, drawn_tiles)
+            }
+
+            Response::Clock{ id, remaining_ms, running } => if *id == self.id {
+                self.clock = Some(ClockDisplay {
+                    remaining_ms: remaining_ms.clone(),
+                    running: running.clone(),
+                    received_ms: js_sys::Date::now(),
+                });
+                self.display_state(world);
             }
 
             _ => {}
@@ -356,9 +430,21 @@ impl AppStateT for Game {
 }
 
 impl Game {
+    /// Deletes the entities of the board, tokens and tiles
+    fn delete_entities(&mut self, world: &mut GameWorld) {
+        let to_delete = chain!(
+            [self.board_entity],
+            self.token_entities.drain(..).flatten(),
+            self.tile_hand_entities.drain(..),
+            self.board_tile_entities.drain(..),
+        ).collect_vec();
+
+        world.world.delete_entities(&to_delete).ok();
+    }
+
     /// Returns either an `StatelessGame` or a `Game` depending on whether the game has started.
     fn app_state(game: GameInstance, world: &mut GameWorld) -> AppState {
-        let (id, game, state, players) = game.into_fields();
+        let (id, game, _settings, state, players) = game.into_fields();
         let stateless = StatelessGame::new(id, game, players, world);
         if let Some(state) = state {
             stateless.with_state(state, world).into()
@@ -367,6 +453,45 @@ impl Game {
         }
     }
 
+    /// Creates the start port colliders for the start ports that are still free
+    /// and the token entity that follows the mouse, for placing this player's token.
+    pub fn create_place_token_entities(&self, world: &mut GameWorld) -> (Vec<Entity>, Entity) {
+        let player = self.state.player_expect();
+        let taken = (0..self.state.num_players())
+            .flat_map(|player| self.state.board_state().player_port(player))
+            .collect_vec();
+
+        let start_ports = self.game.start_ports_and_positions().into_iter()
+            .filter(|(port, _)| !taken.contains(port))
+            .map(|(port, position)| {
+                let svg = render::render_port_collider();
+                world.world.create_entity()
+                    .with(Transform::new(position))
+                    .with(Model::new(
+                        &svg,
+                        Collider::ORDER_START_PORT,
+                        &GameWorld::svg_root(),
+                        &mut world.id_counter
+                    ))
+                    .with(Collider::new(&svg))
+                    .with(TokenSlot)
+                    .with(PortLabel(port))
+                    .build()
+            })
+            .collect_vec();
+        let token_entity = world.world.create_entity()
+            .with(Transform::new(Pt2::origin()))
+            .with(Model::new(
+                &render::parse_svg(&render::render_token(player, self.state.num_players(), &mut world.id_counter)),
+                Model::ORDER_PLAYER_TOKEN, 
+                &GameWorld::svg_root(), &mut world.id_counter
+            ))
+            .with(TokenToPlace)
+            .build();
+
+        (start_ports, token_entity)
+    }
+
     /// Moves a player token to some location.
     /// This does not care about `self.gameplay_state` and can be called with it being `None`.
     pub fn move_token(&mut self, world: &mut GameWorld, player: u32, port: &BasePort) {
@@ -409,19 +534,49 @@ impl Game {
         self.board_tile_entities.push(board_tile_entity);
     }
 
-    pub fn take_turn_placing_tile(&mut self, world: &mut GameWorld, _player: u32, kind: &BaseKind, index: u32, action: &BaseGAct, loc: &BaseTLoc) {
+    /// Eliminates `player` because they left the game, given the tiles dealt from their hand afterward.
+    pub fn forfeit(&mut self, world: &mut GameWorld, player: u32, drawn_tiles: &[(u32, u32, BaseTile)]) {
+        self.state.forfeit(&self.game, player);
+        for (player, index, tile) in drawn_tiles {
+            self.state.reveal_hand_tile(*player, &tile.kind(), *index, tile);
+        }
+        self.display_state(world);
+
+        if let Looker::Player(looker) = self.state.looker() {
+            for (player, index, tile) in drawn_tiles {
+                if *player == looker {
+                    let entity = tile.create_hand_entity(*index, &tile.identity_action(), &mut world.world, &mut world.id_counter);
+                    self.tile_hand_entities.push(entity);
+                }
+            }
+        }
+    }
+
+    /// Has `player` place a tile, given the tile before the group action was applied
+    /// and the tiles drawn afterward, since this client's state only has placeholders for hidden tiles.
+    #[allow(clippy::too_many_arguments)]
+    pub fn take_turn_placing_tile(
+        &mut self, world: &mut GameWorld, player: u32, kind: &BaseKind, index: u32, action: &BaseGAct, loc: &BaseTLoc,
+        tile: &BaseTile, drawn_tiles: &[(u32, u32, BaseTile)]
+    ) {
+        self.state.reveal_hand_tile(player, kind, index, tile);
         let delta = self.state.take_turn_placing_tile(&self.game, kind, index, action, loc);
+        for (player, index, tile) in drawn_tiles {
+            self.state.reveal_hand_tile(*player, &tile.kind(), *index, tile);
+        }
         self.display_state(world);
 
         self.place_tile(world, &delta.tile_placed().1, loc);
 
         for (player, port) in delta.player_ports().iter().enumerate() {
-            self.set_token_position(world, player as u32, port);
+            if let Some(port) = port {
+                self.set_token_position(world, player as u32, port);
+            }
         }
 
         if let Looker::Player(looker) = self.state.looker() {
             // Wipe tiles if dead
-            if delta.dead_players().contains(&looker) {
+            if delta.dead_players().iter().any(|(player, _)| *player == looker) {
                 world.world.delete_entities(&self.tile_hand_entities).expect("Entities deleted too early");
                 self.tile_hand_entities.clear();
             }
@@ -452,7 +607,7 @@ impl Game {
             }
 
             // Add new tiles
-            for (player, index, tile) in delta.drawn_tiles() {
+            for (player, index, tile) in drawn_tiles {
                 if *player == looker {
                     let entity = tile.create_hand_entity(
                         *index, 
@@ -472,12 +627,16 @@ impl Game {
             .map(|state| state.tiles_vec())
             .into_iter()
             .flat_map(|tiles| tiles.into_iter().flat_map(|(_, tiles)| tiles))
-            .map(|tile| render::wrap_svg(&tile.render(), "state-tile"))
+            .map(|tile| render::wrap_svg(&tile.render(), "state-tile", tile.bounding_box()))
             .collect::<String>();
 
         let dead = self.state.player_state(player).is_none();
         let won = self.state.won(player);
         let turn = self.state.turn_player() == player;
+        let dragon = self.state.dragon() == Some(player);
+        let clock = self.clock.as_ref().map_or(String::new(), |clock| xml! {
+            <div class="state-clock" id=("clock-"{player})>{clock.text(player)}</div>
+        }.to_string());
         let state_string = xml! {
             <div class="state">
                 <div class="state-top">
@@ -490,6 +649,8 @@ impl Game {
                     }</div>
                     if (won) { <div class="state-winner">"WIN"</div> }
                     if (turn && !self.state.game_over()) { <div class="state-winner">"TURN"</div> }
+                    if (dragon && !self.state.game_over()) { <div class="state-dragon">"DRAGON"</div> }
+                    {clock}
                 </div>
                 <div class="state-tiles">{tile_svgs}</div>
                 <div class="state-separator"></div>
@@ -498,6 +659,17 @@ impl Game {
         html_string.push_str(&state_string);
     }
 
+    /// Updates the displayed clock times without redrawing the state panel.
+    fn update_clock(&self) {
+        if let Some(clock) = &self.clock {
+            for player in clock.running.iter().copied() {
+                if let Some(element) = document().get_element_by_id(&format!("clock-{}", player)) {
+                    element.set_text_content(Some(&clock.text(player)));
+                }
+            }
+        }
+    }
+
     /// Displays the state of the game in the state panel.
     pub fn display_state(&mut self, world: &mut GameWorld) {
         let state_panel = document().get_element_by_id("state_panel").expect("Missing state panel");
@@ -514,7 +686,7 @@ impl Game {
                 let representative = self.state.top_tile_left_of_kind(&kind)
                     .expect("Must have at least 1 tile in the pile");
 
-                let tile_svg = render::wrap_svg(&representative.render(), "state-draw-tile");
+                let tile_svg = render::wrap_svg(&representative.render(), "state-draw-tile", representative.bounding_box());
                 xml!(
                     <div class="state-draw-pile">
                         {tile_svg}
@@ -530,6 +702,141 @@ impl Game {
 
         state_panel.set_inner_html(&html_string);
         state_panel.remove_attribute("style").expect("Failed to show state panel"); // remove the hiding attribute
+
+        document().get_element_by_id("view_replay").expect("Missing view replay button")
+            .toggle_attribute_with_force("hidden", !self.state.game_over())
+            .expect("Cannot show or hide view replay button");
+    }
+}
+
+impl AppStateT for ReplayViewer {
+    fn update(mut self, world: &mut GameWorld, requests: &mut Vec<Request>) -> AppState {
+        let colliders = world.world.read_component::<Collider>();
+        let [leave, back, play, forward] = [
+            world.leave_game_entity, world.replay_back_entity, world.replay_play_entity, world.replay_forward_entity,
+        ].map(|entity| colliders.get(entity).unwrap().clicked());
+        std::mem::drop(colliders);
+
+        if leave {
+            requests.push(Request::JoinLobby);
+        }
+
+        let slider = Self::slider().value_as_number() as usize;
+        if back {
+            self.set_playing(None);
+            self.go_to(world, self.num_moves.saturating_sub(1));
+        } else if forward {
+            self.set_playing(None);
+            self.go_to(world, self.num_moves + 1);
+        } else if slider != self.num_moves {
+            self.set_playing(None);
+            self.go_to(world, slider);
+        } else if play {
+            let now = js_sys::Date::now();
+            if self.playing_since.is_some() {
+                self.set_playing(None);
+            } else {
+                if self.num_moves == self.replay.moves().len() {
+                    // Start over
+                    self.go_to(world, 0);
+                }
+                self.set_playing(Some(now));
+            }
+        }
+
+        if let Some(since) = self.playing_since {
+            let now = js_sys::Date::now();
+            if now - since >= Self::PLAY_STEP_MS {
+                self.go_to(world, self.num_moves + 1);
+                self.set_playing((self.num_moves < self.replay.moves().len()).then_some(now));
+            }
+        }
+
+        self.into()
+    }
+
+    fn handle_response(mut self, world: &mut GameWorld, response: Response, _requests: &mut Vec<Request>) -> AppState {
+        if let Response::JoinedLobby{ games } = response {
+            self.game.delete_entities(world);
+            return Lobby::new(games, world).into();
+        }
+        self.into()
+    }
+}
+
+impl ReplayViewer {
+    /// How long each move is shown for while playing
+    const PLAY_STEP_MS: f64 = 1000.0;
+
+    /// Starts viewing a replay in place of a game, at the start of the game
+    fn new(mut game: Game, replay: Replay, world: &mut GameWorld) -> Self {
+        game.delete_entities(world);
+        let game = Self::game_at(&replay, game.id, 0, world);
+        Self::slider().set_max(&replay.moves().len().to_string());
+        let viewer = Self { game, replay, num_moves: 0, playing_since: None };
+        viewer.display_position();
+        viewer
+    }
+
+    fn slider() -> HtmlInputElement {
+        document().get_element_by_id("replay_slider").expect("Missing replay slider")
+            .dyn_into().expect("Replay slider is not an input")
+    }
+
+    /// Creates the game as it was after the first `num_moves` moves of the replay
+    fn game_at(replay: &Replay, id: GameId, num_moves: usize, world: &mut GameWorld) -> Game {
+        let stateless = StatelessGame::new(id, replay.game().clone(), replay.players().clone(), world);
+        let game = stateless.with_state(replay.state_after(num_moves), world);
+        render::set_screen_state(ScreenState::Replay);
+        game
+    }
+
+    /// Makes the next move, drawing it the same way as if it came from the server.
+    /// Tiles don't need revealing since the replayed state has nothing hidden.
+    fn step_forward(&mut self, world: &mut GameWorld) {
+        let game = &mut self.game;
+        match &self.replay.moves()[self.num_moves] {
+            Move::PlaceToken{ player, port } => {
+                // This also advances the turn, which the server tells players about separately
+                game.state.take_turn_placing_token(&game.game, *player, port);
+                game.set_token_position(world, *player, port);
+            }
+            Move::PlaceTile{ player, kind, index, action, loc } => {
+                let tile = game.state.hand_tile(*player, kind, *index).expect("Replayed tile placement should be legal");
+                game.take_turn_placing_tile(world, *player, kind, *index, action, loc, &tile, &[]);
+            }
+            Move::Forfeit{ player } => game.forfeit(world, *player, &[]),
+        }
+        self.num_moves += 1;
+    }
+
+    /// Shows the game after the first `num_moves` moves.
+    /// Going forward makes the moves one by one, while going back redraws the game.
+    fn go_to(&mut self, world: &mut GameWorld, num_moves: usize) {
+        let num_moves = num_moves.min(self.replay.moves().len());
+        if num_moves < self.num_moves {
+            self.game.delete_entities(world);
+            self.game = Self::game_at(&self.replay, self.game.id, num_moves, world);
+            self.num_moves = num_moves;
+        }
+        while self.num_moves < num_moves {
+            self.step_forward(world);
+        }
+        self.display_position();
+    }
+
+    fn set_playing(&mut self, playing_since: Option<f64>) {
+        self.playing_since = playing_since;
+        document().get_element_by_id("replay_play").expect("Missing replay play button")
+            .set_attribute("value", if playing_since.is_some() { "Pause" } else { "Play" })
+            .expect("Cannot set replay play button text");
+    }
+
+    /// Updates the slider and move counter
+    fn display_position(&self) {
+        Self::slider().set_value(&self.num_moves.to_string());
+        document().get_element_by_id("replay_move").expect("Missing replay move counter")
+            .set_text_content(Some(&format!("{}/{}", self.num_moves, self.replay.moves().len())));
     }
 }
 
@@ -543,16 +850,24 @@ pub enum AppState {
     WaitJoinGame,
     StatelessGame,
     Game,
+    ReplayViewer,
 }
 
 pub type State = AppState;
 
 pub mod gameplay {
-    use specs::{Entity, WorldExt};
+    use specs::{Entity, Join, WorldExt};
     use enum_dispatch::enum_dispatch;
-    use common::{math::Pt2, message::{Request, Response}, tile::BaseGAct};
+    use common::{board::BaseTLoc, game_state::BaseTilePlacement, math::Pt2, message::{Request, Response}, tile::{BaseGAct, BaseKind}};
+
+    use crate::{ecs::{Model, PlacedPort, PlacedTLoc, RunPlaceTileSystem, RunPlaceTokenSystem, SelectedTile, TileLabel, TileSelect, Transform}, game::{GameWorld, app}, render::{BaseBoardExt, BaseTileExt}};
 
-    use crate::{ecs::{PlacedPort, PlacedTLoc, RunPlaceTileSystem, RunPlaceTokenSystem, SelectedTile, TileLabel, Transform}, game::{GameWorld, app}, render::{BaseBoardExt, BaseTileExt}};
+    /// Tile placements allowed by the no-suicide rule. None if any placement is allowed.
+    pub type SafePlacements = Option<Vec<BaseTilePlacement>>;
+
+    /// Waiting for the server to say it's this player's turn to place their token
+    #[derive(Debug)]
+    pub struct WaitTurnPlaceToken;
 
     #[derive(Debug)]
     pub struct PlaceToken {
@@ -580,6 +895,7 @@ pub mod gameplay {
         pub(crate) tile_entity: Option<Entity>,
         pub(crate) tile_index: u32,
         pub(crate) tile_action: Option<BaseGAct>,
+        pub(crate) safe_placements: SafePlacements,
     }
 
     /// Waiting for the server to check the validity of the tile placement
@@ -589,6 +905,28 @@ pub mod gameplay {
         pub(crate) tile_entity: Option<Entity>,
         pub(crate) tile_index: u32,
         pub(crate) tile_action: Option<BaseGAct>,
+        pub(crate) safe_placements: SafePlacements,
+    }
+
+    /// Marks the tiles in the player's hand by whether they have a safe placement, so the unsafe ones get greyed out.
+    fn mark_safe_hand_tiles(world: &GameWorld, safe_placements: &SafePlacements) {
+        let models = world.world.read_component::<Model>();
+        let tile_selects = world.world.read_component::<TileSelect>();
+        for (model, tile_select) in (&models, &tile_selects).join() {
+            let safe = safe_placements.as_ref().map_or(true, |safe| safe.iter()
+                .any(|(kind, index, _, _)| kind == tile_select.kind() && *index == tile_select.index()));
+            model.element().set_attribute("safe", &safe.to_string()).expect("Cannot set tile safety");
+        }
+    }
+
+    impl PlaceTile {
+        /// Whether placing the selected tile of kind `kind` with the selected action is allowed by the no-suicide rule,
+        /// on location `loc` or on any location if `loc` is None.
+        fn is_safe(&self, kind: &BaseKind, loc: Option<&BaseTLoc>) -> bool {
+            self.safe_placements.as_ref().map_or(true, |safe| safe.iter().any(|(k, i, a, l)|
+                k == kind && *i == self.tile_index && Some(a) == self.tile_action.as_ref() && loc.map_or(true, |loc| loc == l)
+            ))
+        }
     }
 
     #[enum_dispatch]
@@ -598,12 +936,31 @@ pub mod gameplay {
         fn handle_response(self, app: &mut app::Game, world: &mut GameWorld, response: Response, requests: &mut Vec<Request>) -> GameplayState;
     }
 
+    impl GameplayStateT for WaitTurnPlaceToken {
+        fn update(self, _app: &mut app::Game, _world: &mut GameWorld, _requests: &mut Vec<Request>) -> GameplayState {
+            self.into()
+        }
+
+        fn handle_response(self, app: &mut app::Game, world: &mut GameWorld, response: Response, _requests: &mut Vec<Request>) -> GameplayState {
+            if let Response::YourTurnPlaceToken { id } = response {
+                if id == app.id {
+                    let (start_ports, token_entity) = app.create_place_token_entities(world);
+                    PlaceToken { start_ports, token_entity }.into()
+                } else {
+                    self.into()
+                }
+            } else {
+                self.into()
+            }
+        }
+    }
+
     impl GameplayStateT for PlaceToken {
         fn update(self, app: &mut app::Game, world: &mut GameWorld, requests: &mut Vec<Request>) -> GameplayState {
             world.world.get_mut::<RunPlaceTokenSystem>().expect("Missing RunPlaceTokenSystem").0 = true;
 
             if let Some(port) = world.world.get_mut::<PlacedPort>().expect("Missing PlacedPort").0.take() {
-                requests.push(Request::PlaceToken { id: app.id, player: app.state.player_expect(), port });
+                requests.push(Request::PlaceToken { id: app.id, port });
                 // Suspend this while waiting for the check
                 world.world.get_mut::<RunPlaceTokenSystem>().expect("Missing RunPlaceTokenSystem").0 = false;
                 WaitPlaceTokenCheck { start_ports: self.start_ports, token_entity: self.token_entity }.into()
@@ -632,7 +989,7 @@ pub mod gameplay {
                     self.into()
                 },
 
-                Response::Rejected { id } => if id == app.id {
+                Response::Rejected { id, .. } => if id == app.id {
                     PlaceToken { start_ports: self.start_ports, token_entity: self.token_entity }.into()
                 } else {
                     self.into()
@@ -667,8 +1024,9 @@ pub mod gameplay {
         }
 
         fn handle_response(self, app: &mut app::Game, world: &mut GameWorld, response: Response, _requests: &mut Vec<Request>) -> GameplayState {
-            if let Response::YourTurn { id } = response {
+            if let Response::YourTurn { id, safe_placements } = response {
                 if id == app.id {
+                    mark_safe_hand_tiles(world, &safe_placements);
                     let port = app.state.board_state().player_port(app.state.player_expect()).expect("Port should be placed");
                     let locs = app.game.board().port_locs(&port).into_iter().map(|loc| {
                         app.game.board().create_loc_collider_entity(&loc, &mut world.world, &mut world.id_counter)
@@ -679,6 +1037,7 @@ pub mod gameplay {
                         tile_entity: None,
                         tile_index: 0,
                         tile_action: None,
+                        safe_placements,
                     }.into()
                 } else { self.into() }
             } else {
@@ -714,12 +1073,16 @@ pub mod gameplay {
                     }).unwrap_or_else(|| Transform::new(Pt2::origin()));
 
                     if let Some(tile) = tile {
-                        self.tile_entity = Some(tile.create_to_place_entity(
+                        let tile_entity = tile.create_to_place_entity(
                             &self.tile_action.clone().expect("Group action should exist"),
                             transform,
                             &mut world.world,
                             &mut world.id_counter,
-                        ));
+                        );
+                        world.world.read_component::<Model>().get(tile_entity).expect("Tile entity should have Model")
+                            .element().set_attribute("safe", &self.is_safe(&tile.kind(), None).to_string())
+                            .expect("Cannot set tile safety");
+                        self.tile_entity = Some(tile_entity);
                     }
                 }
             }
@@ -730,13 +1093,17 @@ pub mod gameplay {
                 world.world.get_mut::<PlacedTLoc>().expect("Missing PlacedTLoc").0.take(),
                 self.tile_entity
             ) {
-                // Suspend while waiting for the check
-                world.world.get_mut::<RunPlaceTileSystem>().expect("Missing RunPlaceTileSystem").0 = false;
                 let kind = world.world.read_component::<TileLabel>().get(tile_entity)
                     .expect("Tile is missing label").0.kind();
+                // Unsafe placements would just get rejected
+                if !self.is_safe(&kind, Some(&loc)) {
+                    return self.into();
+                }
+
+                // Suspend while waiting for the check
+                world.world.get_mut::<RunPlaceTileSystem>().expect("Missing RunPlaceTileSystem").0 = false;
                 requests.push(Request::PlaceTile {
                     id: app.id,
-                    player: app.state.player_expect(),
                     kind,
                     index: self.tile_index,
                     action: self.tile_action.clone().expect("Group action should exist"),
@@ -748,6 +1115,7 @@ pub mod gameplay {
                     tile_entity: self.tile_entity,
                     tile_index: self.tile_index,
                     tile_action: self.tile_action,
+                    safe_placements: self.safe_placements,
                 }.into()
             } else {
                 self.into()
@@ -770,17 +1138,19 @@ pub mod gameplay {
                     self.tile_entity.map(|e| world.world.delete_entity(e).expect("Entity was deleted too early"));
                     world.world.delete_entities(&self.locs).expect("Entity was deleted too early");
                     world.world.get_mut::<SelectedTile>().expect("Missing SelectedTile").2 = None;
+                    mark_safe_hand_tiles(world, &None);
                     WaitTurn.into()
                 } else {
                     self.into()
                 },
 
-                Response::Rejected{ id } => if id == app.id {
+                Response::Rejected{ id, .. } => if id == app.id {
                     PlaceTile {
                         locs: self.locs,
                         tile_entity: self.tile_entity,
                         tile_index: self.tile_index,
                         tile_action: self.tile_action,
+                        safe_placements: self.safe_placements,
                     }.into()
                 } else { self.into() },
 
@@ -792,6 +1162,7 @@ pub mod gameplay {
     #[enum_dispatch(GameplayStateT)]
     #[derive(Debug)]
     pub enum GameplayState {
+        WaitTurnPlaceToken,
         PlaceToken,
         WaitPlaceTokenCheck,
         WaitPlaceTokens,
@@ -4,8 +4,10 @@ pub mod game;
 pub mod ecs;
 
 
+use common::game_settings::{BoardShape, GameSettings};
 use common::message::Request;
 use common::message::Response;
+use common::message::SessionToken;
 use wasm_bindgen::convert::FromWasmAbi;
 use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
@@ -67,6 +69,31 @@ fn add_event_listener<E: 'static + FromWasmAbi>(element: &Element, event_name: &
     closure.forget()
 }
 
+/// Where the session token is kept between page loads
+const SESSION_TOKEN_KEY: &str = "session_token";
+
+/// The session token saved by a previous page load in this tab, if any
+fn saved_session_token() -> Option<SessionToken> {
+    window().session_storage().ok().flatten()?
+        .get_item(SESSION_TOKEN_KEY).ok().flatten()?
+        .parse().ok()
+        .map(SessionToken)
+}
+
+/// Saves the session token so that reloading the page resumes the session
+pub fn save_session_token(token: SessionToken) {
+    if let Ok(Some(storage)) = window().session_storage() {
+        storage.set_item(SESSION_TOKEN_KEY, &token.0.to_string()).ok();
+    }
+}
+
+/// Asks the user for a username
+pub fn prompt_username(message: &str) -> String {
+    window().prompt_with_message(message)
+        .unwrap_or(None)
+        .unwrap_or_else(|| "Guest".to_owned())
+}
+
 fn request_animation_frame(callback: &Closure<dyn FnMut()>) {
     window().request_animation_frame(callback.as_ref().unchecked_ref()).expect("Cannot request animation frame");
 }
@@ -76,15 +103,34 @@ fn run() -> Result<(), JsValue> {
     ws.set_binary_type(BinaryType::Arraybuffer);
     let game_world = Arc::new(Mutex::new(GameWorld::new()));
 
-    let username = window().prompt_with_message("Enter a username")
-        .unwrap_or(None)
-        .unwrap_or_else(|| "Guest".to_owned());
-    render::set_username(&username);
-    send_request(&Request::SetUsername{ username }, &ws);
+    if let Some(token) = saved_session_token() {
+        send_request(&Request::Resume{ token }, &ws);
+    } else {
+        let username = prompt_username("Enter a username");
+        render::set_username(&username);
+        send_request(&Request::SetUsername{ username }, &ws);
+    }
 
     let cws = ws.clone();
     add_event_listener(&document().get_element_by_id("create").unwrap(), "click", move |_: Event| {
-        send_request(&Request::CreateGame, &cws);
+        send_request(&Request::CreateGame{ settings: GameSettings::default() }, &cws);
+    });
+
+    let cws = ws.clone();
+    add_event_listener(&document().get_element_by_id("create_hex").unwrap(), "click", move |_: Event| {
+        let settings = GameSettings { board: BoardShape::Hexagon{ radius: 3 }, ..GameSettings::default() };
+        send_request(&Request::CreateGame{ settings }, &cws);
+    });
+
+    let cws = ws.clone();
+    add_event_listener(&document().get_element_by_id("create_triangle").unwrap(), "click", move |_: Event| {
+        // Triangles need an even number of ports per edge, and 2 is too few for full hands
+        let settings = GameSettings {
+            board: BoardShape::Triangle{ size: 6 },
+            ports_per_edge: 4,
+            ..GameSettings::default()
+        };
+        send_request(&Request::CreateGame{ settings }, &cws);
     });
     
     let cws = ws.clone();
@@ -6,12 +6,14 @@ use std::fmt::{Debug, Display};
 use std::hash::Hash;
 use common::{for_each_tile, nalgebra, nalgebra as na, GameInstance};
 
-use common::math::{Pt2, Vec3f, Vec3u, pt2};
-use common::nalgebra::vector;
-use common::{board::{BaseBoard, BasePort, Board, RectangleBoard}, for_each_board, for_each_game, game::{BaseGame, Game, PathGame}, math::Vec2, tile::{RegularTile, Tile}};
+use common::math::{Pt2, Pt2i, Pt2u, Vec3f, Vec3u, pt2};
+use common::nalgebra::{point, vector};
+use common::{board::{BaseBoard, BasePort, Board, HexBoard, RectangleBoard, TriangleBoard}, for_each_board, for_each_game, game::{BaseGame, Game, PathGame}, math::Vec2, tile::{RegularTile, Tile}};
 use common::board::{BaseTLoc, Port, TLoc};
 use common::tile::{BaseGAct, BaseTile, Kind};
-use format_xml::{xml, spaced};
+use common::game::TokenPlacement;
+use common::game_settings::{BoardShape, GameSettings, StartPorts, TimeControl};
+use format_xml::xml;
 
 use itertools::{Itertools, chain, iproduct, izip};
 use specs::prelude::*;
@@ -47,7 +49,8 @@ pub fn parse_svg(svg_str: &str) -> SvgElement {
 pub enum ScreenState {
     Lobby,
     StatelessGame,
-    Game
+    Game,
+    Replay,
 }
 
 impl Display for ScreenState {
@@ -56,6 +59,7 @@ impl Display for ScreenState {
             Self::Lobby => write!(f, "lobby"),
             Self::StatelessGame => write!(f, "stateless-game"),
             Self::Game => write!(f, "game"),
+            Self::Replay => write!(f, "replay"),
         }
     }
 }
@@ -90,6 +94,19 @@ impl Rect {
         Self::from_ltwh(left, top, right - left, bottom - top)
     }
 
+    /// Smallest rectangle containing some points
+    pub fn bounding(points: impl IntoIterator<Item = Pt2>) -> Self {
+        let (left, top, right, bottom) = points.into_iter().fold(
+            (f64::INFINITY, f64::INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            |(l, t, r, b), p| (l.min(p.x), t.min(p.y), r.max(p.x), b.max(p.y)));
+        Self::from_ltrb(left as f32, top as f32, right as f32, bottom as f32)
+    }
+
+    /// This rectangle, extended by `amount` on every side
+    pub fn padded(self, amount: f32) -> Self {
+        Self::from_ltwh(self.left - amount, self.top - amount, self.width + 2.0 * amount, self.height + 2.0 * amount)
+    }
+
     /// Converts this to a viewBox value string
     pub fn to_viewbox_value(self) -> String {
         format!("{} {} {} {}", self.left, self.top, self.width, self.height)
@@ -98,7 +115,11 @@ impl Rect {
 
 /// Renders a game instance as the html string for a selectable game in the lobby
 pub fn render_game_instance(game: &GameInstance) -> String {
-    let title = format!("{}. Normal", game.id().0);
+    let title = match game.game() {
+        BaseGame::Normal(_) => format!("{}. Normal", game.id().0),
+        BaseGame::Hex(_) => format!("{}. Hex", game.id().0),
+        BaseGame::Triangle(_) => format!("{}. Triangle", game.id().0),
+    };
     let board = game.game().board();
     let board_svg = board.render();
     let board_bb = board.bounding_box();
@@ -106,17 +127,58 @@ pub fn render_game_instance(game: &GameInstance) -> String {
         if state.game_over() { "Game Over" } else { "Game Started" }
     } else { "Game Not Started" };
     let players = game.players().iter().map(|player| html_escape::encode_text(player)).join("; ");
+    let settings = render_game_settings(game.settings());
 
     xml!(
         <div class="game-box">
             <div class="title">{ title }</div>
             <svg xmlns={SVG_NS} class="board" viewBox={board_bb.to_viewbox_value()}>{ board_svg }</svg>
+            <div class="settings">{ settings }</div>
             <div class="status">{ status }</div>
             <div class="players">"Players: "{ players }</div>
         </div>
     ).to_string()
 }
 
+/// Renders a short description of game settings
+pub fn render_game_settings(settings: &GameSettings) -> String {
+    let tiles = settings.tiles_per_player.iter().map(|(_, num)| num).sum::<u32>();
+    let start_ports = match &settings.start_ports {
+        StartPorts::Boundary => "boundary".to_owned(),
+        StartPorts::All => "anywhere".to_owned(),
+        StartPorts::Custom(ports) => format!("{} custom", ports.len()),
+    };
+    let placement = match settings.token_placement {
+        TokenPlacement::Sequential => "in turn order",
+        TokenPlacement::Simultaneous => "simultaneously",
+    };
+    let board = match settings.board {
+        BoardShape::Rectangle{ width, height } => format!("{}×{}", width, height),
+        BoardShape::Hexagon{ radius } => format!("hexagon of radius {}", radius),
+        BoardShape::Triangle{ size } => format!("triangle of size {}", size),
+    };
+    let suicide = if settings.no_suicide { ", no suicide unless forced" } else { "" };
+    let collisions = if settings.token_collisions { ", colliding tokens are eliminated" } else { "" };
+    let time = match settings.time_control {
+        None => "".to_owned(),
+        Some(TimeControl::PerMove{ seconds }) => format!(", {}s per move", seconds),
+        Some(TimeControl::Clock{ budget_seconds, increment_seconds }) =>
+            format!(", {}s + {}s per move", budget_seconds, increment_seconds),
+    };
+    // Custom decks list each tile's catalog id, with the number of copies if there's more than one
+    let deck = match &settings.deck {
+        None => "".to_owned(),
+        Some(entries) => format!(", deck of {} tiles: {}",
+            entries.iter().map(|(_, copies)| copies).sum::<u32>(),
+            entries.iter()
+                .map(|(id, copies)| if *copies == 1 { format!("#{}", id) } else { format!("#{}×{}", id, copies) })
+                .join(" ")),
+    };
+    format!("{}, {} ports per edge, {} tiles per hand, {}–{} players, start ports {} placed {}{}{}{}{}",
+        board, settings.ports_per_edge, tiles,
+        settings.min_players, settings.max_players, start_ports, placement, suicide, collisions, time, deck)
+}
+
 /// Creates a entity corresponding to a game instance.
 pub fn game_entity(game: GameInstance, world: &mut World, id_counter: &mut u64) -> Entity {
     let elem = parse_elem(&render_game_instance(&game));
@@ -157,11 +219,26 @@ pub trait BoardExt: Board {
 
     fn loc_position(&self, loc: &Self::TLoc) -> Pt2;
 
+    /// Clockwise rotation in degrees of a tile at a specific tile location.
+    /// Only needed for boards with tiles in different orientations.
+    fn loc_rotation(&self, _loc: &Self::TLoc) -> f64 {
+        0.0
+    }
+
     /// Render the collider for a specific tile location.
     fn render_collider(&self, loc: &Self::TLoc) -> SvgElement;
 
     /// Creates an entity (mainly for collision detection) at a specific tile location.
-    fn create_loc_collider_entity(&self, loc: &Self::TLoc, world: &mut World, id_counter: &mut u64) -> Entity;
+    fn create_loc_collider_entity(&self, loc: &Self::TLoc, world: &mut World, id_counter: &mut u64) -> Entity {
+        let svg = self.render_collider(loc);
+        world.create_entity()
+            .with(Model::new(&svg, Collider::ORDER_TILE_LOC, &GameWorld::svg_root(), id_counter))
+            .with(Collider::new(&svg))
+            .with(Transform::new(self.loc_position(loc)).with_rotation(self.loc_rotation(loc)))
+            .with(TLocLabel(loc.clone().wrap_base()))
+            .with(TileSlot)
+            .build()
+    }
 }
 
 impl BoardExt for RectangleBoard {
@@ -201,19 +278,135 @@ impl BoardExt for RectangleBoard {
         }.to_string();
         parse_svg(&svg_str)
     }
+}
 
-    fn create_loc_collider_entity(&self, loc: &Self::TLoc, world: &mut World, id_counter: &mut u64) -> Entity {
-        let svg = self.render_collider(loc);
-        world.create_entity()
-            .with(Model::new(&svg, Collider::ORDER_TILE_LOC, &GameWorld::svg_root(), id_counter))
-            .with(Collider::new(&svg))
-            .with(Transform::new(self.loc_position(loc)))
-            .with(TLocLabel(loc.wrap_base()))
-            .with(TileSlot)
-            .build()
+impl BoardExt for HexBoard {
+    fn bounding_box(&self) -> Rect {
+        let half_height = 3f64.sqrt() / 2.0;
+        Rect::bounding(self.all_locs().into_iter().flat_map(|loc| {
+            let center = self.loc_position(&loc);
+            [center - vector![1.0, half_height], center + vector![1.0, half_height]]
+        })).padded(0.1)
+    }
+
+    fn render(&self) -> String {
+        let poly_str = regular_polygon_svg_str(6);
+        format!(r##"<g xmlns="{}" class="hex-board">"##, SVG_NS) +
+            &chain!(
+                self.all_locs().into_iter().map(|loc| {
+                    let v = self.loc_position(&loc);
+                    let result = xml!(<g transform=("translate("{v.x}", "{v.y}")")>{poly_str}</g>).to_string();
+                    result
+                }),
+                self.boundary_ports().into_iter().map(|port| {
+                    let v = self.port_position(&port);
+                    let (p0, p1) = hex_port_edge(self, &port);
+                    let normal = vector![p0.y - p1.y, p1.x - p0.x] * 0.1;
+                    xml!(<line x1={v.x - normal.x} x2={v.x + normal.x} y1={v.y - normal.y} y2={v.y + normal.y} class="hex-board-notch"/>).to_string()
+                })
+            )
+                .join("") +
+            r##"</g>"##
+    }
+
+    fn port_position(&self, port: &<Self as Board>::Port) -> Pt2 {
+        let (p0, p1) = hex_port_edge(self, port);
+        let index = port.1 % self.ports_per_edge();
+        p0 + (p1 - p0) * (index + 1) as f64 / (self.ports_per_edge() + 1) as f64
+    }
+
+    fn loc_position(&self, loc: &Self::TLoc) -> Pt2 {
+        pt2(1.5 * loc.x as f64, 3f64.sqrt() * (loc.y as f64 + loc.x as f64 / 2.0))
+    }
+
+    fn render_collider(&self, _loc: &Self::TLoc) -> SvgElement {
+        let svg_str = xml! {
+            <g xmlns={SVG_NS} fill="transparent">{regular_polygon_svg_str(6)}</g>
+        }.to_string();
+        parse_svg(&svg_str)
     }
 }
 
+impl BoardExt for TriangleBoard {
+    fn bounding_box(&self) -> Rect {
+        Rect::bounding([
+            triangle_vertex_position(&point![0, 0]),
+            triangle_vertex_position(&point![0, self.size()]),
+            triangle_vertex_position(&point![self.size(), self.size()]),
+        ]).padded(0.1)
+    }
+
+    fn render(&self) -> String {
+        let poly_str = regular_polygon_svg_str(3);
+        format!(r##"<g xmlns="{}" class="triangle-board">"##, SVG_NS) +
+            &chain!(
+                self.all_locs().into_iter().map(|loc| {
+                    let v = self.loc_position(&loc);
+                    let rotation = self.loc_rotation(&loc);
+                    let result = xml!(<g transform=("translate("{v.x}", "{v.y}") rotate("{rotation}")")>{poly_str}</g>).to_string();
+                    result
+                }),
+                self.boundary_ports().into_iter().map(|port| {
+                    let v = self.port_position(&port);
+                    let (p0, p1) = triangle_port_edge(self, &port);
+                    let normal = vector![p0.y - p1.y, p1.x - p0.x] * 0.1;
+                    xml!(<line x1={v.x - normal.x} x2={v.x + normal.x} y1={v.y - normal.y} y2={v.y + normal.y} class="triangle-board-notch"/>).to_string()
+                })
+            )
+                .join("") +
+            r##"</g>"##
+    }
+
+    fn port_position(&self, port: &<Self as Board>::Port) -> Pt2 {
+        let (p0, p1) = triangle_port_edge(self, port);
+        let index = port.1 % self.ports_per_edge();
+        p0 + (p1 - p0) * (index + 1) as f64 / (self.ports_per_edge() + 1) as f64
+    }
+
+    fn loc_position(&self, loc: &Self::TLoc) -> Pt2 {
+        let (pos, down) = *loc;
+        let vertices = if down {
+            [pos, pos + vector![1, 0], pos + vector![1, 1]]
+        } else {
+            [pos, pos + vector![0, 1], pos + vector![1, 1]]
+        };
+        Pt2::from(vertices.iter().map(|v| triangle_vertex_position(v).coords).sum::<Vec2>() / 3.0)
+    }
+
+    fn loc_rotation(&self, loc: &Self::TLoc) -> f64 {
+        // Tiles are rendered pointing down, so upward triangles get turned around
+        if loc.1 { 0.0 } else { 180.0 }
+    }
+
+    fn render_collider(&self, _loc: &Self::TLoc) -> SvgElement {
+        let svg_str = xml! {
+            <g xmlns={SVG_NS} fill="transparent">{regular_polygon_svg_str(3)}</g>
+        }.to_string();
+        parse_svg(&svg_str)
+    }
+}
+
+/// Position of a vertex of a triangle board, given its row and index in the row
+fn triangle_vertex_position(vertex: &Pt2u) -> Pt2 {
+    pt2(vertex.x as f64 - vertex.y as f64 / 2.0, vertex.y as f64 * 3f64.sqrt() / 2.0)
+}
+
+/// The endpoints of the edge a triangle board port is on, in clockwise order around the upward triangle that owns the edge
+fn triangle_port_edge(board: &TriangleBoard, port: &(Pt2u, u32)) -> (Pt2, Pt2) {
+    let pos = port.0;
+    let vertices = [pos + vector![1, 1], pos + vector![0, 1], pos];
+    let edge = (port.1 / board.ports_per_edge()) as usize;
+    (triangle_vertex_position(&vertices[edge]), triangle_vertex_position(&vertices[(edge + 1) % 3]))
+}
+
+/// The endpoints of the edge a hex board port is on, in clockwise order around the tile that owns the edge
+fn hex_port_edge(board: &HexBoard, port: &(Pt2i, u32)) -> (Pt2, Pt2) {
+    let center = board.loc_position(&port.0);
+    let edge = (port.1 / board.ports_per_edge()) as usize;
+    let points = regular_polygon_points(6);
+    (center + points[edge], center + points[(edge + 1) % 6])
+}
+
 /// Extension trait for BaseBoard, mainly for rendering since
 /// the server should know nothing about rendering
 pub trait BaseBoardExt {
@@ -225,6 +418,8 @@ pub trait BaseBoardExt {
 
     fn loc_position(&self, loc: &BaseTLoc) -> Pt2;
 
+    fn loc_rotation(&self, loc: &BaseTLoc) -> f64;
+
     /// Creates an entity (mainly for collision detection) at a specific tile location.
     fn create_loc_collider_entity(&self, loc: &BaseTLoc, world: &mut World, id_counter: &mut u64) -> Entity;
 }
@@ -257,6 +452,12 @@ for_each_board! {
             }
         }
 
+        fn loc_rotation(&self, loc: &BaseTLoc) -> f64 {
+            match self {
+                $($($p)*::$x(b) => b.loc_rotation(<$t as Board>::TLoc::unwrap_base_ref(loc))),*
+            }
+        }
+
         fn create_loc_collider_entity(&self, loc: &BaseTLoc, world: &mut World, id_counter: &mut u64) -> Entity {
             match self {
                 $($($p)*::$x(b) => b.create_loc_collider_entity(
@@ -293,9 +494,16 @@ fn regular_polygon_svg_str(n: u32) -> String {
 /// the server should know nothing about rendering
 pub trait TileExt: Tile {
     fn render(&self) -> String;
+
+    /// Gets the bounding box of the rendered tile, which is centered at the origin
+    fn bounding_box(&self) -> Rect;
 }
 
 impl<const EDGES: u32> TileExt for RegularTile<EDGES> {
+    fn bounding_box(&self) -> Rect {
+        Rect::bounding(regular_polygon_points(EDGES).into_iter().map(Pt2::from))
+    }
+
     fn render(&self) -> String {
         if self.visible() {
             let connections = (0..self.num_ports()).map(|i| self.output(i)).collect_vec();
@@ -345,6 +553,8 @@ impl<const EDGES: u32> TileExt for RegularTile<EDGES> {
 pub trait BaseTileExt {
     fn render(&self) -> String;
 
+    fn bounding_box(&self) -> Rect;
+
     fn create_hand_entity(&self, index: u32, action: &BaseGAct, world: &mut World, id_counter: &mut u64) -> Entity;
 
     fn create_board_entity_common<'a>(&self, world: &'a mut World, id_counter: &mut u64) -> EntityBuilder<'a>;
@@ -362,10 +572,14 @@ for_each_tile! {
             match self { $($($p)*::$x(b) => b.render()),* }
         }
 
+        fn bounding_box(&self) -> Rect {
+            match self { $($($p)*::$x(b) => b.bounding_box()),* }
+        }
+
         fn create_hand_entity(&self, index: u32, action: &BaseGAct, world: &mut World, id_counter: &mut u64) -> Entity {
             match self { $($($p)*::$x(b) => {
                 let svg = self.apply_action(action).render();
-                let wrapper = parse_svg(&wrap_svg(&svg, ""));
+                let wrapper = parse_svg(&wrap_svg(&svg, "", self.bounding_box()));
                 wrapper.set_attribute("class", "bottom-tile tile-unselected").expect("Cannot set tile select class");
                 world.create_entity()
                     .with(TileLabel(self.clone()))
@@ -399,7 +613,7 @@ for_each_tile! {
                 let svg = self.render();
                 self.create_board_entity_common(world, id_counter)
                     .with(Model::new(&parse_svg(&svg), Model::ORDER_TILE, &GameWorld::svg_root(), id_counter))
-                    .with(Transform::new(board.loc_position(loc)))
+                    .with(Transform::new(board.loc_position(loc)).with_rotation(board.loc_rotation(loc)))
                     .build()
             }),* }
         }
@@ -492,9 +706,9 @@ pub fn render_token(index: u32, num_players: u32, id_counter: &mut u64) -> Strin
 }
 
 /// Wraps the SVG in an `<svg>` element of a specific class.
-/// TODO: The viewport is set so the svg fits snugly inside.
-pub fn wrap_svg(svg: &str, class: &str) -> String {
+/// The viewport is set to `view_box`, which should be the bounding box of the svg so it fits snugly inside.
+pub fn wrap_svg(svg: &str, class: &str, view_box: Rect) -> String {
     xml!(
-        <svg xmlns={SVG_NS} class={class} viewBox={spaced!(-0.5, -0.5, 1, 1)}>{svg}</svg>
+        <svg xmlns={SVG_NS} class={class} viewBox={view_box.to_viewbox_value()}>{svg}</svg>
     ).to_string()
 }
\ No newline at end of file
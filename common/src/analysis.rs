@@ -0,0 +1,170 @@
+use getset::{CopyGetters, Getters};
+use itertools::Itertools;
+use rand::Rng;
+
+use crate::board::BasePort;
+use crate::bot::{Bot, BotLevel};
+use crate::game::BaseGame;
+use crate::game_state::{BaseGameState, BaseTilePlacement};
+use crate::pcg64_seeded;
+use crate::player_state::Looker;
+use crate::strategy::{GreedySurvivalStrategy, Strategy};
+
+/// Estimates how good each move is by sampling the tiles a player can't see
+/// and playing the rest of the game out with bots, many times over.
+/// The same seed always gives the same estimates.
+#[derive(Clone, Copy, Debug, CopyGetters)]
+pub struct Analysis {
+    #[getset(get_copy = "pub")]
+    seed: u64,
+    /// How many ways to fill in the hidden tiles each move gets played out with
+    #[getset(get_copy = "pub")]
+    num_samples: u32,
+    /// How well the bots playing games out play
+    #[getset(get_copy = "pub")]
+    rollout_level: BotLevel,
+}
+
+/// How a move fared when games were played out after it
+#[derive(Clone, Debug, Getters, CopyGetters)]
+pub struct MoveEstimate {
+    #[getset(get = "pub")]
+    placement: BaseTilePlacement,
+    /// Chance that the player is still alive when their next turn comes up, or when the game ends
+    #[getset(get_copy = "pub")]
+    survival: f64,
+    /// Expected share of the win, where a game won together with others counts as a fraction of a win
+    #[getset(get_copy = "pub")]
+    win: f64,
+}
+
+impl Analysis {
+    pub const DEFAULT_NUM_SAMPLES: u32 = 32;
+
+    /// Construct an analysis with the default number of samples and medium bots
+    pub fn new(seed: u64) -> Self {
+        Self { seed, num_samples: Self::DEFAULT_NUM_SAMPLES, rollout_level: BotLevel::Medium }
+    }
+
+    /// Set the number of samples using the builder pattern
+    pub fn with_num_samples(mut self, num_samples: u32) -> Self {
+        self.num_samples = num_samples;
+        self
+    }
+
+    /// Set how well games are played out using the builder pattern
+    pub fn with_rollout_level(mut self, rollout_level: BotLevel) -> Self {
+        self.rollout_level = rollout_level;
+        self
+    }
+
+    /// Estimates every legal tile placement of `player`, using only what `player` can see of `state`.
+    /// Every placement is played out on the same samples, so estimates can be compared fairly.
+    /// Sorted from best to worst by win, then survival.
+    /// Empty if it isn't `player`'s turn to place a tile.
+    pub fn tile_placements(&self, game: &BaseGame, state: &BaseGameState, player: u32) -> Vec<MoveEstimate> {
+        if state.game_over() || !state.all_players_placed() || state.turn_player() != player {
+            return vec![];
+        }
+        let state = state.visible_state(Looker::Player(player));
+
+        let mut rng = pcg64_seeded(self.seed);
+        let samples = (0..self.num_samples)
+            .map(|_| (state.sample_hidden(game, &mut rng), rng.gen::<u64>()))
+            .collect_vec();
+
+        state.legal_tile_placements(game, player).into_iter()
+            .map(|placement| {
+                let (kind, index, action, loc) = &placement;
+                let (survived, won) = samples.iter()
+                    .map(|(sample, rollout_seed)| {
+                        let mut sample = sample.clone();
+                        sample.take_turn_placing_tile(game, kind, *index, action, loc);
+                        self.play_out(game, sample, player, &mut pcg64_seeded(*rollout_seed))
+                    })
+                    .fold((0, 0.0), |(survived, won), (s, w)| (survived + s as u32, won + w));
+                let num_samples = self.num_samples.max(1) as f64;
+                MoveEstimate { placement, survival: survived as f64 / num_samples, win: won / num_samples }
+            })
+            .sorted_by(|a, b| b.win.total_cmp(&a.win).then(b.survival.total_cmp(&a.survival)))
+            .collect()
+    }
+
+    /// Plays a game out to the end with bots.
+    /// Returns whether `player` survived to their next turn, and their share of the win.
+    fn play_out(&self, game: &BaseGame, mut state: BaseGameState, player: u32, rng: &mut impl Rng) -> (bool, f64) {
+        let mut bot = Bot::new(self.rollout_level, rng);
+        let mut survived = None;
+        while !state.game_over() {
+            let turn_player = state.turn_player();
+            if turn_player == player {
+                survived.get_or_insert(true);
+            }
+            match bot.choose_tile_placement(game, &state.visible_state(Looker::Player(turn_player))) {
+                Some((kind, index, action, loc)) => {
+                    state.take_turn_placing_tile(game, &kind, index, &action, &loc);
+                }
+                None => {
+                    state.forfeit(game, turn_player);
+                }
+            }
+        }
+
+        let won = state.won(player);
+        let share = if won { 1.0 / state.winners().len() as f64 } else { 0.0 };
+        (survived.unwrap_or(won), share)
+    }
+}
+
+/// Places the tile the analysis rates best. Doesn't analyze start ports, so it picks them greedily.
+impl Strategy for Analysis {
+    fn choose_port(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BasePort> {
+        GreedySurvivalStrategy::new(pcg64_seeded(self.seed)).choose_port(game, state)
+    }
+
+    fn choose_tile_placement(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BaseTilePlacement> {
+        self.tile_placements(game, state, state.player_expect()).into_iter()
+            .next()
+            .map(|estimate| estimate.placement)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_settings::GameSettings;
+
+    use super::*;
+
+    #[test]
+    fn test_analysis() {
+        let game = GameSettings::default().build().unwrap();
+        let mut state = game.new_state_seeded(2, 3);
+        let ports = game.start_ports();
+        state.take_turn_placing_token(&game, 0, &ports[0]);
+        state.take_turn_placing_token(&game, 1, &ports[ports.len() / 2]);
+
+        let analysis = Analysis::new(7).with_num_samples(4);
+        let estimates = analysis.tile_placements(&game, &state, 0);
+        assert_eq!(estimates.len(), state.legal_tile_placements(&game, 0).len());
+        assert!(estimates.windows(2).all(|pair| pair[0].win() >= pair[1].win()));
+        assert!(estimates.iter().all(|estimate| (0.0..=1.0).contains(&estimate.win())));
+
+        // Placements that lose right away never survive
+        for estimate in &estimates {
+            let (kind, index, action, loc) = estimate.placement();
+            let mut after = state.clone();
+            after.take_turn_placing_tile(&game, kind, *index, action, loc);
+            if after.player_state(0).is_none() && !after.won(0) {
+                assert_eq!(estimate.survival(), 0.0);
+            }
+        }
+
+        // Deterministic given the seed, and only depends on what the player can see
+        let again = analysis.tile_placements(&game, &state.visible_state(Looker::Player(0)), 0);
+        assert_eq!(
+            estimates.iter().map(|e| (e.placement().clone(), e.survival(), e.win())).collect_vec(),
+            again.iter().map(|e| (e.placement().clone(), e.survival(), e.win())).collect_vec(),
+        );
+        assert!(analysis.tile_placements(&game, &state, 1).is_empty());
+    }
+}
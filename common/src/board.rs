@@ -1,4 +1,4 @@
-use crate::math::{Pt2i, Pt2u, Vec2u};
+use crate::math::{Pt2i, Pt2u, Vec2i, Vec2u};
 use crate::tile::Kind;
 use na::point;
 use nalgebra as na;
@@ -20,9 +20,19 @@ impl Port for (Pt2u, Vec2u) {
     impl_wrap_functions!(() BasePort, Pt2uVec2u);
 }
 
+impl Port for (Pt2i, u32) {
+    impl_wrap_functions!(() BasePort, Pt2iU32);
+}
+
+impl Port for (Pt2u, u32) {
+    impl_wrap_functions!(() BasePort, Pt2uU32);
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BasePort {
-    Pt2uVec2u((Pt2u, Vec2u))
+    Pt2uVec2u((Pt2u, Vec2u)),
+    Pt2iU32((Pt2i, u32)),
+    Pt2uU32((Pt2u, u32)),
 }
 
 pub trait TLoc: Clone + Debug + Eq + Hash + Serialize + for<'a> Deserialize<'a> {
@@ -33,9 +43,19 @@ impl TLoc for Pt2u {
     impl_wrap_functions!(() BaseTLoc, Pt2u);
 }
 
+impl TLoc for Pt2i {
+    impl_wrap_functions!(() BaseTLoc, Pt2i);
+}
+
+impl TLoc for (Pt2u, bool) {
+    impl_wrap_functions!(() BaseTLoc, Pt2uBool);
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum BaseTLoc {
-    Pt2u(Pt2u)
+    Pt2u(Pt2u),
+    Pt2i(Pt2i),
+    Pt2uBool((Pt2u, bool)),
 }
 
 #[macro_export]
@@ -46,6 +66,8 @@ macro_rules! for_each_board {
         }
         __mac! {
             ($crate::board::BaseBoard)::RectangleBoard: $crate::board::RectangleBoard,
+            ($crate::board::BaseBoard)::HexBoard: $crate::board::HexBoard,
+            ($crate::board::BaseBoard)::TriangleBoard: $crate::board::TriangleBoard,
         }
     };
 
@@ -58,7 +80,7 @@ macro_rules! for_each_board {
 
 for_each_board! {
     p::x, t =>
-    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
     pub enum BaseBoard {
         $($x($t)),*
     }
@@ -72,7 +94,7 @@ for_each_board! {
         }
     }
 
-    $($crate::impl_wrap_base!(BaseBoard::$x($t)))*;
+    $($crate::impl_wrap_base!(BaseBoard::$x($t));)*
 }
 
 /// A board in the path game, parameterized by player location (port) type, tile location type, and tile kind type
@@ -100,6 +122,15 @@ pub trait Board: Clone + Debug + Serialize + for<'a> Deserialize<'a> {
     /// The tile locations around a port, in no particular order
     fn port_locs(&self, port: &Self::Port) -> Vec<Self::TLoc>;
 
+    /// The tile location a path continues into after leaving location `from` through port `port`,
+    /// given which locations have tiles on them. None if the path leaves the board there.
+    /// By default, this prefers the first other location around the port that has a tile,
+    /// so it's only unambiguous when a port touches at most two locations.
+    fn next_loc(&self, port: &Self::Port, from: &Self::TLoc, has_tile: &dyn Fn(&Self::TLoc) -> bool) -> Option<Self::TLoc> {
+        let locs = self.port_locs(port).into_iter().filter(|loc| loc != from).collect_vec();
+        locs.iter().find(|loc| has_tile(loc)).or_else(|| locs.first()).cloned()
+    }
+
     /// Tile configuration for the board, used for generating tiles
     fn tile_config(&self) -> Self::TileConfig;
 }
@@ -109,7 +140,7 @@ pub trait Board: Clone + Debug + Serialize + for<'a> Deserialize<'a> {
 pub struct PortsPerEdgeTileConfig(pub u32);
 
 /// A rectangular board with square tiles.
-#[derive(Clone, Debug, Serialize, Deserialize, CopyGetters)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, CopyGetters)]
 pub struct RectangleBoard {
     #[getset(get_copy = "pub")]
     width: u32,
@@ -178,6 +209,194 @@ impl Board for RectangleBoard {
     }
 }
 
+/// A hexagon-shaped board with flat-topped hexagonal tiles, using axial coordinates.
+/// The center tile is at the origin, the q axis points to the lower right, and the r axis points down.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, CopyGetters)]
+pub struct HexBoard {
+    /// Number of rings of tiles around the center tile
+    #[getset(get_copy = "pub")]
+    radius: u32,
+    #[getset(get_copy = "pub")]
+    ports_per_edge: u32
+}
+
+impl HexBoard {
+    /// Directions to the neighbors of a tile, in clockwise order starting from the top edge
+    pub const DIRECTIONS: [Vec2i; 6] = [
+        Vec2i::new(0, -1),
+        Vec2i::new(1, -1),
+        Vec2i::new(1, 0),
+        Vec2i::new(0, 1),
+        Vec2i::new(-1, 1),
+        Vec2i::new(-1, 0),
+    ];
+
+    pub fn new(radius: u32, ports_per_edge: u32) -> Self {
+        Self { radius, ports_per_edge }
+    }
+
+    /// Whether a tile location is on the board
+    pub fn contains(&self, loc: &Pt2i) -> bool {
+        let radius = self.radius as i32;
+        loc.x.abs() <= radius && loc.y.abs() <= radius && (loc.x + loc.y).abs() <= radius
+    }
+
+    /// All the tile locations on the board, in no particular order
+    pub fn all_locs(&self) -> Vec<Pt2i> {
+        let radius = self.radius as i32;
+        iproduct!(-radius..=radius, -radius..=radius)
+            .map(|(q, r)| point![q, r])
+            .filter(|loc| self.contains(loc))
+            .collect_vec()
+    }
+
+    /// The ports on edge `edge` of the tile at `loc`, in clockwise order.
+    /// Each edge is owned by the tile that has it as edge 0, 1, or 2.
+    fn edge_ports(&self, loc: &Pt2i, edge: u32) -> Vec<(Pt2i, u32)> {
+        if edge < 3 {
+            (0..self.ports_per_edge).map(|i| (*loc, edge * self.ports_per_edge + i)).collect_vec()
+        } else {
+            let owner = loc + Self::DIRECTIONS[edge as usize];
+            (0..self.ports_per_edge).rev().map(|i| (owner, (edge - 3) * self.ports_per_edge + i)).collect_vec()
+        }
+    }
+}
+
+impl Board for HexBoard {
+    /// Axial coordinates of a tile
+    type TLoc = Pt2i;
+    /// Coordinates of the tile that owns the port's edge,
+    /// followed by the port's index clockwise around that tile, starting from the top edge
+    type Port = (Pt2i, u32);
+    type Kind = ();
+    type TileConfig = PortsPerEdgeTileConfig;
+
+    fn all_ports(&self) -> Vec<Self::Port> {
+        self.all_locs().into_iter()
+            .flat_map(|loc| self.loc_ports(&loc))
+            .unique()
+            .collect_vec()
+    }
+
+    fn boundary_ports(&self) -> Vec<Self::Port> {
+        self.all_ports().into_iter()
+            .filter(|port| self.port_locs(port).len() == 1)
+            .collect_vec()
+    }
+
+    fn all_kinds(&self) -> Vec<Self::Kind> {
+        vec![()]
+    }
+
+    fn kind_at(&self, _: &Self::TLoc) -> Self::Kind {
+    }
+
+    fn loc_ports(&self, loc: &Self::TLoc) -> Vec<Self::Port> {
+        (0..6).flat_map(|edge| self.edge_ports(loc, edge)).collect_vec()
+    }
+
+    fn port_locs(&self, port: &Self::Port) -> Vec<Self::TLoc> {
+        let edge = port.1 / self.ports_per_edge;
+        IntoIterator::into_iter([port.0, port.0 + Self::DIRECTIONS[edge as usize]])
+            .filter(|loc| self.contains(loc))
+            .collect_vec()
+    }
+
+    fn tile_config(&self) -> Self::TileConfig {
+        PortsPerEdgeTileConfig(self.ports_per_edge)
+    }
+}
+
+/// A board shaped like a large upward-pointing triangle, tiled with alternating upward and downward triangles.
+/// Row `y` from the top has `y + 1` upward triangles and `y` downward triangles between them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize, CopyGetters)]
+pub struct TriangleBoard {
+    /// Number of tiles along each side of the board
+    #[getset(get_copy = "pub")]
+    size: u32,
+    #[getset(get_copy = "pub")]
+    ports_per_edge: u32
+}
+
+impl TriangleBoard {
+    pub fn new(size: u32, ports_per_edge: u32) -> Self {
+        Self { size, ports_per_edge }
+    }
+
+    /// All the tile locations on the board, in no particular order
+    pub fn all_locs(&self) -> Vec<(Pt2u, bool)> {
+        iproduct!(0..self.size, [false, true])
+            .flat_map(|(y, down)| (0..y + if down { 0 } else { 1 }).map(move |x| (point![x, y], down)))
+            .collect_vec()
+    }
+
+    /// The upward triangle that owns edge `edge` of a downward triangle.
+    /// The upward triangle has the same edge number, but sees the ports on it in the opposite order.
+    fn down_edge_owner(loc: &Pt2u, edge: u32) -> Pt2u {
+        match edge {
+            0 => point![loc.x, loc.y - 1],
+            1 => point![loc.x + 1, loc.y],
+            _ => *loc,
+        }
+    }
+}
+
+impl Board for TriangleBoard {
+    /// Coordinates of the upward triangle at or to the left of a tile,
+    /// followed by whether the tile is the downward triangle to its right
+    type TLoc = (Pt2u, bool);
+    /// Coordinates of the upward triangle that owns the port's edge,
+    /// followed by the port's index clockwise around that triangle, starting from the bottom edge
+    type Port = (Pt2u, u32);
+    type Kind = ();
+    type TileConfig = PortsPerEdgeTileConfig;
+
+    fn all_ports(&self) -> Vec<Self::Port> {
+        iproduct!(0..self.size, 0..3 * self.ports_per_edge)
+            .flat_map(|(y, i)| (0..=y).map(move |x| (point![x, y], i)))
+            .collect_vec()
+    }
+
+    fn boundary_ports(&self) -> Vec<Self::Port> {
+        self.all_ports().into_iter()
+            .filter(|port| self.port_locs(port).len() == 1)
+            .collect_vec()
+    }
+
+    fn all_kinds(&self) -> Vec<Self::Kind> {
+        vec![()]
+    }
+
+    fn kind_at(&self, _: &Self::TLoc) -> Self::Kind {
+    }
+
+    fn loc_ports(&self, loc: &Self::TLoc) -> Vec<Self::Port> {
+        let (pos, down) = *loc;
+        if down {
+            (0..3).flat_map(|edge| {
+                let owner = Self::down_edge_owner(&pos, edge);
+                (0..self.ports_per_edge).rev().map(move |i| (owner, edge * self.ports_per_edge + i))
+            }).collect_vec()
+        } else {
+            (0..3 * self.ports_per_edge).map(|i| (pos, i)).collect_vec()
+        }
+    }
+
+    fn port_locs(&self, port: &Self::Port) -> Vec<Self::TLoc> {
+        let pos = port.0;
+        let down = match port.1 / self.ports_per_edge {
+            0 => (pos.y + 1 < self.size).then(|| point![pos.x, pos.y + 1]),
+            1 => (pos.x > 0).then(|| point![pos.x - 1, pos.y]),
+            _ => (pos.x < pos.y).then_some(pos),
+        };
+        chain!([(pos, false)], down.map(|down| (down, true))).collect_vec()
+    }
+
+    fn tile_config(&self) -> Self::TileConfig {
+        PortsPerEdgeTileConfig(self.ports_per_edge)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,4 +460,62 @@ mod tests {
         expected.sort_by_key(|vec| *AsRef::<[u32; 2]>::as_ref(&vec.coords));
         assert_eq!(locs, expected);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_hex_board_size() {
+        let board = HexBoard::new(2, 2);
+        assert_eq!(board.all_locs().len(), 19);
+        // Each tile has 6 edges with 2 ports each, and interior edges are shared
+        assert_eq!(board.boundary_ports().len(), 6 * 5 * 2);
+        assert_eq!(board.all_ports().len(), (19 * 12 + 60) / 2);
+    }
+
+    #[test]
+    fn test_hex_board_shared_edge() {
+        let board = HexBoard::new(1, 2);
+        let center = point![0, 0];
+        // Every edge of the center tile is shared with the neighbor in that direction,
+        // which sees the ports in the opposite order
+        for (edge, dir) in HexBoard::DIRECTIONS.iter().enumerate() {
+            let ports = board.loc_ports(&center)[edge * 2..edge * 2 + 2].to_vec();
+            let opposite = (edge + 3) % 6;
+            let neighbor_ports = board.loc_ports(&(center + dir))[opposite * 2..opposite * 2 + 2].to_vec();
+            assert_eq!(ports, neighbor_ports.into_iter().rev().collect_vec());
+
+            let mut locs = board.port_locs(&ports[0]);
+            locs.sort_by_key(|loc| (loc.x, loc.y));
+            let mut expected = vec![center, center + dir];
+            expected.sort_by_key(|loc| (loc.x, loc.y));
+            assert_eq!(locs, expected);
+        }
+    }
+
+    #[test]
+    fn test_triangle_board_size() {
+        let board = TriangleBoard::new(3, 2);
+        assert_eq!(board.all_locs().len(), 9);
+        // Each side of the board has 3 edges with 2 ports each
+        assert_eq!(board.boundary_ports().len(), 3 * 3 * 2);
+        assert_eq!(board.all_ports().len(), 6 * 3 * 2);
+    }
+
+    #[test]
+    fn test_triangle_board_shared_edges() {
+        let board = TriangleBoard::new(3, 2);
+        for loc in board.all_locs() {
+            let ports = board.loc_ports(&loc);
+            assert_eq!(ports.len(), 6);
+            for port in ports {
+                assert!(board.port_locs(&port).contains(&loc));
+            }
+        }
+
+        // The downward triangle in the middle row shares each edge with a different upward triangle
+        let down = (point![0, 1], true);
+        let owners = board.loc_ports(&down).into_iter().map(|(owner, _)| owner).dedup().collect_vec();
+        assert_eq!(owners, vec![point![0, 0], point![1, 1], point![0, 1]]);
+        for port in board.loc_ports(&down) {
+            assert_eq!(board.port_locs(&port).len(), 2);
+        }
+    }
+}
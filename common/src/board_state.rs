@@ -1,4 +1,4 @@
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 use itertools::Itertools;
 
 use std::hash::Hash;
@@ -21,6 +21,12 @@ macro_rules! for_each_board_state {
             ($crate::board_state::BaseBoardState)::Normal: $crate::board_state::BoardState<
                 $crate::board::RectangleBoard, $crate::tile::RegularTile<4>
             >,
+            ($crate::board_state::BaseBoardState)::Hex: $crate::board_state::BoardState<
+                $crate::board::HexBoard, $crate::tile::RegularTile<6>
+            >,
+            ($crate::board_state::BaseBoardState)::Triangle: $crate::board_state::BoardState<
+                $crate::board::TriangleBoard, $crate::tile::RegularTile<3>
+            >,
         }
     };
 
@@ -53,7 +59,7 @@ for_each_board_state! {
         }
     }
 
-    $($crate::impl_wrap_base!(BaseBoardState::$x($t)))*;
+    $($crate::impl_wrap_base!(BaseBoardState::$x($t));)*
 }
 
 /// The state of the board
@@ -114,46 +120,160 @@ where
     }
 
     /// Move players that touch a tile along their respective paths until they face a dead end.
+    /// Only the tokens of `players` are on the board; the rest belong to eliminated players.
+    /// If `collisions` is set, players whose tokens meet are eliminated as well.
+    /// Players stuck in a loop are eliminated instead of moving forever.
     /// Assumes the location has a tile on it.
-    /// Returns a list of newly dead players.
-    pub fn advance_players(&mut self, board: &B, loc: &B::TLoc) -> Vec<u32> {
-        // Contains tuples of player and tile location to move through.
-        // If the tile location is None, the player is done moving.
-        let mut to_advance = (0..self.players.len())
-            .flat_map(|i| self.player_port(i as u32)
-                .and_then(|p| board.loc_ports(loc).into_iter().position(|q| p == &q))
-                .map(|_| (i as u32, Some(loc.clone()))))
+    /// Returns a list of newly dead players and why they died.
+    pub fn advance_players(&mut self, board: &B, loc: &B::TLoc, players: &[u32], collisions: bool) -> Vec<(u32, DeathCause)> {
+        // Contains tuples of player and tile location to move through next
+        let mut to_advance = players.iter()
+            .filter(|player| self.player_port(**player).is_some_and(|p| board.loc_ports(loc).contains(p)))
+            .map(|player| (*player, loc.clone()))
             .collect_vec();
 
         let mut dead = vec![];
+        // The ports and locations each player entered, to detect loops
+        let mut visited = FnvHashSet::default();
+
+        while !to_advance.is_empty() {
+            let old_ports = self.players.clone();
+            let num_dead = dead.len();
+            let mut next = vec![];
+
+            for (player, loc) in &to_advance {
+                let port_in = self.player_port(*player).unwrap();
+                if !visited.insert((*player, port_in.clone(), loc.clone())) {
+                    dead.push((*player, DeathCause::Loop));
+                    continue;
+                }
 
-        // Making this change would introduce short-circuiting
-        #[allow(clippy::unnecessary_fold)]
-        while !to_advance.iter_mut().map(|(player, maybe_loc)| {
-                if let Some(loc) = maybe_loc {
-                    // Move player
-                    let port_in = self.player_port(*player).unwrap();
-                    let input = board.loc_ports(loc).into_iter().position(|p| &p == port_in).unwrap() as u32;
-                    let tile = self.tile_at(loc).unwrap().clone();
-                    let output = tile.output(input);
-                    let port_out = board.loc_ports(loc)[output as usize].clone();
-                    self.players[*player as usize] = Some(port_out.clone());
-
-                    // Figure out if they can move again
-                    // TODO: What if there's a choice?
-                    *maybe_loc = board.port_locs(&port_out).into_iter().find(|l| l != loc);
-                    if maybe_loc.is_none() {
-                        dead.push(*player);
+                // Move player
+                let input = board.loc_ports(loc).into_iter().position(|p| &p == port_in).unwrap() as u32;
+                let output = self.tile_at(loc).unwrap().output(input);
+                let port_out = board.loc_ports(loc)[output as usize].clone();
+                self.players[*player as usize] = Some(port_out.clone());
+
+                // Figure out if they can move again
+                match board.next_loc(&port_out, loc, &|l| self.tile_at(l).is_some()) {
+                    None => dead.push((*player, DeathCause::Boundary)),
+                    Some(l) => if self.tile_at(&l).is_some() {
+                        next.push((*player, l));
                     }
-                    *maybe_loc = maybe_loc.clone().filter(|l| self.tile_at(l).is_some());
-                    maybe_loc.is_none()
-                } else {
-                    true
                 }
-                // Don't use `all` to avoid short-circuiting
-            }).fold(true, |b1, b2| b1 && b2)
-        {}
+            }
+
+            if collisions {
+                let moved_through = |player: u32| to_advance.iter().find(|(p, _)| *p == player).map(|(_, l)| l);
+                // Players who ran off the board this step can still collide on the way out
+                let alive = players.iter().copied()
+                    .filter(|player| !dead[..num_dead].iter().any(|(p, _)| p == player))
+                    .collect_vec();
+
+                // Tokens collide if they end up on the same port,
+                // or if they swap ports by going through the same tile in opposite directions
+                let collided = alive.iter().copied()
+                    .filter(|a| alive.iter().any(|b| a != b && (
+                        self.players[*a as usize] == self.players[*b as usize] ||
+                        moved_through(*a).is_some() && moved_through(*a) == moved_through(*b) &&
+                            self.players[*a as usize] == old_ports[*b as usize] &&
+                            self.players[*b as usize] == old_ports[*a as usize]
+                    )))
+                    .collect_vec();
+
+                next.retain(|(player, _)| !collided.contains(player));
+                dead.retain(|(player, _)| !collided.contains(player));
+                dead.extend(collided.into_iter().map(|player| (player, DeathCause::Collision)));
+            }
+
+            to_advance = next;
+        }
 
         dead
     }
-}
\ No newline at end of file
+}
+
+/// Why a player was eliminated
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DeathCause {
+    /// Their token ran off the edge of the board
+    Boundary,
+    /// Their token met another player's token
+    Collision,
+    /// Their token got stuck going around a loop forever
+    Loop,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{board::RectangleBoard, game::PathGame, math::{pt2, Pt2u, Vec2u}, tile::RegularTile};
+
+    use super::*;
+
+    /// A board of `width` straight tiles in a row with 1 port per edge
+    fn straight_row(width: u32) -> (RectangleBoard, BoardState<RectangleBoard, RegularTile<4>>) {
+        let board = RectangleBoard::new(width, 1, 1);
+        let game = PathGame::<_, RegularTile<4>>::new(board.clone(), vec![], [((), 1)]);
+        let mut state = BoardState::new(&game, 2);
+        for x in 0..width {
+            state.place_tile(RegularTile::<4>::new(vec![2, 3, 0, 1]), &pt2(x, 0));
+        }
+        (board, state)
+    }
+
+    fn vertical_port(x: u32) -> (Pt2u, Vec2u) {
+        (pt2(x, 0), Vec2u::new(0, 1))
+    }
+
+    #[test]
+    fn test_board_state_collision_same_port() {
+        let (board, mut state) = straight_row(2);
+        state.place_player(0, &vertical_port(0));
+        state.place_player(1, &vertical_port(2));
+
+        // Player 0 runs into player 1, who's waiting at the edge of the board
+        let mut collided = state.clone();
+        assert_eq!(collided.advance_players(&board, &pt2(0, 0), &[0, 1], true),
+            vec![(0, DeathCause::Collision), (1, DeathCause::Collision)]);
+
+        let mut passed = state.clone();
+        assert_eq!(passed.advance_players(&board, &pt2(0, 0), &[0, 1], false), vec![(0, DeathCause::Boundary)]);
+
+        // Eliminated players' tokens are off the board
+        assert_eq!(state.advance_players(&board, &pt2(0, 0), &[0], true), vec![(0, DeathCause::Boundary)]);
+    }
+
+    #[test]
+    fn test_board_state_collision_swap() {
+        let (board, mut state) = straight_row(1);
+        state.place_player(0, &vertical_port(0));
+        state.place_player(1, &vertical_port(1));
+
+        // The players meet head-on in the middle of the tile
+        let mut collided = state.clone();
+        assert_eq!(collided.advance_players(&board, &pt2(0, 0), &[0, 1], true),
+            vec![(0, DeathCause::Collision), (1, DeathCause::Collision)]);
+
+        assert_eq!(state.advance_players(&board, &pt2(0, 0), &[0, 1], false),
+            vec![(0, DeathCause::Boundary), (1, DeathCause::Boundary)]);
+    }
+
+    #[test]
+    fn test_board_state_loop() {
+        let board = RectangleBoard::new(2, 2, 1);
+        let game = PathGame::<_, RegularTile<4>>::new(board.clone(), vec![], [((), 1)]);
+        let mut state = BoardState::new(&game, 1);
+
+        // Tiles that form a circle around the center of the board
+        let top_left = RegularTile::<4>::new(vec![3, 2, 1, 0]);
+        let top_right = RegularTile::<4>::new(vec![1, 0, 3, 2]);
+        state.place_tile(top_left.clone(), &pt2(0, 0));
+        state.place_tile(top_right.clone(), &pt2(1, 0));
+        state.place_tile(top_right, &pt2(0, 1));
+        state.place_tile(top_left, &pt2(1, 1));
+        state.place_player(0, &vertical_port(1));
+
+        assert_eq!(state.advance_players(&board, &pt2(0, 0), &[0], false), vec![(0, DeathCause::Loop)]);
+        assert_eq!(state.player_port(0), Some(&vertical_port(1)));
+    }
+}
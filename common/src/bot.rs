@@ -0,0 +1,113 @@
+use std::fmt::{self, Display};
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::board::BasePort;
+use crate::game::BaseGame;
+use crate::game_state::{BaseGameState, BaseTilePlacement};
+use crate::strategy::{choose_best, hand, num_safe_placements, GreedySurvivalStrategy, RandomStrategy, Strategy};
+
+/// How well a computer player plays
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BotLevel {
+    /// Makes random legal moves
+    Easy,
+    /// Avoids eliminating itself when it can
+    #[default]
+    Medium,
+    /// Avoids eliminating itself, and keeps as many ways to survive its next turn as it can
+    Hard,
+}
+
+impl BotLevel {
+    pub const ALL: [BotLevel; 3] = [BotLevel::Easy, BotLevel::Medium, BotLevel::Hard];
+}
+
+impl Display for BotLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Easy => write!(f, "Easy"),
+            Self::Medium => write!(f, "Medium"),
+            Self::Hard => write!(f, "Hard"),
+        }
+    }
+}
+
+/// A computer player of some level
+#[derive(Clone, Debug)]
+pub struct Bot<R: Rng> {
+    level: BotLevel,
+    rng: R,
+}
+
+impl<R: Rng> Bot<R> {
+    pub fn new(level: BotLevel, rng: R) -> Self {
+        Self { level, rng }
+    }
+}
+
+impl<R: Rng> Strategy for Bot<R> {
+    fn choose_port(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BasePort> {
+        match self.level {
+            BotLevel::Easy => RandomStrategy::new(&mut self.rng).choose_port(game, state),
+            BotLevel::Medium | BotLevel::Hard => GreedySurvivalStrategy::new(&mut self.rng).choose_port(game, state),
+        }
+    }
+
+    fn choose_tile_placement(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BaseTilePlacement> {
+        match self.level {
+            BotLevel::Easy => RandomStrategy::new(&mut self.rng).choose_tile_placement(game, state),
+            BotLevel::Medium => GreedySurvivalStrategy::new(&mut self.rng).choose_tile_placement(game, state),
+            BotLevel::Hard => {
+                let player = state.player_expect();
+                choose_best(state.legal_tile_placements(game, player), |(kind, index, action, loc)| {
+                    let mut after = state.clone();
+                    let result = after.take_turn_placing_tile(game, kind, *index, action, loc);
+                    let alive = after.player_state(player).is_some();
+
+                    let mut tiles = hand(state, player);
+                    tiles.retain(|tile| Some(tile) != state.hand_tile(player, kind, *index).as_ref());
+                    let num_safe = if alive { num_safe_placements(game, &after, player, &tiles) } else { 0 };
+                    (alive, num_safe, result.dead_players().len())
+                }, &mut self.rng)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_settings::GameSettings;
+    use crate::pcg64_seeded;
+    use crate::strategy::play_out;
+
+    use super::*;
+
+    /// Plays a game between bots and returns the final state
+    fn play(levels: &[BotLevel], seed: u64) -> BaseGameState {
+        let game = GameSettings::default().build().unwrap();
+        let mut state = game.new_state_seeded(levels.len() as u32, seed);
+        let mut bots = levels.iter()
+            .enumerate()
+            .map(|(player, level)| Bot::new(*level, pcg64_seeded(seed + player as u64)))
+            .collect::<Vec<_>>();
+        play_out(&game, &mut state, &mut bots);
+        state
+    }
+
+    #[test]
+    fn test_bots_finish_games() {
+        for seed in 0..3 {
+            assert!(play(&[BotLevel::Easy, BotLevel::Medium, BotLevel::Hard], seed).game_over());
+        }
+    }
+
+    #[test]
+    fn test_hard_bot_beats_easy_bot() {
+        let wins = (0..20)
+            .filter(|seed| play(&[BotLevel::Hard, BotLevel::Easy], *seed).won(0))
+            .count();
+        assert!(wins > 10, "Hard bot won {} of 20 games", wins);
+    }
+}
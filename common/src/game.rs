@@ -5,9 +5,10 @@ use enum_dispatch::enum_dispatch;
 use fnv::FnvHashMap;
 use serde::{Deserialize, Serialize};
 
-use crate::{board::{Board, Port, TLoc}, game_state::GameState, tile::{GAct, Kind, Tile}};
+use crate::{board::{Board, Port, TLoc}, game_state::GameState, tile::{GAct, Kind, Tile, TileCatalog}};
 use crate::game_state::BaseGameState;
-use crate::board::BaseBoard;
+use crate::board::{BaseBoard, BasePort};
+use crate::tile::BaseTileCatalog;
 use crate::WrapBase;
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
@@ -17,6 +18,8 @@ pub struct GameId(pub u32);
 pub trait GenericGame {
     fn new_state(&self, num_players: u32) -> BaseGameState;
 
+    fn new_state_seeded(&self, num_players: u32, seed: u64) -> BaseGameState;
+
     fn board(&self) -> BaseBoard;
 }
 
@@ -30,6 +33,10 @@ where
         GameState::new(self, num_players).into()
     }
 
+    fn new_state_seeded(&self, num_players: u32, seed: u64) -> BaseGameState {
+        GameState::new_seeded(self, num_players, seed).into()
+    }
+
     fn board(&self) -> BaseBoard {
         self.board().clone().into()
     }
@@ -43,6 +50,8 @@ macro_rules! for_each_game {
         }
         __mac! {
             ($crate::game::BaseGame)::Normal: $crate::game::PathGame<$crate::board::RectangleBoard, $crate::tile::RegularTile<4>>,
+            ($crate::game::BaseGame)::Hex: $crate::game::PathGame<$crate::board::HexBoard, $crate::tile::RegularTile<6>>,
+            ($crate::game::BaseGame)::Triangle: $crate::game::PathGame<$crate::board::TriangleBoard, $crate::tile::RegularTile<3>>,
         }
     };
 
@@ -55,7 +64,7 @@ macro_rules! for_each_game {
 
 for_each_game! {
     p::x, t =>
-    #[derive(Clone, Debug, Serialize, Deserialize)]
+    #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
     pub enum BaseGame {
         $($x($t)),*
     }
@@ -65,12 +74,42 @@ for_each_game! {
             match self { $($($p)*::$x(s) => GameState::new(s, num_players).wrap_base()),* }
         }
 
+        /// Like `new_state`, but the draw pile is shuffled deterministically from `seed`
+        pub fn new_state_seeded(&self, num_players: u32, seed: u64) -> BaseGameState {
+            match self { $($($p)*::$x(s) => GameState::new_seeded(s, num_players, seed).wrap_base()),* }
+        }
+
         pub fn board(&self) -> BaseBoard {
             match self { $($($p)*::$x(s) => s.board().clone().wrap_base()),* }
         }
+
+        /// All the ports that players can start at
+        pub fn start_ports(&self) -> Vec<BasePort> {
+            match self { $($($p)*::$x(s) => s.start_ports().into_iter().map(|port| port.wrap_base()).collect()),* }
+        }
+
+        /// Stable ids for the tiles of the game's tile type
+        pub fn tile_catalog(&self) -> BaseTileCatalog {
+            match self { $($($p)*::$x(s) => Game::tile_catalog(s).wrap_base()),* }
+        }
+
+        /// How players place their tokens at the start of the game
+        pub fn token_placement(&self) -> TokenPlacement {
+            match self { $($($p)*::$x(s) => s.token_placement()),* }
+        }
+
+        /// Whether players can't place a tile that eliminates themselves unless every placement does
+        pub fn no_suicide(&self) -> bool {
+            match self { $($($p)*::$x(s) => s.no_suicide()),* }
+        }
+
+        /// Whether players whose tokens meet are eliminated
+        pub fn token_collisions(&self) -> bool {
+            match self { $($($p)*::$x(s) => s.token_collisions()),* }
+        }
     }
 
-    $($crate::impl_wrap_base!(BaseGame::$x($t)))*;
+    $($crate::impl_wrap_base!(BaseGame::$x($t));)*
 }
 
 pub trait Game: Clone + Debug + Serialize {
@@ -93,12 +132,36 @@ pub trait Game: Clone + Debug + Serialize {
         Self::Tile::all(self.board().tile_config())
     }
 
+    /// Stable ids for the tiles of the game's tile type
+    fn tile_catalog(&self) -> TileCatalog<Self::Tile> {
+        TileCatalog::new(self.board().tile_config())
+    }
+
     /// Tiles of some kind that a player starts with
     fn num_tiles_per_player(&self, kind: &Self::Kind) -> u32;
+
+    /// How players place their tokens at the start of the game
+    fn token_placement(&self) -> TokenPlacement;
+
+    /// Whether players can't place a tile that eliminates themselves unless every placement does
+    fn no_suicide(&self) -> bool;
+
+    /// Whether players whose tokens meet are eliminated
+    fn token_collisions(&self) -> bool;
+}
+
+/// How players place their tokens at the start of the game
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TokenPlacement {
+    /// Players place their tokens one at a time in turn order
+    #[default]
+    Sequential,
+    /// Players place their tokens whenever they want, all at the same time
+    Simultaneous,
 }
 
 /// A definition for a path game
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct PathGame<B: Board, T> {
     #[serde(bound = "")]
     board: B,
@@ -106,6 +169,11 @@ pub struct PathGame<B: Board, T> {
     start_ports: Vec<<B as Board>::Port>,
     #[serde(bound = "")]
     tiles_per_player: FnvHashMap<<B as Board>::Kind, u32>,
+    token_placement: TokenPlacement,
+    no_suicide: bool,
+    token_collisions: bool,
+    /// The tiles in the draw pile, or None for one copy of every tile
+    deck: Option<Vec<T>>,
     phantom: PhantomData<T>,
 }
 
@@ -122,9 +190,37 @@ where
             board,
             start_ports,
             tiles_per_player: tiles_per_player.into_iter().collect(),
+            token_placement: TokenPlacement::default(),
+            no_suicide: false,
+            token_collisions: false,
+            deck: None,
             phantom: PhantomData,
         }
     }
+
+    /// Set how players place their tokens using the builder pattern
+    pub fn with_token_placement(mut self, token_placement: TokenPlacement) -> Self {
+        self.token_placement = token_placement;
+        self
+    }
+
+    /// Set whether players can't eliminate themselves unless forced, using the builder pattern
+    pub fn with_no_suicide(mut self, no_suicide: bool) -> Self {
+        self.no_suicide = no_suicide;
+        self
+    }
+
+    /// Set whether players whose tokens meet are eliminated, using the builder pattern
+    pub fn with_token_collisions(mut self, token_collisions: bool) -> Self {
+        self.token_collisions = token_collisions;
+        self
+    }
+
+    /// Set the tiles in the draw pile using the builder pattern. None means one copy of every tile.
+    pub fn with_deck(mut self, deck: Option<Vec<T>>) -> Self {
+        self.deck = deck;
+        self
+    }
 }
 
 impl<K, C, B, T> Game for PathGame<B, T>
@@ -150,7 +246,23 @@ where
         self.start_ports.clone()
     }
 
+    fn all_tiles(&self) -> Vec<Self::Tile> {
+        self.deck.clone().unwrap_or_else(|| T::all(self.board.tile_config()))
+    }
+
     fn num_tiles_per_player(&self, kind: &Self::Kind) -> u32 {
         self.tiles_per_player[kind]
     }
+
+    fn token_placement(&self) -> TokenPlacement {
+        self.token_placement
+    }
+
+    fn no_suicide(&self) -> bool {
+        self.no_suicide
+    }
+
+    fn token_collisions(&self) -> bool {
+        self.token_collisions
+    }
 }
\ No newline at end of file
@@ -0,0 +1,434 @@
+use std::fmt::{Debug, Display};
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+use crate::board::{BasePort, Board, HexBoard, Port, RectangleBoard, TriangleBoard};
+use crate::game::{BaseGame, Game, PathGame, TokenPlacement};
+use crate::tile::{BaseKind, Kind, RegularTile, Tile, TileCatalog, TileConfigError};
+use crate::WrapBase;
+
+/// Which ports players can place their tokens on at the start of the game
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StartPorts {
+    /// Ports on the boundary of the board
+    Boundary,
+    /// Every port on the board
+    All,
+    /// A specific list of ports
+    Custom(Vec<BasePort>),
+}
+
+/// The shape of the board, which also decides the shape of the tiles
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BoardShape {
+    /// A rectangular board with square tiles
+    Rectangle{ width: u32, height: u32 },
+    /// A hexagon-shaped board with hexagonal tiles and `radius` rings of tiles around the center tile
+    Hexagon{ radius: u32 },
+    /// A triangle-shaped board with triangular tiles and `size` tiles along each side
+    Triangle{ size: u32 },
+}
+
+/// How long players get to make their moves
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeControl {
+    /// Each move must be made within `seconds`
+    PerMove{ seconds: u32 },
+    /// Like a chess clock, each player has `budget_seconds` for the whole game
+    /// and gains `increment_seconds` after each move
+    Clock{ budget_seconds: u32, increment_seconds: u32 },
+}
+
+/// What happens to a player who runs out of time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeoutPolicy {
+    /// A random legal move is made for them: a random start port, or a random tile placement
+    #[default]
+    RandomMove,
+    /// They're eliminated
+    Eliminate,
+}
+
+/// Settings chosen when creating a game. These get validated before a game is built from them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameSettings {
+    pub board: BoardShape,
+    pub ports_per_edge: u32,
+    /// Tiles of each kind that a player starts with
+    pub tiles_per_player: Vec<(BaseKind, u32)>,
+    pub start_ports: StartPorts,
+    pub min_players: u32,
+    pub max_players: u32,
+    pub token_placement: TokenPlacement,
+    /// Whether players can't place a tile that eliminates themselves unless every placement does
+    pub no_suicide: bool,
+    /// Whether players whose tokens meet are eliminated
+    pub token_collisions: bool,
+    /// None if players can take as long as they want
+    pub time_control: Option<TimeControl>,
+    pub timeout_policy: TimeoutPolicy,
+    /// Tiles in the draw pile, as tile catalog ids with how many copies of each.
+    /// None for one copy of every tile.
+    pub deck: Option<Vec<(u32, u32)>>,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            board: BoardShape::Rectangle{ width: 6, height: 6 },
+            ports_per_edge: 2,
+            tiles_per_player: vec![(().wrap_base(), 3)],
+            start_ports: StartPorts::Boundary,
+            min_players: 2,
+            max_players: 8,
+            token_placement: TokenPlacement::default(),
+            no_suicide: false,
+            token_collisions: false,
+            time_control: None,
+            timeout_policy: TimeoutPolicy::default(),
+            deck: None,
+        }
+    }
+}
+
+/// Why a game can't be built from some settings
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameSettingsError {
+    /// The board has no tile locations
+    EmptyBoard,
+    /// The board is wider or taller than allowed, in tiles
+    BoardTooLarge{ max: u32 },
+    /// Tiles can't be generated for the board
+    InvalidTiles(TileConfigError),
+    /// A kind of tile the board uses has no number of tiles per player
+    MissingKind(BaseKind),
+    /// A kind of tile was given that the board doesn't use, or was given twice
+    UnexpectedKind(BaseKind),
+    /// Players wouldn't start with any tiles
+    EmptyHand,
+    /// There aren't enough tiles for every player to get a full hand
+    NotEnoughTiles{ needed: u32, available: u32 },
+    /// The minimum number of players is 0 or more than the maximum
+    InvalidPlayerLimits{ min: u32, max: u32 },
+    /// A start port isn't on the board, or is listed twice
+    InvalidStartPort(BasePort),
+    /// There aren't enough start ports for every player
+    NotEnoughStartPorts{ needed: u32, available: u32 },
+    /// Players would have no time to move
+    NoTime,
+    /// A deck entry has an id that's not in the tile catalog, is listed twice, or has no copies
+    InvalidDeckEntry{ id: u32 },
+    /// The deck has more tiles than allowed
+    DeckTooLarge{ max: u32 },
+}
+
+impl Display for GameSettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyBoard => write!(f, "the board must be at least 1 tile wide and tall"),
+            Self::BoardTooLarge{ max } => write!(f, "the board can be at most {} tiles wide and tall", max),
+            Self::InvalidTiles(err) => write!(f, "invalid ports per edge: {}", err),
+            Self::MissingKind(kind) => write!(f, "missing number of tiles per player for kind {:?}", kind),
+            Self::UnexpectedKind(kind) => write!(f, "unexpected number of tiles per player for kind {:?}", kind),
+            Self::EmptyHand => write!(f, "players must start with at least 1 tile"),
+            Self::NotEnoughTiles{ needed, available } =>
+                write!(f, "the deck has {} tiles, but full hands for every player need {}", available, needed),
+            Self::InvalidPlayerLimits{ min, max } =>
+                write!(f, "invalid player limits: at least {} and at most {} players", min, max),
+            Self::InvalidStartPort(port) => write!(f, "invalid or duplicate start port {:?}", port),
+            Self::NotEnoughStartPorts{ needed, available } =>
+                write!(f, "there are {} start ports, but up to {} players need one each", available, needed),
+            Self::NoTime => write!(f, "players must have some time to move"),
+            Self::InvalidDeckEntry{ id } => write!(f, "invalid, duplicate or empty deck entry for tile {}", id),
+            Self::DeckTooLarge{ max } => write!(f, "the deck can have at most {} tiles", max),
+        }
+    }
+}
+
+impl GameSettings {
+    /// Maximum width and height of the board
+    pub const MAX_BOARD_SIZE: u32 = 20;
+    /// Maximum number of tiles in a custom deck
+    pub const MAX_DECK_SIZE: u32 = 1000;
+
+    /// Validates the settings and builds a game from them.
+    pub fn build(&self) -> Result<BaseGame, GameSettingsError> {
+        match self.board {
+            BoardShape::Rectangle{ width, height } => {
+                if width == 0 || height == 0 {
+                    return Err(GameSettingsError::EmptyBoard);
+                }
+                if width > Self::MAX_BOARD_SIZE || height > Self::MAX_BOARD_SIZE {
+                    return Err(GameSettingsError::BoardTooLarge{ max: Self::MAX_BOARD_SIZE });
+                }
+
+                let board = RectangleBoard::new(width, height, self.ports_per_edge);
+                self.build_path_game::<_, _, _, RegularTile<4>>(board).map(|game| game.wrap_base())
+            }
+
+            BoardShape::Hexagon{ radius } => {
+                // Checked, since a huge radius would overflow
+                let diameter = radius.checked_mul(2).and_then(|diameter| diameter.checked_add(1));
+                if diameter.is_none_or(|diameter| diameter > Self::MAX_BOARD_SIZE) {
+                    return Err(GameSettingsError::BoardTooLarge{ max: Self::MAX_BOARD_SIZE });
+                }
+
+                let board = HexBoard::new(radius, self.ports_per_edge);
+                self.build_path_game::<_, _, _, RegularTile<6>>(board).map(|game| game.wrap_base())
+            }
+
+            BoardShape::Triangle{ size } => {
+                if size == 0 {
+                    return Err(GameSettingsError::EmptyBoard);
+                }
+                if size > Self::MAX_BOARD_SIZE {
+                    return Err(GameSettingsError::BoardTooLarge{ max: Self::MAX_BOARD_SIZE });
+                }
+
+                let board = TriangleBoard::new(size, self.ports_per_edge);
+                self.build_path_game::<_, _, _, RegularTile<3>>(board).map(|game| game.wrap_base())
+            }
+        }
+    }
+
+    /// Validates the settings that don't depend on the shape of the board, and builds a game on `board`.
+    fn build_path_game<K, C, B, T>(&self, board: B) -> Result<PathGame<B, T>, GameSettingsError>
+    where
+        K: Kind,
+        C: Clone + Debug,
+        B: Board<Kind = K, TileConfig = C>,
+        T: Tile<Kind = K, TileConfig = C>
+    {
+        if self.min_players == 0 || self.min_players > self.max_players {
+            return Err(GameSettingsError::InvalidPlayerLimits{ min: self.min_players, max: self.max_players });
+        }
+        if let Some(TimeControl::PerMove{ seconds: 0 } | TimeControl::Clock{ budget_seconds: 0, .. }) = self.time_control {
+            return Err(GameSettingsError::NoTime);
+        }
+
+        T::check_config(&board.tile_config()).map_err(GameSettingsError::InvalidTiles)?;
+
+        let mut tiles_per_player = vec![];
+        for (kind, num) in &self.tiles_per_player {
+            let kind = K::unwrap_base_ref(kind);
+            if !board.all_kinds().contains(kind) || tiles_per_player.iter().any(|(k, _)| k == kind) {
+                return Err(GameSettingsError::UnexpectedKind(kind.clone().wrap_base()));
+            }
+            tiles_per_player.push((kind.clone(), *num));
+        }
+        if let Some(kind) = board.all_kinds().into_iter().find(|kind| tiles_per_player.iter().all(|(k, _)| k != kind)) {
+            return Err(GameSettingsError::MissingKind(kind.wrap_base()));
+        }
+        // None if the hand is too large to count, which no deck has enough tiles for
+        let hand_size = tiles_per_player.iter().try_fold(0u32, |sum, (_, num)| sum.checked_add(*num));
+        if hand_size == Some(0) {
+            return Err(GameSettingsError::EmptyHand);
+        }
+
+        let start_ports = match &self.start_ports {
+            StartPorts::Boundary => board.boundary_ports(),
+            StartPorts::All => board.all_ports(),
+            StartPorts::Custom(ports) => {
+                let all_ports = board.all_ports();
+                if let Some(port) = ports.iter().find(|port| B::Port::try_unwrap_base_ref(port).is_none()) {
+                    return Err(GameSettingsError::InvalidStartPort(port.clone()));
+                }
+                let ports = ports.iter().map(|port| B::Port::unwrap_base_ref(port).clone()).collect_vec();
+                if let Some(port) = ports.iter().duplicates().next()
+                    .or_else(|| ports.iter().find(|port| !all_ports.contains(port)))
+                {
+                    return Err(GameSettingsError::InvalidStartPort(port.clone().wrap_base()));
+                }
+                ports
+            }
+        };
+        if (start_ports.len() as u32) < self.max_players {
+            return Err(GameSettingsError::NotEnoughStartPorts{
+                needed: self.max_players,
+                available: start_ports.len() as u32,
+            });
+        }
+
+        let deck = match &self.deck {
+            Some(entries) => Some(self.build_deck::<K, C, B, T>(&board, entries)?),
+            None => None,
+        };
+
+        let game = PathGame::<B, T>::new(board, start_ports, tiles_per_player)
+            .with_token_placement(self.token_placement)
+            .with_no_suicide(self.no_suicide)
+            .with_token_collisions(self.token_collisions)
+            .with_deck(deck);
+        let available = game.all_tiles().len() as u32;
+        let needed = hand_size.and_then(|hand_size| hand_size.checked_mul(self.max_players));
+        if needed.is_none_or(|needed| needed > available) {
+            return Err(GameSettingsError::NotEnoughTiles{ needed: needed.unwrap_or(u32::MAX), available });
+        }
+
+        Ok(game)
+    }
+
+    /// Validates a custom deck against the tile catalog and the kinds `board` uses, and lists its tiles
+    fn build_deck<K, C, B, T>(&self, board: &B, entries: &[(u32, u32)]) -> Result<Vec<T>, GameSettingsError>
+    where
+        K: Kind,
+        C: Clone + Debug,
+        B: Board<Kind = K, TileConfig = C>,
+        T: Tile<Kind = K, TileConfig = C>
+    {
+        if entries.iter().map(|(_, copies)| *copies as u64).sum::<u64>() > Self::MAX_DECK_SIZE as u64 {
+            return Err(GameSettingsError::DeckTooLarge{ max: Self::MAX_DECK_SIZE });
+        }
+
+        let catalog = TileCatalog::<T>::new(board.tile_config());
+        let duplicate = entries.iter().map(|(id, _)| id).duplicates().next();
+        let mut deck = vec![];
+        for (id, copies) in entries {
+            let tile = catalog.tile(*id)
+                .filter(|_| *copies > 0 && duplicate != Some(id))
+                .ok_or(GameSettingsError::InvalidDeckEntry{ id: *id })?;
+            if !board.all_kinds().contains(tile.kind()) {
+                return Err(GameSettingsError::UnexpectedKind(tile.kind().clone().wrap_base()));
+            }
+            deck.extend(std::iter::repeat_n(tile.clone(), *copies as usize));
+        }
+        Ok(deck)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::math::{pt2, Vec2u};
+
+    use super::*;
+
+    #[test]
+    fn test_game_settings_default() {
+        assert!(GameSettings::default().build().is_ok());
+    }
+
+    #[test]
+    fn test_game_settings_errors() {
+        let settings = GameSettings { board: BoardShape::Rectangle{ width: 0, height: 6 }, ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::EmptyBoard);
+
+        let settings = GameSettings { ports_per_edge: 0, ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::InvalidTiles(TileConfigError::NoPorts));
+
+        let settings = GameSettings { tiles_per_player: vec![], ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::MissingKind(().wrap_base()));
+
+        let settings = GameSettings { tiles_per_player: vec![(().wrap_base(), 0)], ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::EmptyHand);
+
+        let settings = GameSettings { time_control: Some(TimeControl::PerMove{ seconds: 0 }), ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::NoTime);
+
+        let settings = GameSettings { tiles_per_player: vec![(().wrap_base(), 5)], ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::NotEnoughTiles{ needed: 40, available: 35 });
+
+        let settings = GameSettings { min_players: 3, max_players: 2, ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::InvalidPlayerLimits{ min: 3, max: 2 });
+
+        // Counts that would overflow are rejected rather than wrapping around
+        let settings = GameSettings { tiles_per_player: vec![(().wrap_base(), 1 << 31)], max_players: 2, ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::NotEnoughTiles{ needed: u32::MAX, available: 35 });
+
+        let settings = GameSettings { ports_per_edge: 0x4000_0001, ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(),
+            GameSettingsError::InvalidTiles(TileConfigError::TooManyPorts{ num_ports: u32::MAX, max: 12 }));
+    }
+
+    #[test]
+    fn test_game_settings_custom_start_ports() {
+        let ports = (0..2).map(|x| (pt2(x, 0), Vec2u::new(1, 0)).wrap_base()).collect_vec();
+        let settings = GameSettings {
+            start_ports: StartPorts::Custom(ports.clone()),
+            max_players: 2,
+            ..GameSettings::default()
+        };
+        assert!(settings.build().is_ok());
+
+        let settings = GameSettings { max_players: 3, ..settings };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::NotEnoughStartPorts{ needed: 3, available: 2 });
+
+        let outside = (pt2(9, 0), Vec2u::new(1, 0)).wrap_base();
+        let settings = GameSettings {
+            start_ports: StartPorts::Custom(vec![ports[0].clone(), outside.clone()]),
+            max_players: 2,
+            ..GameSettings::default()
+        };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::InvalidStartPort(outside));
+
+        // A port of another board's type
+        let hex_port = BasePort::Pt2iU32((pt2(0, 0), 0));
+        let settings = GameSettings {
+            start_ports: StartPorts::Custom(vec![ports[0].clone(), hex_port.clone()]),
+            max_players: 2,
+            ..GameSettings::default()
+        };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::InvalidStartPort(hex_port));
+    }
+
+    #[test]
+    fn test_game_settings_hexagon() {
+        let settings = GameSettings { board: BoardShape::Hexagon{ radius: 3 }, ..GameSettings::default() };
+        let game = settings.build().unwrap();
+        assert!(matches!(game, BaseGame::Hex(_)));
+
+        let settings = GameSettings { board: BoardShape::Hexagon{ radius: 10 }, ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::BoardTooLarge{ max: GameSettings::MAX_BOARD_SIZE });
+
+        let settings = GameSettings { board: BoardShape::Hexagon{ radius: u32::MAX }, ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::BoardTooLarge{ max: GameSettings::MAX_BOARD_SIZE });
+
+        // With 1 port per edge, there are only 5 distinct hexagonal tiles
+        let settings = GameSettings {
+            board: BoardShape::Hexagon{ radius: 3 },
+            ports_per_edge: 1,
+            ..GameSettings::default()
+        };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::NotEnoughTiles{ needed: 24, available: 5 });
+    }
+
+    #[test]
+    fn test_game_settings_triangle() {
+        let settings = GameSettings {
+            board: BoardShape::Triangle{ size: 6 },
+            ports_per_edge: 4,
+            ..GameSettings::default()
+        };
+        let game = settings.build().unwrap();
+        assert!(matches!(game, BaseGame::Triangle(_)));
+
+        // Triangles with 1 port per edge would have an odd number of ports
+        let settings = GameSettings { ports_per_edge: 1, ..settings };
+        assert_eq!(settings.build().unwrap_err(),
+            GameSettingsError::InvalidTiles(TileConfigError::OddPortCount{ num_ports: 3 }));
+    }
+
+    #[test]
+    fn test_game_settings_deck() {
+        // 2 copies each of the first 12 tiles
+        let settings = GameSettings {
+            deck: Some((0..12).map(|id| (id, 2)).collect()),
+            max_players: 8,
+            ..GameSettings::default()
+        };
+        let game = settings.build().unwrap();
+        let state = game.new_state(2);
+        assert_eq!(state.num_tiles_left_by_kind()[0].1, 24 - 6);
+
+        let settings = GameSettings { deck: Some(vec![(0, 20)]), ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::NotEnoughTiles{ needed: 24, available: 20 });
+
+        for entries in [vec![(35, 30)], vec![(0, 0), (1, 30)], vec![(0, 15), (1, 1), (0, 15)]] {
+            let settings = GameSettings { deck: Some(entries), ..GameSettings::default() };
+            assert!(matches!(settings.build().unwrap_err(), GameSettingsError::InvalidDeckEntry{ .. }));
+        }
+
+        let settings = GameSettings { deck: Some(vec![(0, 600), (1, 600)]), ..GameSettings::default() };
+        assert_eq!(settings.build().unwrap_err(), GameSettingsError::DeckTooLarge{ max: GameSettings::MAX_DECK_SIZE });
+    }
+}
@@ -3,16 +3,17 @@ use std::collections::VecDeque;
 use fnv::FnvHashMap;
 use getset::{CopyGetters, Getters};
 use itertools::Itertools;
+use rand::Rng;
 use rand::prelude::SliceRandom;
 use serde::{Deserialize, Serialize};
 
 
-use crate::{board::{BasePort, BaseTLoc, Board, TLoc}, board_state::BoardState, game::{Game}, pcg64, player_state::{Looker, PlayerState}, tile::{BaseKind, Tile, Kind}};
+use crate::{board::{BasePort, BaseTLoc, Board, TLoc}, board_state::{BoardState, DeathCause}, game::{Game}, pcg64_seeded, player_state::{Looker, PlayerState}, tile::{BaseKind, Tile, Kind}};
 use crate::tile::{BaseTile, GAct, BaseGAct};
 use crate::board_state::BaseBoardState;
 use crate::board::Port;
 use crate::player_state::{BasePlayerState, LookerTag};
-use crate::game::BaseGame;
+use crate::game::{BaseGame, TokenPlacement};
 use crate::WrapBase;
 
 #[macro_export]
@@ -25,6 +26,12 @@ macro_rules! for_each_game_state {
             ($crate::game_state::BaseGameState)::Normal: $crate::game_state::GameState<
                 $crate::game::PathGame<$crate::board::RectangleBoard, $crate::tile::RegularTile<4>>
             >,
+            ($crate::game_state::BaseGameState)::Hex: $crate::game_state::GameState<
+                $crate::game::PathGame<$crate::board::HexBoard, $crate::tile::RegularTile<6>>
+            >,
+            ($crate::game_state::BaseGameState)::Triangle: $crate::game_state::GameState<
+                $crate::game::PathGame<$crate::board::TriangleBoard, $crate::tile::RegularTile<3>>
+            >,
         }
     };
 
@@ -47,6 +54,12 @@ for_each_game_state! {
             match self { $($($p)*::$x(s) => s.visible_state(looker).wrap_base()),* }
         }
 
+        /// A state the server could have, given what this state's looker can see.
+        /// Hidden tiles are filled in at random from the tiles the looker hasn't seen, and the draw pile gets reshuffled.
+        pub fn sample_hidden(&self, game: &BaseGame, rng: &mut impl Rng) -> BaseGameState {
+            match self { $($($p)*::$x(s) => s.sample_hidden(<$t as GameStateT>::Game::unwrap_base_ref(game), rng).wrap_base()),* }
+        }
+
         /// Can someone place their token on the board on port `port`?
         pub fn can_place_player(&mut self, game: &BaseGame, port: &BasePort) -> bool {
             match self { $($($p)*::$x(s) => s.can_place_player(
@@ -55,18 +68,100 @@ for_each_game_state! {
             )),* }
         }
 
+        /// The players that are allowed to place their token right now.
+        /// Empty once every player placed their token.
+        pub fn players_placing_token(&self, game: &BaseGame) -> Vec<u32> {
+            match self { $($($p)*::$x(s) => s.players_placing_token(
+                <$t as GameStateT>::Game::unwrap_base_ref(game),
+            )),* }
+        }
+
+        /// Can `player` place their token on port `port`?
+        /// Tokens can only be placed once, and only on the player's turn if tokens are placed sequentially.
+        /// False if the port is of a type the board doesn't use, since clients can send any type.
+        pub fn can_place_token(&mut self, game: &BaseGame, player: u32, port: &BasePort) -> bool {
+            match self { $($($p)*::$x(s) => <<$t as GameStateT>::Game as Game>::Port::try_unwrap_base_ref(port)
+                .is_some_and(|port| s.can_place_token(
+                    <$t as GameStateT>::Game::unwrap_base_ref(game),
+                    player,
+                    port,
+                ))),* }
+        }
+
+        /// Have `player` place their token on port `port`.
+        /// If tokens are placed sequentially, the turn advances to the next player.
+        pub fn take_turn_placing_token(&mut self, game: &BaseGame, player: u32, port: &BasePort) {
+            match self { $($($p)*::$x(s) => s.take_turn_placing_token(
+                <$t as GameStateT>::Game::unwrap_base_ref(game),
+                player,
+                Port::unwrap_base_ref(port),
+            )),* }
+        }
+
         /// Can `player` place a tile of kind `kind` from index `index` in their hand transformed by group action `action` to location `loc`?
+        /// False if the kind, action or location is of a type the game doesn't use, since clients can send any type.
         pub fn can_place_tile(&mut self, game: &BaseGame, player: u32, kind: &BaseKind, index: u32, action: &BaseGAct, loc: &BaseTLoc) -> bool {
-            match self { $($($p)*::$x(s) => s.can_place_tile(
+            match self { $($($p)*::$x(s) => {
+                let kind = <<$t as GameStateT>::Game as Game>::Kind::try_unwrap_base_ref(kind);
+                let action = <<$t as GameStateT>::Game as Game>::GAct::try_unwrap_base_ref(action);
+                let loc = <<$t as GameStateT>::Game as Game>::TLoc::try_unwrap_base_ref(loc);
+                match (kind, action, loc) {
+                    (Some(kind), Some(action), Some(loc)) =>
+                        s.can_place_tile(<$t as GameStateT>::Game::unwrap_base_ref(game), player, kind, index, action, loc),
+                    _ => false,
+                }
+            }),* }
+        }
+
+        /// The tile placements `player` is restricted to because the game forbids suicide.
+        /// None if there's no restriction, either because the game allows suicide or because every placement eliminates `player`.
+        pub fn safe_tile_placements(&self, game: &BaseGame, player: u32) -> Option<Vec<BaseTilePlacement>> {
+            match self { $($($p)*::$x(s) => s.safe_tile_placements(
                 <$t as GameStateT>::Game::unwrap_base_ref(game),
                 player,
-                <<$t as GameStateT>::Game as Game>::Kind::unwrap_base_ref(kind),
-                index,
-                <<$t as GameStateT>::Game as Game>::GAct::unwrap_base_ref(action),
-                <<$t as GameStateT>::Game as Game>::TLoc::unwrap_base_ref(loc),
+            ).map(|placements| placements.into_iter()
+                .map(|(kind, index, action, loc)| (kind.wrap_base(), index, action.wrap_base(), loc.wrap_base()))
+                .collect())),* }
+        }
+
+        /// Every tile placement with a rotated tile that `player` is allowed to make on their turn
+        pub fn legal_tile_placements(&self, game: &BaseGame, player: u32) -> Vec<BaseTilePlacement> {
+            match self { $($($p)*::$x(s) => s.legal_tile_placements(
+                <$t as GameStateT>::Game::unwrap_base_ref(game),
+                player,
+            ).into_iter()
+                .map(|(kind, index, action, loc)| (kind.wrap_base(), index, action.wrap_base(), loc.wrap_base()))
+                .collect()),* }
+        }
+
+        /// Every tile placement `player` could make with a rotated tile, ignoring whether it eliminates them.
+        pub fn tile_placements(&self, game: &BaseGame, player: u32) -> Vec<BaseTilePlacement> {
+            match self { $($($p)*::$x(s) => s.tile_placements(
+                <$t as GameStateT>::Game::unwrap_base_ref(game),
+                player,
+            ).into_iter()
+                .map(|(kind, index, action, loc)| (kind.wrap_base(), index, action.wrap_base(), loc.wrap_base()))
+                .collect()),* }
+        }
+
+        /// Whether placing `tile` at `loc` would eliminate `player`, whether or not the tile is in anyone's hand.
+        pub fn placing_eliminates(&self, game: &BaseGame, player: u32, tile: &BaseTile, loc: &BaseTLoc) -> bool {
+            match self { $($($p)*::$x(s) => s.placing_eliminates(
+                <$t as GameStateT>::Game::unwrap_base_ref(game),
+                player,
+                <<$t as GameStateT>::Game as Game>::Tile::unwrap_base_ref(tile).clone(),
+                TLoc::unwrap_base_ref(loc),
             )),* }
         }
 
+        /// The empty tile locations that `player`'s token faces, where they can place a tile
+        pub fn open_locs(&self, game: &BaseGame, player: u32) -> Vec<BaseTLoc> {
+            match self { $($($p)*::$x(s) => s.open_locs(<$t as GameStateT>::Game::unwrap_base_ref(game), player)
+                .into_iter()
+                .map(|loc| loc.wrap_base())
+                .collect()),* }
+        }
+
         /// The player looking at this state, or None if no specific person
         pub fn looker(&self) -> Looker {
             match self { $($($p)*::$x(s) => s.looker()),* }
@@ -104,6 +199,11 @@ for_each_game_state! {
             match self { $($($p)*::$x(s) => s.winners().contains(&player)),* }
         }
 
+        /// The players who won the game. Empty if the game isn't over.
+        pub fn winners(&self) -> Vec<u32> {
+            match self { $($($p)*::$x(s) => s.winners().clone()),* }
+        }
+
         /// Number of tiles left of each kind in the draw pile
         pub fn num_tiles_left_by_kind(&self) -> Vec<(BaseKind, u32)> {
             match self { $($($p)*::$x(s) => 
@@ -122,6 +222,16 @@ for_each_game_state! {
             ),* }
         }
 
+        /// The tile of kind `kind` at index `index` in `player`'s hand.
+        /// None if the player is dead or there's no such tile.
+        pub fn hand_tile(&self, player: u32, kind: &BaseKind, index: u32) -> Option<BaseTile> {
+            match self { $($($p)*::$x(s) => s.player_state(player)
+                .and_then(|state| state.tiles().get(Kind::unwrap_base_ref(kind))
+                    .and_then(|tiles| tiles.get(index as usize)))
+                .map(|tile| tile.clone().wrap_base())
+            ),* }
+        }
+
         /// Whose turn it is
         pub fn turn_player(&self) -> u32 {
             match self { $($($p)*::$x(s) => s.turn_player()),* }
@@ -132,10 +242,39 @@ for_each_game_state! {
             match self { $($($p)*::$x(s) => s.all_players_placed()),* }
         }
 
+        /// The player holding the dragon tile, who gets dealt first when tiles return to the draw pile.
+        /// None if nobody is waiting for tiles.
+        pub fn dragon(&self) -> Option<u32> {
+            match self { $($($p)*::$x(s) => s.dragon()),* }
+        }
+
         pub fn place_player(&mut self, player: u32, port: &BasePort) {
             match self { $($($p)*::$x(s) => s.place_player(player, Port::unwrap_base_ref(port))),* }
         }
 
+        /// Replaces a tile in a player's hand with its revealed version.
+        /// Used by lookers whose state only has a placeholder for that tile.
+        pub fn reveal_hand_tile(&mut self, player: u32, kind: &BaseKind, index: u32, tile: &BaseTile) {
+            match self { $($($p)*::$x(s) => s.reveal_hand_tile(
+                player,
+                Kind::unwrap_base_ref(kind),
+                index,
+                <<$t as GameStateT>::Game as Game>::Tile::unwrap_base_ref(tile).clone(),
+            )),* }
+        }
+
+        /// Eliminates `player` because they left the game.
+        /// Their tiles go back to the draw pile and get dealt out again,
+        /// and if it was their turn, the turn passes to the next player.
+        /// Assumes the player is alive.
+        /// Returns a list of tiles added to player's hands in the form (player, index, tile)
+        pub fn forfeit(&mut self, game: &BaseGame, player: u32) -> Vec<(u32, u32, BaseTile)> {
+            match self { $($($p)*::$x(s) => s.forfeit(<$t as GameStateT>::Game::unwrap_base_ref(game), player)
+                .into_iter()
+                .map(|(p, i, t)| (p, i, t.wrap_base()))
+                .collect()),* }
+        }
+
         /// Have the current player take a turn by placing a tile of kind `kind` from index `index` in their hand
         /// transformed by group action `action` to location `loc`.
         /// The turn is processed and then advances to the next player.
@@ -152,7 +291,7 @@ for_each_game_state! {
                     tile_placer: res.tile_placer,
                     tile_placed: (res.tile_placed.0, res.tile_placed.1.wrap_base()),
                     tile_loc: res.tile_loc.wrap_base(),
-                    player_ports: res.player_ports.into_iter().map(|p| p.wrap_base()).collect(),
+                    player_ports: res.player_ports.into_iter().map(|p| p.map(|p| p.wrap_base())).collect(),
                     dead_players: res.dead_players,
                     num_tiles_left: res.num_tiles_left.into_iter().map(|(k, n)| (k.wrap_base(), n)).collect(),
                     drawn_tiles: res.drawn_tiles.into_iter().map(|(p, i, t)| (p, i, t.wrap_base())).collect(),
@@ -162,9 +301,15 @@ for_each_game_state! {
         }
     }
 
-    $($crate::impl_wrap_base!(BaseGameState::$x($t)))*;
+    $($crate::impl_wrap_base!(BaseGameState::$x($t));)*
 }
 
+/// A tile placement, as the kind and index of the tile in the player's hand, the group action applied to it, and the location
+pub type TilePlacement<G> = (<G as Game>::Kind, u32, <G as Game>::GAct, <G as Game>::TLoc);
+
+/// A tile placement, as the kind and index of the tile in the player's hand, the group action applied to it, and the location
+pub type BaseTilePlacement = (BaseKind, u32, BaseGAct, BaseTLoc);
+
 /// This trait is just to make the macro work
 pub trait GameStateT {
     type Game: Game;
@@ -186,20 +331,32 @@ pub struct GameState<G: Game> {
     tiles: FnvHashMap<G::Kind, VecDeque<G::Tile>>,
     #[getset(get = "pub")]
     winners: Vec<u32>,
+    /// The player holding the dragon tile, who gets dealt first when tiles return to the draw pile.
+    /// None if nobody is waiting for tiles.
+    #[getset(get_copy = "pub")]
+    dragon: Option<u32>,
 }
 
 impl<G: Game> GameState<G> {
-    /// Construct a new state from a game
+    /// Construct a new state from a game, shuffling the draw pile randomly
     pub fn new(game: &G, num_players: u32) -> Self {
+        let seed = crate::random_seed();
+        log::debug!("Generating tiles for game");
+        log::debug!("Seed {}", seed);
+        Self::new_seeded(game, num_players, seed)
+    }
+
+    /// Construct a new state from a game, shuffling the draw pile deterministically from `seed`
+    pub fn new_seeded(game: &G, num_players: u32, seed: u64) -> Self {
         let mut tiles = game.all_tiles();
-        // TODO: Shuffle tiles first
         tiles.sort_by_key(|tile| tile.kind().clone());
         let groups = tiles.into_iter().group_by(|tile| tile.kind().clone());
-        let mut tiles = groups.into_iter().map(|(kind, tiles)|
-            (kind, tiles.map(|t| t.with_visible(false)).collect::<VecDeque<_>>())).collect::<FnvHashMap<_, _>>();
-        for tiles in tiles.values_mut() {
-            tiles.make_contiguous().shuffle(&mut pcg64!("Generating tiles for game"));
-        }
+        let mut rng = pcg64_seeded(seed);
+        let tiles = groups.into_iter().map(|(kind, tiles)| {
+            let mut tiles = tiles.map(|t| t.with_visible(false)).collect::<VecDeque<_>>();
+            tiles.make_contiguous().shuffle(&mut rng);
+            (kind, tiles)
+        }).collect::<FnvHashMap<_, _>>();
 
         let mut state = Self {
             board_state: BoardState::new(game, num_players),
@@ -208,6 +365,7 @@ impl<G: Game> GameState<G> {
             turn_player: 0,
             tiles,
             winners: vec![],
+            dragon: None,
         };
 
         // deal tiles
@@ -227,7 +385,8 @@ impl<G: Game> GameState<G> {
     }
 
     /// The state of the game visible to `looker`.
-    /// `looker` is None for spectators.
+    /// Tiles that `looker` isn't allowed to see are replaced with placeholders,
+    /// so the draw pile only keeps the number of tiles of each kind.
     pub fn visible_state(&self, looker: Looker) -> GameState<G> {
         GameState {
             board_state: self.board_state().clone(),
@@ -237,10 +396,61 @@ impl<G: Game> GameState<G> {
             looker,
             turn_player: self.turn_player,
             tiles: self.tiles.iter().map(|(kind, tiles)|
-                (kind.clone(), tiles.iter().map(|t| t.clone().with_visible(false)).collect()))
+                (kind.clone(), tiles.iter().map(|t| if looker == Looker::Server {
+                    t.clone().with_visible(false)
+                } else {
+                    t.hidden()
+                }).collect()))
                 .collect(),
             winners: self.winners.clone(),
+            dragon: self.dragon,
+        }
+    }
+
+    /// A state the server could have, given what this state's looker can see.
+    /// Tiles in hands that the looker can't see are filled in at random from the tiles the looker hasn't seen,
+    /// and the rest of those tiles make up the draw pile, in random order.
+    /// The result is looked at by the server.
+    /// Assumes this state came from a game of `game`.
+    pub fn sample_hidden(&self, game: &G, rng: &mut impl Rng) -> GameState<G> {
+        let mut unseen = FnvHashMap::<G::Tile, u32>::default();
+        for tile in game.all_tiles() {
+            *unseen.entry(tile.with_visible(false).canonical()).or_default() += 1;
+        }
+        let seen = self.board_state.tiles_vec().into_iter().map(|(_, tile)| tile)
+            .chain(self.player_states.iter().flatten()
+                .flat_map(|state| state.tiles_vec().into_iter().flat_map(|(_, tiles)| tiles))
+                .filter(|tile| tile.visible()));
+        for tile in seen {
+            *unseen.get_mut(&tile.clone().with_visible(false).canonical()).expect("Seen tile should be in the game") -= 1;
         }
+
+        let mut tiles = self.tiles.keys()
+            .map(|kind| (kind.clone(), VecDeque::new()))
+            .collect::<FnvHashMap<_, _>>();
+        for (tile, count) in unseen.into_iter().sorted() {
+            tiles.get_mut(tile.kind()).expect("Each kind should have a list of tiles")
+                .extend(std::iter::repeat_n(tile, count as usize));
+        }
+        for pile in tiles.values_mut() {
+            pile.make_contiguous().shuffle(rng);
+        }
+
+        let mut state = self.clone();
+        state.looker = Looker::Server;
+        for player_state in state.player_states.iter_mut().flatten() {
+            let hidden = player_state.tiles_vec().into_iter()
+                .flat_map(|(kind, hand)| hand.iter().positions(|tile| !tile.visible())
+                    .map(|index| (kind.clone(), index as u32))
+                    .collect_vec())
+                .collect_vec();
+            for (kind, index) in hidden {
+                let tile = tiles.get_mut(&kind).unwrap().pop_front().expect("Hidden tile should be unseen");
+                player_state.replace_tile(&kind, index, tile.with_visible(true));
+            }
+        }
+        state.tiles = tiles;
+        state
     }
 
     /// Number of players in the game
@@ -262,12 +472,19 @@ impl<G: Game> GameState<G> {
     pub fn deal_tile(&mut self, player: u32, kind: &G::Kind) -> Option<(u32, G::Tile)> {
         self.next_tile(kind).zip(self.player_states[player as usize].as_mut())
             .map(|(mut tile, state)| {
-                tile.set_visible(self.looker.tag() != LookerTag::Player || self.looker == Looker::Player(player));
+                tile.set_visible(self.looker.can_see_hand_of(player));
                 state.add_tile(tile.clone());
                 (state.num_tiles_by_kind(kind) as u32 - 1, tile)
             })
     }
 
+    /// Replaces a tile in a player's hand with its revealed version.
+    /// Used by lookers whose state only has a placeholder for that tile.
+    /// For now, assumes the player is alive and the index exists.
+    pub fn reveal_hand_tile(&mut self, player: u32, kind: &G::Kind, index: u32, tile: G::Tile) {
+        self.player_states[player as usize].as_mut().unwrap().replace_tile(kind, index, tile);
+    }
+
     /// Place a player on some port.
     pub fn place_player(&mut self, player: u32, port: &G::Port) {
         self.board_state.place_player(player, port)
@@ -289,9 +506,9 @@ impl<G: Game> GameState<G> {
         tile
     }
 
-    /// Whether all players placed their tokens
+    /// Whether all players that are still alive placed their tokens
     pub fn all_players_placed(&self) -> bool {
-        self.board_state().all_players_placed()
+        self.alive_players().into_iter().all(|player| self.board_state.player_port(player).is_some())
     }
 
     /// Number of tiles left of each kind in the draw pile
@@ -315,9 +532,17 @@ impl<G: Game> GameState<G> {
 
     /// Move players that touch a tile along their respective paths until they face a dead end.
     /// Assumes the location has a tile on it.
-    /// Returns a list of dead players.
-    pub fn advance_players(&mut self, board: &G::Board, loc: &G::TLoc) -> Vec<u32> {
-        self.board_state.advance_players(board, loc)
+    /// Returns a list of dead players and why they died.
+    pub fn advance_players(&mut self, game: &G, loc: &G::TLoc) -> Vec<(u32, DeathCause)> {
+        let alive = self.alive_players();
+        self.board_state.advance_players(game.board(), loc, &alive, game.token_collisions())
+    }
+
+    /// Players that haven't been eliminated
+    fn alive_players(&self) -> Vec<u32> {
+        (0..self.num_players())
+            .filter(|player| self.player_state(*player).is_some())
+            .collect()
     }
 
     /// Give remaining tiles to players so that for each tile kind,
@@ -325,6 +550,7 @@ impl<G: Game> GameState<G> {
     /// each player has either *n* or *n* - 1 tiles for some *n*,
     /// and players with *n* tiles go before players with *n* - 1 tiles.
     /// Prioritize giving tiles to players with less tiles, then players whose turn is sooner, if this is impossible.
+    /// Turn order starts from the dragon holder if there is one.
     /// 
     /// This is intended to be called before updating whose turn it is.
     /// 
@@ -334,10 +560,10 @@ impl<G: Game> GameState<G> {
 
         for kind in game.board().all_kinds() {
             let num_tiles = game.num_tiles_per_player(&kind);
-            let turn_player = self.turn_player();
             let num_players = self.num_players();
+            let first = self.dragon.unwrap_or((self.turn_player() + 1) % num_players);
             let deal_tile_order = (0..num_tiles)
-                .flat_map(|i| (0..num_players).map(move |j| ((j + turn_player + 1) % num_players, i)))
+                .flat_map(|i| (0..num_players).map(move |j| ((j + first) % num_players, i)))
                 .flat_map(|(player, i)| self.player_state(player)
                     .filter(|state| state.num_tiles_by_kind(&kind) <= i)
                     .map(|_| player))
@@ -355,6 +581,22 @@ impl<G: Game> GameState<G> {
         new_tiles
     }
 
+    /// Whether `player` is alive and has fewer tiles of some kind than the game deals them
+    fn missing_tiles(&self, game: &G, player: u32) -> bool {
+        self.player_state(player).is_some_and(|state| game.board().all_kinds().iter()
+            .any(|kind| state.num_tiles_by_kind(kind) < game.num_tiles_per_player(kind)))
+    }
+
+    /// Passes the dragon tile on after tiles were dealt.
+    /// The holder keeps it while they're still missing tiles.
+    /// Otherwise, it goes to the next player in turn order who's missing tiles,
+    /// starting from `player` if nobody held it, or nobody gets it.
+    fn pass_dragon(&mut self, game: &G, player: u32) {
+        let first = self.dragon.unwrap_or(player);
+        self.dragon = (0..self.num_players()).cycle().skip(first as usize).take(self.num_players() as usize)
+            .find(|next| self.missing_tiles(game, *next));
+    }
+
     /// Removes tiles from dead players.
     /// Assumes the players were just alive
     pub fn handle_dead_players(&mut self, _game: &G, players: &[u32]) {
@@ -379,19 +621,164 @@ impl<G: Game> GameState<G> {
     /// The turn is processed and then advances to the next player.
     pub fn take_turn_placing_player(&mut self, _game: &G, port: &G::Port) {
         self.place_player(self.turn_player(), port);
-        // All players should still be alive
-        self.turn_player = (self.turn_player + 1) % self.num_players();
+        self.turn_player = self.next_alive_player(self.turn_player).unwrap_or(self.turn_player);
+    }
+
+    /// The players that are allowed to place their token right now.
+    /// Empty once every player placed their token.
+    pub fn players_placing_token(&self, game: &G) -> Vec<u32> {
+        match game.token_placement() {
+            TokenPlacement::Sequential => (!self.all_players_placed())
+                .then_some(self.turn_player)
+                .into_iter()
+                .collect(),
+            TokenPlacement::Simultaneous => self.alive_players().into_iter()
+                .filter(|player| self.board_state.player_port(*player).is_none())
+                .collect(),
+        }
+    }
+
+    /// Can `player` place their token on port `port`?
+    /// Tokens can only be placed once, and only on the player's turn if tokens are placed sequentially.
+    pub fn can_place_token(&mut self, game: &G, player: u32, port: &G::Port) -> bool {
+        self.players_placing_token(game).contains(&player) && self.can_place_player(game, port)
+    }
+
+    /// Have `player` place their token on port `port`.
+    /// If tokens are placed sequentially, the turn advances to the next player.
+    /// Assumes the placement was checked with `can_place_token`.
+    pub fn take_turn_placing_token(&mut self, game: &G, player: u32, port: &G::Port) {
+        match game.token_placement() {
+            TokenPlacement::Sequential => {
+                debug_assert_eq!(player, self.turn_player, "Placed a token out of turn");
+                self.take_turn_placing_player(game, port)
+            }
+            TokenPlacement::Simultaneous => self.place_player(player, port),
+        }
     }
 
     /// Can `player` place a tile of kind `kind` from index `index` in their hand transformed by group action `action` to location `loc`?
-    pub fn can_place_tile(&mut self, game: &G, player: u32, kind: &G::Kind, index: u32, _action: &G::GAct, loc: &G::TLoc) -> bool {
-        self.player_states[player as usize].as_ref().map_or(false, |state| index < state.num_tiles_by_kind(kind)) &&
+    /// This requires it to be `player`'s turn, and `action` to be an action of the tile's group.
+    /// If the game forbids suicide, the placement also can't eliminate `player` unless every placement does.
+    pub fn can_place_tile(&mut self, game: &G, player: u32, kind: &G::Kind, index: u32, action: &G::GAct, loc: &G::TLoc) -> bool {
+        player == self.turn_player && !self.game_over() &&
+            self.player_states[player as usize].as_ref().map_or(false, |state| index < state.num_tiles_by_kind(kind) &&
+                state.tiles()[kind][index as usize].is_valid_action(action)) &&
             self.board_state.player_port(player).map_or(false, |port|
                 game.board().port_locs(port).contains(loc)) &&
             self.board_state.tile_at(loc).is_none() &&
-            kind == &game.board().kind_at(loc)
-            // TODO: In the original game, there's also the condition that a player can't kill themselves with a tile
-            // if they have a move that doesn't do that. Figure out if this should be checked here.
+            kind == &game.board().kind_at(loc) &&
+            (!game.no_suicide() || !self.is_suicidal(game, player, kind, index, action, loc) ||
+                self.safe_tile_placements(game, player).is_none())
+    }
+
+    /// Whether `player` placing a tile of kind `kind` from index `index` in their hand transformed by group action `action`
+    /// to location `loc` would eliminate them. This is simulated on a copy of the board.
+    /// Assumes the player is alive and the tile exists.
+    pub fn is_suicidal(&self, game: &G, player: u32, kind: &G::Kind, index: u32, action: &G::GAct, loc: &G::TLoc) -> bool {
+        let tile = self.player_states[player as usize].as_ref().unwrap().tiles()[kind][index as usize]
+            .apply_action(action);
+        self.placing_eliminates(game, player, tile, loc)
+    }
+
+    /// Whether placing `tile` at `loc` would eliminate `player`, whether or not the tile is in anyone's hand.
+    /// This is simulated on a copy of the board.
+    pub fn placing_eliminates(&self, game: &G, player: u32, tile: G::Tile, loc: &G::TLoc) -> bool {
+        let mut board_state = self.board_state.clone();
+        board_state.place_tile(tile, loc);
+        board_state.advance_players(game.board(), loc, &self.alive_players(), game.token_collisions()).iter()
+            .any(|(dead, _)| *dead == player)
+    }
+
+    /// The empty tile locations that `player`'s token faces, where they can place a tile.
+    /// Empty if the player is dead or hasn't placed their token.
+    pub fn open_locs(&self, game: &G, player: u32) -> Vec<G::TLoc> {
+        match (self.player_state(player), self.board_state.player_port(player)) {
+            (Some(_), Some(port)) => game.board().port_locs(port).into_iter()
+                .filter(|loc| self.board_state.tile_at(loc).is_none())
+                .collect_vec(),
+            _ => vec![],
+        }
+    }
+
+    /// Every tile placement `player` could make with a rotated tile, ignoring whether it eliminates them.
+    pub fn tile_placements(&self, game: &G, player: u32) -> Vec<TilePlacement<G>> {
+        let state = match self.player_state(player) {
+            Some(state) => state,
+            None => return vec![],
+        };
+
+        self.open_locs(game, player).into_iter()
+            .flat_map(|loc| {
+                let kind = game.board().kind_at(&loc);
+                state.tiles()[&kind].iter().enumerate()
+                    .flat_map(|(index, tile)| (0..tile.all_rotations().len())
+                        .map(|rotation| (kind.clone(), index as u32, tile.rotation_action(rotation as i32), loc.clone()))
+                        .collect_vec())
+                    .collect_vec()
+            })
+            .collect_vec()
+    }
+
+    /// The tile placements `player` is restricted to because the game forbids suicide.
+    /// None if there's no restriction, either because the game allows suicide or because every placement eliminates `player`.
+    pub fn safe_tile_placements(&self, game: &G, player: u32) -> Option<Vec<TilePlacement<G>>> {
+        if !game.no_suicide() {
+            return None;
+        }
+        let safe = self.tile_placements(game, player).into_iter()
+            .filter(|(kind, index, action, loc)| !self.is_suicidal(game, player, kind, *index, action, loc))
+            .collect_vec();
+        (!safe.is_empty()).then_some(safe)
+    }
+
+    /// The first player alive after `player` in turn order, wrapping around to `player` itself.
+    /// None if every player is dead.
+    fn next_alive_player(&self, player: u32) -> Option<u32> {
+        (0..self.num_players()).cycle().skip(player as usize + 1).take(self.num_players() as usize)
+            .find(|player| self.player_state(*player).is_some())
+    }
+
+    /// Ends the game if it's over, given the players that just died
+    fn update_winners(&mut self, dead: &[u32]) {
+        let remaining = self.alive_players();
+        if remaining.is_empty() {
+            // Every player died, so the last ones that remained won
+            self.winners = dead.to_vec();
+        } else if remaining.len() == 1 {
+            // Unique player remaning, game is over
+            self.winners = remaining;
+        } else if self.player_states.iter()
+            .flat_map(|maybe| maybe.as_ref())
+            .all(|state| !state.has_tiles())
+        {
+            // If everyone's out of tiles, the game's over
+            self.winners = remaining;
+        }
+    }
+
+    /// Eliminates `player` because they left the game.
+    /// Their tiles go back to the draw pile and get dealt out again,
+    /// and if it was their turn, the turn passes to the next player.
+    /// Assumes the player is alive.
+    /// Returns a list of tiles added to player's hands in the form (player, index, tile)
+    pub fn forfeit(&mut self, game: &G, player: u32) -> Vec<(u32, u32, G::Tile)> {
+        self.handle_dead_players(game, &[player]);
+        let drawn_tiles = self.redistribute_tiles(game);
+        self.pass_dragon(game, player);
+
+        if self.turn_player == player {
+            if let Some(next) = self.next_alive_player(player) {
+                self.turn_player = next;
+            }
+        }
+        self.update_winners(&[player]);
+        drawn_tiles
+    }
+
+    /// Every tile placement with a rotated tile that `player` is allowed to make on their turn
+    pub fn legal_tile_placements(&self, game: &G, player: u32) -> Vec<TilePlacement<G>> {
+        self.safe_tile_placements(game, player).unwrap_or_else(|| self.tile_placements(game, player))
     }
 
     /// Have the current player take a turn by placing a tile of kind `kind` from index `index` in their hand
@@ -401,50 +788,29 @@ impl<G: Game> GameState<G> {
         let tile_placer = self.turn_player;
 
         let tile_placed = self.player_place_tile(self.turn_player(), kind, index, action, loc);
-        let dead = self.advance_players(game.board(), loc);
+        let dead = self.advance_players(game, loc);
+        let dead_ids = dead.iter().map(|(player, _)| *player).collect_vec();
         let players_died = !dead.is_empty();
-        self.handle_dead_players(game, &dead);
+        self.handle_dead_players(game, &dead_ids);
         let drawn_tiles = if players_died {
             self.redistribute_tiles(game)
         } else {
             self.deal_tile(self.turn_player, kind).map(|(index, tile)| (self.turn_player, index, tile)).into_iter().collect()
         };
+        self.pass_dragon(game, tile_placer);
 
-        let mut all_dead = false;
-        if let Some(next) = (0..self.num_players()).cycle().skip(self.turn_player() as usize + 1).take(self.num_players() as usize)
-            .find(|player| self.player_state(*player).is_some())
-        {
+        if let Some(next) = self.next_alive_player(self.turn_player) {
             self.turn_player = next;
-        } else {
-            // Every player died, so the last ones that remained won
-            all_dead = true;
-            self.winners = dead.clone();
         }
+        self.update_winners(&dead_ids);
 
         let player_ports = (0..self.num_players())
-            .map(|player| self.board_state().player_port(player).expect("Players should have placed ports").clone())
+            .map(|player| self.board_state().player_port(player).cloned())
             .collect();
         let num_tiles_left = self.tiles.iter()
             .map(|(kind, tiles)| (kind.clone(), tiles.len() as u32))
             .collect();
 
-        if !all_dead {
-            let mut remaining = (0..self.num_players())
-                .filter(|player| self.player_state(*player).is_some());
-            if let (Some(winner), None) = (remaining.next(), remaining.next()) {
-                // Unique player remaning, game is over
-                self.winners = vec![winner];
-            } else if self.player_states.iter()
-                .flat_map(|maybe| maybe.as_ref())
-                .all(|state| !state.has_tiles())
-            {
-                // If everyone's out of tiles, the game's over
-                self.winners = (0..self.num_players())
-                    .filter(|player| self.player_state(*player).is_some())
-                    .collect();
-            }
-        }
-
         TurnResult {
             tile_placer,
             tile_placed: (index, tile_placed),
@@ -470,12 +836,12 @@ pub struct TurnResult<G: Game> {
     /// Where the tile was placed
     #[getset(get = "pub")]
     tile_loc: G::TLoc,
-    /// New locations of players, indexed by player
+    /// New locations of players, indexed by player. None for players that left before placing their token
     #[getset(get = "pub")]
-    player_ports: Vec<G::Port>,
-    /// Which players died
+    player_ports: Vec<Option<G::Port>>,
+    /// Which players died and why
     #[getset(get = "pub")]
-    dead_players: Vec<u32>,
+    dead_players: Vec<(u32, DeathCause)>,
     /// New number of tiles per kind in the draw pile
     #[getset(get = "pub")]
     num_tiles_left: Vec<(G::Kind, u32)>,
@@ -499,12 +865,12 @@ pub struct BaseTurnResult {
     /// Where the tile was placed
     #[getset(get = "pub")]
     tile_loc: BaseTLoc,
-    /// New locations of players, indexed by player
+    /// New locations of players, indexed by player. None for players that left before placing their token
     #[getset(get = "pub")]
-    player_ports: Vec<BasePort>,
-    /// Which players died
+    player_ports: Vec<Option<BasePort>>,
+    /// Which players died and why
     #[getset(get = "pub")]
-    dead_players: Vec<u32>,
+    dead_players: Vec<(u32, DeathCause)>,
     /// New number of tiles per kind in the draw pile
     #[getset(get = "pub")]
     num_tiles_left: Vec<(BaseKind, u32)>,
@@ -516,9 +882,29 @@ pub struct BaseTurnResult {
     game_over: bool,
 }
 
+impl BaseTurnResult {
+    /// New tiles drawn by players in (player, index, tile) format,
+    /// with the tiles that `looker` isn't allowed to see replaced by placeholders
+    pub fn drawn_tiles_visible_to(&self, looker: Looker) -> Vec<(u32, u32, BaseTile)> {
+        drawn_tiles_visible_to(&self.drawn_tiles, looker)
+    }
+}
+
+/// Tiles drawn by players in (player, index, tile) format,
+/// with the tiles that `looker` isn't allowed to see replaced by placeholders
+pub fn drawn_tiles_visible_to(drawn_tiles: &[(u32, u32, BaseTile)], looker: Looker) -> Vec<(u32, u32, BaseTile)> {
+    drawn_tiles.iter()
+        .map(|(player, index, tile)| (*player, *index, if looker.can_see_hand_of(*player) {
+            tile.clone()
+        } else {
+            tile.hidden()
+        }))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::{board::RectangleBoard, game::PathGame, tile::RegularTile};
+    use crate::{board::{HexBoard, RectangleBoard}, game::PathGame, math::{pt2, Vec2u}, tile::RegularTile};
 
     use super::*;
 
@@ -535,4 +921,233 @@ mod tests {
             assert_eq!(state.board_state().player_port(player), None);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_game_state_place_tokens_sequential() {
+        let board = RectangleBoard::new(6, 6, 2);
+        let start_ports = board.boundary_ports();
+        let game = PathGame::<_, RegularTile<4>>::new(board, start_ports.clone(), [((), 3)]);
+        let mut state = GameState::new(&game, 3);
+
+        assert_eq!(state.players_placing_token(&game), vec![0]);
+        assert!(!state.can_place_token(&game, 1, &start_ports[1]));
+        assert!(state.can_place_token(&game, 0, &start_ports[0]));
+        state.take_turn_placing_token(&game, 0, &start_ports[0]);
+
+        // No second placement, and no taking someone else's port
+        assert_eq!(state.players_placing_token(&game), vec![1]);
+        assert!(!state.can_place_token(&game, 0, &start_ports[1]));
+        assert!(!state.can_place_token(&game, 1, &start_ports[0]));
+        state.take_turn_placing_token(&game, 1, &start_ports[1]);
+        state.take_turn_placing_token(&game, 2, &start_ports[2]);
+
+        assert!(state.all_players_placed());
+        assert_eq!(state.players_placing_token(&game), vec![]);
+        assert_eq!(state.turn_player(), 0);
+    }
+
+    #[test]
+    fn test_game_state_place_tokens_simultaneous() {
+        let board = RectangleBoard::new(6, 6, 2);
+        let start_ports = board.boundary_ports();
+        let game = PathGame::<_, RegularTile<4>>::new(board, start_ports.clone(), [((), 3)])
+            .with_token_placement(TokenPlacement::Simultaneous);
+        let mut state = GameState::new(&game, 3);
+
+        assert_eq!(state.players_placing_token(&game), vec![0, 1, 2]);
+        assert!(state.can_place_token(&game, 2, &start_ports[2]));
+        state.take_turn_placing_token(&game, 2, &start_ports[2]);
+
+        assert_eq!(state.players_placing_token(&game), vec![0, 1]);
+        assert!(!state.can_place_token(&game, 2, &start_ports[3]));
+        assert_eq!(state.turn_player(), 0);
+    }
+
+    #[test]
+    fn test_game_state_visible_state_hides_tiles() {
+        let board = RectangleBoard::new(6, 6, 2);
+        let start_ports = board.boundary_ports();
+        let game = PathGame::<_, RegularTile<4>>::new(board, start_ports, [((), 3)]);
+        let state = GameState::new(&game, 4);
+
+        for looker in [Looker::Player(0), Looker::Player(2), Looker::Spectator] {
+            let visible = state.visible_state(looker);
+
+            for player in 0..state.num_players() {
+                let tiles = &visible.player_state(player).unwrap().tiles()[&()];
+                assert_eq!(tiles.len(), 3);
+                for (tile, original) in tiles.iter().zip(&state.player_state(player).unwrap().tiles()[&()]) {
+                    // Spectators don't see anyone's hand
+                    if looker == Looker::Player(player) {
+                        assert_eq!(tile, &original.clone().with_visible(true));
+                    } else {
+                        assert_eq!(tile, &original.hidden());
+                    }
+                }
+            }
+
+            assert_eq!(visible.num_tiles_left_by_kind(), state.num_tiles_left_by_kind());
+            assert!(visible.tiles[&()].iter().all(|tile| tile == &tile.hidden()));
+        }
+    }
+
+    #[test]
+    fn test_game_state_visible_state_server() {
+        let board = RectangleBoard::new(6, 6, 2);
+        let start_ports = board.boundary_ports();
+        let game = PathGame::<_, RegularTile<4>>::new(board, start_ports, [((), 3)]);
+        let state = GameState::new(&game, 2);
+
+        let visible = state.visible_state(Looker::Server);
+        assert_eq!(visible.tiles[&()], state.tiles[&()]);
+    }
+
+    #[test]
+    fn test_game_state_sample_hidden() {
+        let board = RectangleBoard::new(6, 6, 2);
+        let start_ports = board.boundary_ports();
+        let game = PathGame::<_, RegularTile<4>>::new(board, start_ports, [((), 3)]);
+        let mut state = GameState::new_seeded(&game, 3, 5);
+        let ports = game.start_ports();
+        for player in 0..3 {
+            state.take_turn_placing_token(&game, player, &ports[player as usize * 7]);
+        }
+        let (kind, index, action, loc) = state.legal_tile_placements(&game, 0).swap_remove(0);
+        state.take_turn_placing_tile(&game, &kind, index, &action, &loc);
+
+        let visible = state.visible_state(Looker::Player(1));
+        let sampled = visible.sample_hidden(&game, &mut pcg64_seeded(0));
+        assert_eq!(sampled.looker(), Looker::Server);
+        assert_eq!(sampled.board_state().tiles_vec(), state.board_state().tiles_vec());
+        assert_eq!(sampled.player_state(1).map(|s| s.tiles()), visible.player_state(1).map(|s| s.tiles()));
+        assert_eq!(sampled.num_tiles_left_by_kind(), state.num_tiles_left_by_kind());
+
+        // Every tile of the game shows up exactly once
+        let all_tiles = sampled.board_state().tiles_vec().into_iter().map(|(_, tile)| tile)
+            .chain((0..3).flat_map(|player| sampled.player_state(player)).flat_map(|s| &s.tiles()[&()]))
+            .chain(&sampled.tiles[&()])
+            .map(|tile| tile.clone().with_visible(false).canonical())
+            .sorted()
+            .collect_vec();
+        assert_eq!(all_tiles, game.all_tiles().into_iter().map(|tile| tile.with_visible(false)).collect_vec());
+    }
+
+    #[test]
+    fn test_game_state_hex_turn() {
+        let board = HexBoard::new(2, 2);
+        let start_ports = board.boundary_ports();
+        let game = PathGame::<_, RegularTile<6>>::new(board.clone(), start_ports, [((), 3)]);
+        let mut state = GameState::new(&game, 2);
+
+        let corner = pt2(0, -2);
+        let ports = board.loc_ports(&corner);
+        state.take_turn_placing_token(&game, 0, &ports[0]);
+        state.take_turn_placing_token(&game, 1, &ports[11]);
+
+        let action = state.player_state(0).unwrap().tiles()[&()][0].identity_action();
+        assert!(state.can_place_tile(&game, 0, &(), 0, &action, &corner));
+        assert!(!state.can_place_tile(&game, 0, &(), 0, &action, &pt2(0, 0)));
+        let result = state.take_turn_placing_tile(&game, &(), 0, &action, &corner);
+
+        // Both players went through the corner tile, so they're either on its far side or dead on the boundary
+        for player in 0..2 {
+            let port = result.player_ports[player].as_ref().unwrap();
+            assert!(board.port_locs(port).contains(&corner));
+            assert_eq!(result.dead_players.iter().any(|(p, _)| *p == player as u32), board.port_locs(port).len() == 1);
+        }
+    }
+
+    #[test]
+    fn test_game_state_no_suicide() {
+        let board = RectangleBoard::new(2, 1, 1);
+        let start_ports = board.boundary_ports();
+        let game = PathGame::<_, RegularTile<4>>::new(board, start_ports, [((), 1)])
+            .with_no_suicide(true);
+        let mut state = GameState::new(&game, 1);
+        let left = (pt2(0, 0), Vec2u::new(0, 1));
+        state.take_turn_placing_token(&game, 0, &left);
+
+        // Every rotation of the turn tile leads off the board, but the straight tile leads to the next location
+        let turn = RegularTile::<4>::new(vec![1, 0, 3, 2]);
+        let straight = RegularTile::<4>::new(vec![2, 3, 0, 1]);
+        let hand = state.player_states[0].as_mut().unwrap();
+        hand.remove_all_tiles();
+        hand.add_tile(turn.clone());
+        hand.add_tile(straight);
+
+        let loc = pt2(0, 0);
+        for rotation in 0..4 {
+            assert!(!state.can_place_tile(&game, 0, &(), 0, &turn.rotation_action(rotation), &loc));
+            assert!(state.can_place_tile(&game, 0, &(), 1, &turn.rotation_action(rotation), &loc));
+        }
+        let safe = state.safe_tile_placements(&game, 0).unwrap();
+        assert_eq!(safe.len(), 4);
+        assert!(safe.iter().all(|(_, index, _, l)| *index == 1 && l == &loc));
+
+        // Suicide is allowed when it's forced
+        let hand = state.player_states[0].as_mut().unwrap();
+        hand.remove_tile(&(), 1);
+        assert_eq!(state.safe_tile_placements(&game, 0), None);
+        assert!(state.can_place_tile(&game, 0, &(), 0, &turn.identity_action(), &loc));
+
+        // And always when the rule is off
+        let game = game.with_no_suicide(false);
+        let hand = state.player_states[0].as_mut().unwrap();
+        hand.add_tile(RegularTile::<4>::new(vec![2, 3, 0, 1]));
+        assert_eq!(state.safe_tile_placements(&game, 0), None);
+        assert!(state.can_place_tile(&game, 0, &(), 0, &turn.identity_action(), &loc));
+    }
+
+    #[test]
+    fn test_game_state_dragon() {
+        let board = RectangleBoard::new(6, 6, 2);
+        let start_ports = board.boundary_ports();
+        let game = PathGame::<_, RegularTile<4>>::new(board, start_ports, [((), 1)]);
+        let mut state = GameState::new(&game, 3);
+        state.tiles.get_mut(&()).unwrap().clear();
+
+        // The first player who can't draw takes the dragon
+        state.player_states[0].as_mut().unwrap().remove_tile(&(), 0);
+        state.pass_dragon(&game, 0);
+        assert_eq!(state.dragon(), Some(0));
+        state.player_states[1].as_mut().unwrap().remove_tile(&(), 0);
+        state.pass_dragon(&game, 1);
+        assert_eq!(state.dragon(), Some(0));
+
+        // Returned tiles go to the dragon holder first, then the dragon passes on
+        state.handle_dead_players(&game, &[2]);
+        let dealt = state.redistribute_tiles(&game);
+        assert_eq!(dealt.iter().map(|(player, index, _)| (*player, *index)).collect_vec(), vec![(0, 0)]);
+        state.pass_dragon(&game, 1);
+        assert_eq!(state.dragon(), Some(1));
+
+        // Nobody's waiting for tiles anymore
+        state.handle_dead_players(&game, &[1]);
+        state.pass_dragon(&game, 0);
+        assert_eq!(state.dragon(), None);
+    }
+
+    #[test]
+    fn test_game_state_forfeit() {
+        let board = RectangleBoard::new(6, 6, 2);
+        let start_ports = board.boundary_ports();
+        let game = PathGame::<_, RegularTile<4>>::new(board, start_ports, [((), 3)]);
+        let mut state = GameState::new(&game, 3);
+        state.take_turn_placing_token(&game, 0, &(pt2(0, 0), Vec2u::new(1, 0)));
+
+        // The turn player leaves before placing their token, so the next one places theirs
+        let pile = state.num_tiles_left_by_kind()[0].1;
+        assert!(state.forfeit(&game, 1).is_empty());
+        assert_eq!(state.num_tiles_left_by_kind()[0].1, pile + 3);
+        assert_eq!(state.turn_player(), 2);
+        assert_eq!(state.players_placing_token(&game), vec![2]);
+        state.take_turn_placing_token(&game, 2, &(pt2(1, 0), Vec2u::new(1, 0)));
+        assert!(state.all_players_placed());
+        assert_eq!(state.turn_player(), 0);
+        assert!(!state.game_over());
+
+        // The last player remaining wins
+        state.forfeit(&game, 0);
+        assert_eq!(state.winners(), &vec![2]);
+    }
+}
@@ -2,13 +2,20 @@ pub mod board;
 pub mod math;
 pub mod tile;
 pub mod game;
+pub mod game_settings;
 pub mod player_state;
 pub mod board_state;
 pub mod game_state;
 pub mod message;
+pub mod replay;
+pub mod notation;
+pub mod strategy;
+pub mod bot;
+pub mod analysis;
 
 use game::GameId;
 use game::BaseGame;
+use game_settings::GameSettings;
 use game_state::BaseGameState;
 use getset::{Getters, CopyGetters};
 pub use nalgebra;
@@ -27,8 +34,12 @@ pub fn pcg64_seeded(seed: u64) -> Pcg64 {
 }
 
 pub fn pcg64() -> Pcg64 {
-    let seed = Uniform::from(0..=u64::MAX).sample(&mut thread_rng());
-    pcg64_seeded(seed)
+    pcg64_seeded(random_seed())
+}
+
+/// A random seed for `pcg64_seeded`
+pub fn random_seed() -> u64 {
+    Uniform::from(0..=u64::MAX).sample(&mut thread_rng())
 }
 
 /// Constructs a PCG64 from a random seed and debugs the seed
@@ -51,6 +62,8 @@ macro_rules! wrap_functions {
         fn wrap_base(self) -> $base;
         fn unwrap_base(base: $base) -> Self;
         fn unwrap_base_ref(base: &$base) -> &Self;
+        /// Like `unwrap_base_ref`, but None instead of panicking if `base` is a different variant
+        fn try_unwrap_base_ref(base: &$base) -> Option<&Self>;
     };
 }
 
@@ -84,6 +97,13 @@ macro_rules! impl_wrap_functions {
                 x
             } else { panic!("Mismatched type and associated type") }
         }
+
+        $($vis)* fn try_unwrap_base_ref(base: &$base) -> Option<&Self> {
+            #[allow(irrefutable_let_patterns)]
+            if let $base::$variant(x) = base {
+                Some(x)
+            } else { None }
+        }
     };
 }
 
@@ -120,6 +140,9 @@ pub struct GameInstance {
     id: GameId,
     #[getset(get = "pub")]
     game: BaseGame,
+    /// The settings the game was created with
+    #[getset(get = "pub")]
+    settings: GameSettings,
     /// None if the game hasn't started
     #[getset(get = "pub")]
     state: Option<BaseGameState>,
@@ -129,8 +152,8 @@ pub struct GameInstance {
 }
 
 impl GameInstance {
-    pub fn new(id: GameId, game: BaseGame, state: Option<BaseGameState>, players: Vec<String>) -> Self {
-        Self { id, game, state, players }
+    pub fn new(id: GameId, game: BaseGame, settings: GameSettings, state: Option<BaseGameState>, players: Vec<String>) -> Self {
+        Self { id, game, settings, state, players }
     }
 
     /// Sets the looker of the game state. The game state must exist.
@@ -139,7 +162,7 @@ impl GameInstance {
     }
 
     /// Extracts all the fields for separate manipulation.
-    pub fn into_fields(self) -> (GameId, BaseGame, Option<BaseGameState>, Vec<String>) {
-        (self.id, self.game, self.state, self.players)
+    pub fn into_fields(self) -> (GameId, BaseGame, GameSettings, Option<BaseGameState>, Vec<String>) {
+        (self.id, self.game, self.settings, self.state, self.players)
     }
 }
\ No newline at end of file
@@ -3,26 +3,63 @@
 use serde::{Deserialize, Serialize};
 
 use crate::GameInstance;
+use crate::game_settings::{GameSettings, GameSettingsError};
 use crate::game::{GameId};
-use crate::game_state::BaseGameState;
+use crate::game_state::{BaseGameState, BaseTilePlacement};
+use crate::replay::Replay;
+use crate::bot::BotLevel;
 use crate::board::{BasePort, BaseTLoc};
-use crate::tile::{BaseKind, BaseGAct};
+use crate::tile::{BaseKind, BaseGAct, BaseTile};
+
+/// A secret the server hands out with a username so that the peer can resume their session after reconnecting
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SessionToken(pub u128);
 
 /// The request type used by the client to communicate to the server
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Request {
     /// Set the username for a player
     SetUsername{ username: String },
+    /// Resume a session after reconnecting, going back to the lobby or games the session was in
+    Resume{ token: SessionToken },
     JoinLobby,
-    CreateGame,
+    /// Creates a game with some settings. The settings are validated by the server.
+    CreateGame{ settings: GameSettings },
     JoinGame{ id: GameId },
+    /// Seats a computer player in a game that hasn't started
+    AddBot{ id: GameId, level: BotLevel },
     /// Starts the game
     StartGame{ id: GameId },
-    PlaceToken{ id: GameId, player: u32, port: BasePort },
-    PlaceTile{ id: GameId, player: u32, kind: BaseKind, index: u32, action: BaseGAct, loc: BaseTLoc },
+    /// Places the requester's token. The server figures out which player the requester is.
+    PlaceToken{ id: GameId, port: BasePort },
+    /// Places a tile from the requester's hand. The server figures out which player the requester is.
+    PlaceTile{ id: GameId, kind: BaseKind, index: u32, action: BaseGAct, loc: BaseTLoc },
+    /// Leaves a started game for good, eliminating the requester
+    Forfeit{ id: GameId },
+    /// Asks for the move log of a finished game
+    GetReplay{ id: GameId },
     RemovePeer,
 }
 
+/// Why a request was rejected
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RejectReason {
+    /// The game doesn't exist
+    NoSuchGame,
+    /// The game isn't in the right phase for the request, e.g. it hasn't started yet
+    WrongPhase,
+    /// The requester isn't a player of the game
+    NotAPlayer,
+    /// It's not the requester's turn
+    NotYourTurn,
+    /// The game doesn't have enough players to start
+    NotEnoughPlayers,
+    /// The game has no seats left
+    GameFull,
+    /// The move breaks the rules of the game
+    IllegalMove,
+}
+
 /// The response type used by the server to communicate to the client
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Response {
@@ -32,6 +69,8 @@ pub enum Response {
     ChangedPlayers{ id: GameId, names: Vec<String> },
     /// A game was created or edited in the lobby
     ChangedGame{ game: GameInstance },
+    /// A game was removed from the lobby
+    RemovedGame{ id: GameId },
     /// A game was joined
     JoinedGame{ game: GameInstance },
     /// The lobby was joined. The lobby has games.
@@ -40,17 +79,41 @@ pub enum Response {
     StartedGame{ id: GameId, state: BaseGameState },
     /// Player `player` has placed a token on port `port`.
     PlacedToken{ id: GameId, player: u32, port: BasePort },
+    /// The username was accepted. `token` can be used to resume the session after reconnecting.
+    Session{ username: String, token: SessionToken },
     /// Invalid username
     RejectedUsername,
+    /// The session doesn't exist anymore, or someone is still connected to it
+    RejectedSession,
+    /// A game couldn't be created with the requested settings
+    RejectedGameSettings{ error: GameSettingsError },
     /// Invalid move, please undo
-    Rejected{ id: GameId },
+    Rejected{ id: GameId, reason: RejectReason },
     /// Everyone placed their tokens; it's time to place some tiles
     AllPlacedTokens{ id: GameId },
-    /// It's your turn, make a move
-    YourTurn{ id: GameId },
+    /// It's your turn, make a move.
+    /// If the game forbids suicide, `safe_placements` are the only placements allowed. None if any placement is allowed.
+    YourTurn{ id: GameId, safe_placements: Option<Vec<BaseTilePlacement>> },
+    /// It's your turn to place your token
+    YourTurnPlaceToken{ id: GameId },
+    /// Time left on each player's clock in milliseconds, indexed by player.
+    /// Only the clocks of the players in `running` are ticking.
+    Clock{ id: GameId, remaining_ms: Vec<u64>, running: Vec<u32> },
     /// Player `player` has placed a tile transformed by group action `action`
     /// from index `index` in their list of tiles of kind `kind` onto location `loc`.
-    PlacedTile{ id: GameId, player: u32, kind: BaseKind, index: u32, action: BaseGAct, loc: BaseTLoc },
+    /// `tile` is the placed tile before the group action was applied.
+    /// `drawn_tiles` are the tiles drawn afterward in (player, index, tile) format,
+    /// hidden if the receiver isn't allowed to see them.
+    PlacedTile{
+        id: GameId, player: u32, kind: BaseKind, index: u32, action: BaseGAct, loc: BaseTLoc,
+        tile: BaseTile, drawn_tiles: Vec<(u32, u32, BaseTile)>
+    },
+    /// Player `player` left the game and got eliminated.
+    /// `drawn_tiles` are the tiles dealt from their returned hand in (player, index, tile) format,
+    /// hidden if the receiver isn't allowed to see them.
+    Forfeited{ id: GameId, player: u32, drawn_tiles: Vec<(u32, u32, BaseTile)> },
+    /// The move log of a finished game
+    Replay{ id: GameId, replay: Replay },
     ///// Players moved across tiles. Stores a port per player
     //CrossedTiles{ new_ports: Vec<G::Port> },
     ///// Players died. Stores players that died
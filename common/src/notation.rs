@@ -0,0 +1,678 @@
+use std::fmt::{self, Display, Write};
+use std::str::FromStr;
+
+use getset::Getters;
+use itertools::Itertools;
+
+use crate::board::{BasePort, BaseTLoc, Port, TLoc};
+use crate::game::{BaseGame, TokenPlacement};
+use crate::game_state::BaseGameState;
+use crate::game_settings::{BoardShape, GameSettings, GameSettingsError, StartPorts, TimeControl, TimeoutPolicy};
+use crate::math::{pt2, Pt2i, Pt2u, Vec2u};
+use crate::replay::{Move, Replay};
+use crate::tile::{BaseTileCatalog, Kind};
+
+/// A game written down in a text notation, so it can be shared, diffed and imported.
+///
+/// A record starts with a header of `[Tag "value"]` lines giving the settings, the seed and the players,
+/// followed by a blank line and one numbered move per line:
+///
+/// ```text
+/// [Board "rectangle 6x6"]
+/// [PortsPerEdge "2"]
+/// [TilesPerPlayer "3"]
+/// [StartPorts "boundary"]
+/// [MinPlayers "2"]
+/// [MaxPlayers "8"]
+/// [TokenPlacement "sequential"]
+/// [NoSuicide "false"]
+/// [TokenCollisions "false"]
+/// [TimeControl "none"]
+/// [TimeoutPolicy "random move"]
+/// [Deck "full"]
+/// [Seed "1234"]
+/// [Player "alice"]
+/// [Player "bob"]
+///
+/// 1. 0 token 0,0+1,0
+/// 2. 1 token 0,0+2,0
+/// 3. 0 tile 12r1 0,0
+/// 4. 1 forfeit
+/// ```
+///
+/// Moves start with the index of the player making them. A tile is written as its id in the game's tile catalog,
+/// then how many times it's rotated clockwise from its canonical orientation, then its location.
+/// Locations are `x,y`, with a `v` suffix for downward triangles. Ports are `x,y+dx,dy` on rectangle boards and `x,y:i` otherwise.
+/// A custom deck is written as tile catalog ids, each followed by `*copies` if there's more than one copy, like `0*2 3 17`.
+/// Lines starting with `;` are comments.
+#[derive(Clone, Debug, Getters)]
+pub struct GameRecord {
+    #[getset(get = "pub")]
+    settings: GameSettings,
+    #[getset(get = "pub")]
+    replay: Replay,
+}
+
+/// Why a game record couldn't be parsed or constructed. Line numbers start at 1.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum NotationError {
+    /// A line isn't a tag or a move
+    Syntax{ line: usize },
+    /// A tag has a value it can't have
+    InvalidTag{ line: usize, tag: String },
+    /// A required tag is missing
+    MissingTag(&'static str),
+    /// The settings in the header can't build a game
+    InvalidSettings(GameSettingsError),
+    /// A move can't be made at that point in the game
+    IllegalMove{ line: usize },
+    /// The settings build a different game than the replay's
+    MismatchedSettings,
+    /// The settings don't allow this many players
+    WrongNumberOfPlayers{ num_players: usize },
+    /// A move of the replay can't be made at that point in the game. Moves are numbered from 1, as in the record.
+    IllegalReplayMove{ number: usize },
+}
+
+impl Display for NotationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax{ line } => write!(f, "line {}: expected a tag or a move", line),
+            Self::InvalidTag{ line, tag } => write!(f, "line {}: invalid value for tag {}", line, tag),
+            Self::MissingTag(tag) => write!(f, "missing tag {}", tag),
+            Self::InvalidSettings(err) => write!(f, "invalid settings: {}", err),
+            Self::IllegalMove{ line } => write!(f, "line {}: illegal move", line),
+            Self::MismatchedSettings => write!(f, "the settings don't match the replay's game"),
+            Self::WrongNumberOfPlayers{ num_players } => write!(f, "the settings don't allow {} players", num_players),
+            Self::IllegalReplayMove{ number } => write!(f, "move {} of the replay is illegal", number),
+        }
+    }
+}
+
+impl GameRecord {
+    /// Construct a record of a replay of a game built from `settings`.
+    /// Fails if the settings build a different game or a move of the replay is illegal, since the record couldn't be written.
+    pub fn new(settings: GameSettings, replay: Replay) -> Result<Self, NotationError> {
+        let game = settings.build().map_err(NotationError::InvalidSettings)?;
+        if game != *replay.game() {
+            return Err(NotationError::MismatchedSettings);
+        }
+        check_num_players(&settings, replay.players().len())?;
+
+        let catalog = game.tile_catalog();
+        let num_players = replay.players().len() as u32;
+        let mut state = replay.initial_state();
+        for (i, mv) in replay.moves().iter().enumerate() {
+            written_move(mv, &catalog, &state)
+                .and_then(|written| resolve_move(written, &game, &catalog, &mut state, num_players))
+                .ok_or(NotationError::IllegalReplayMove{ number: i + 1 })?;
+            replay.apply(&mut state, mv);
+        }
+
+        Ok(Self { settings, replay })
+    }
+
+    fn fmt_header(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let settings = &self.settings;
+        let board = match settings.board {
+            BoardShape::Rectangle{ width, height } => format!("rectangle {}x{}", width, height),
+            BoardShape::Hexagon{ radius } => format!("hexagon {}", radius),
+            BoardShape::Triangle{ size } => format!("triangle {}", size),
+        };
+        let start_ports = match &settings.start_ports {
+            StartPorts::Boundary => "boundary".to_owned(),
+            StartPorts::All => "all".to_owned(),
+            StartPorts::Custom(ports) => ports.iter().map(port_notation).join(" "),
+        };
+        let token_placement = match settings.token_placement {
+            TokenPlacement::Sequential => "sequential",
+            TokenPlacement::Simultaneous => "simultaneous",
+        };
+        let time_control = match settings.time_control {
+            None => "none".to_owned(),
+            Some(TimeControl::PerMove{ seconds }) => format!("{} per move", seconds),
+            Some(TimeControl::Clock{ budget_seconds, increment_seconds }) => format!("{}+{}", budget_seconds, increment_seconds),
+        };
+        let timeout_policy = match settings.timeout_policy {
+            TimeoutPolicy::RandomMove => "random move",
+            TimeoutPolicy::Eliminate => "eliminate",
+        };
+        let deck = match &settings.deck {
+            None => "full".to_owned(),
+            Some(entries) => entries.iter()
+                .map(|(id, copies)| if *copies == 1 { id.to_string() } else { format!("{}*{}", id, copies) })
+                .join(" "),
+        };
+
+        write_tag(f, "Board", &board)?;
+        write_tag(f, "PortsPerEdge", &settings.ports_per_edge.to_string())?;
+        write_tag(f, "TilesPerPlayer", &settings.tiles_per_player.iter().map(|(_, num)| num).join(" "))?;
+        write_tag(f, "StartPorts", &start_ports)?;
+        write_tag(f, "MinPlayers", &settings.min_players.to_string())?;
+        write_tag(f, "MaxPlayers", &settings.max_players.to_string())?;
+        write_tag(f, "TokenPlacement", token_placement)?;
+        write_tag(f, "NoSuicide", &settings.no_suicide.to_string())?;
+        write_tag(f, "TokenCollisions", &settings.token_collisions.to_string())?;
+        write_tag(f, "TimeControl", &time_control)?;
+        write_tag(f, "TimeoutPolicy", timeout_policy)?;
+        write_tag(f, "Deck", &deck)?;
+        write_tag(f, "Seed", &self.replay.seed().to_string())?;
+        for player in self.replay.players() {
+            write_tag(f, "Player", player)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_header(f)?;
+        writeln!(f)?;
+
+        let catalog = self.replay.game().tile_catalog();
+        let mut state = self.replay.initial_state();
+        for (i, mv) in self.replay.moves().iter().enumerate() {
+            // `new` and `from_str` check every move, so this always succeeds
+            match written_move(mv, &catalog, &state).ok_or(fmt::Error)? {
+                ParsedMove::PlaceToken{ player, port } => writeln!(f, "{}. {} token {}", i + 1, player, port_notation(&port))?,
+                ParsedMove::PlaceTile{ player, id, rotation, loc } =>
+                    writeln!(f, "{}. {} tile {}r{} {}", i + 1, player, id, rotation, loc_notation(&loc))?,
+                ParsedMove::Forfeit{ player } => writeln!(f, "{}. {} forfeit", i + 1, player)?,
+            }
+            self.replay.apply(&mut state, mv);
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for GameRecord {
+    type Err = NotationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines = s.lines().enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with(';'))
+            .collect_vec();
+        let num_tags = lines.iter().take_while(|(_, line)| line.starts_with('[')).count();
+        let (tag_lines, move_lines) = lines.split_at(num_tags);
+
+        let tags = tag_lines.iter()
+            .map(|(line, text)| parse_tag(text).map(|(tag, value)| (*line, tag, value)).ok_or(NotationError::Syntax{ line: *line }))
+            .collect::<Result<Vec<_>, _>>()?;
+        let (settings, seed, players) = parse_header(&tags)?;
+        let game = settings.build().map_err(NotationError::InvalidSettings)?;
+        check_num_players(&settings, players.len())?;
+
+        let catalog = game.tile_catalog();
+        let mut replay = Replay::new(game.clone(), players, seed);
+        let mut state = replay.initial_state();
+        for (line, text) in move_lines {
+            let mv = parse_move(text, &settings.board, &catalog).ok_or(NotationError::Syntax{ line: *line })?;
+            let mv = resolve_move(mv, &game, &catalog, &mut state, replay.players().len() as u32)
+                .ok_or(NotationError::IllegalMove{ line: *line })?;
+            replay.apply(&mut state, &mv);
+            replay.push(mv);
+        }
+
+        Ok(Self { settings, replay })
+    }
+}
+
+/// A move as written, before the placed tile is found in the player's hand
+enum ParsedMove {
+    PlaceToken{ player: u32, port: BasePort },
+    /// The tile is given by its catalog id and rotation, which is less than the tile's number of distinct rotations
+    PlaceTile{ player: u32, id: u32, rotation: u32, loc: BaseTLoc },
+    Forfeit{ player: u32 },
+}
+
+fn write_tag(f: &mut fmt::Formatter<'_>, tag: &str, value: &str) -> fmt::Result {
+    write!(f, "[{} \"", tag)?;
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    writeln!(f, "\"]")
+}
+
+/// Parses a `[Tag "value"]` line into the tag and the unescaped value
+fn parse_tag(line: &str) -> Option<(&str, String)> {
+    let (tag, value) = line.strip_prefix('[')?.strip_suffix(']')?.split_once(' ')?;
+    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.push(chars.next()?),
+            '"' => return None,
+            c => unescaped.push(c),
+        }
+    }
+    Some((tag, unescaped))
+}
+
+fn parse_header(tags: &[(usize, &str, String)]) -> Result<(GameSettings, u64, Vec<String>), NotationError> {
+    let mut settings = GameSettings::default();
+    let mut seed = None;
+    let mut players = vec![];
+    // Custom start ports need the board shape to be parsed, so they're parsed after every other tag
+    let mut start_ports = None;
+
+    for (line, tag, value) in tags {
+        let invalid = || NotationError::InvalidTag{ line: *line, tag: tag.to_string() };
+        match *tag {
+            "Board" => settings.board = parse_board(value).ok_or_else(invalid)?,
+            "PortsPerEdge" => settings.ports_per_edge = value.parse().map_err(|_| invalid())?,
+            "TilesPerPlayer" => settings.tiles_per_player = value.split_whitespace()
+                .map(|num| num.parse().map(|num| (().wrap_base(), num)))
+                .collect::<Result<_, _>>().map_err(|_| invalid())?,
+            "StartPorts" => start_ports = Some((*line, value)),
+            "MinPlayers" => settings.min_players = value.parse().map_err(|_| invalid())?,
+            "MaxPlayers" => settings.max_players = value.parse().map_err(|_| invalid())?,
+            "TokenPlacement" => settings.token_placement = match value.as_str() {
+                "sequential" => TokenPlacement::Sequential,
+                "simultaneous" => TokenPlacement::Simultaneous,
+                _ => return Err(invalid()),
+            },
+            "NoSuicide" => settings.no_suicide = value.parse().map_err(|_| invalid())?,
+            "TokenCollisions" => settings.token_collisions = value.parse().map_err(|_| invalid())?,
+            "TimeControl" => settings.time_control = parse_time_control(value).ok_or_else(invalid)?,
+            "TimeoutPolicy" => settings.timeout_policy = match value.as_str() {
+                "random move" => TimeoutPolicy::RandomMove,
+                "eliminate" => TimeoutPolicy::Eliminate,
+                _ => return Err(invalid()),
+            },
+            "Deck" => settings.deck = parse_deck(value).ok_or_else(invalid)?,
+            "Seed" => seed = Some(value.parse().map_err(|_| invalid())?),
+            "Player" => players.push(value.clone()),
+            // Unknown tags are allowed, for things like event names and dates
+            _ => {}
+        }
+    }
+
+    if let Some((line, value)) = start_ports {
+        settings.start_ports = match value.as_str() {
+            "boundary" => StartPorts::Boundary,
+            "all" => StartPorts::All,
+            ports => StartPorts::Custom(ports.split_whitespace()
+                .map(|port| parse_port(&settings.board, port))
+                .collect::<Option<_>>()
+                .ok_or_else(|| NotationError::InvalidTag{ line, tag: "StartPorts".to_owned() })?),
+        };
+    }
+    let seed = seed.ok_or(NotationError::MissingTag("Seed"))?;
+    if players.is_empty() {
+        return Err(NotationError::MissingTag("Player"));
+    }
+    Ok((settings, seed, players))
+}
+
+/// Checks that a game with these settings can have `num_players` players.
+/// Settings that build check there's a start port for every player they allow.
+fn check_num_players(settings: &GameSettings, num_players: usize) -> Result<(), NotationError> {
+    if (settings.min_players as usize..=settings.max_players as usize).contains(&num_players) {
+        Ok(())
+    } else {
+        Err(NotationError::WrongNumberOfPlayers{ num_players })
+    }
+}
+
+fn parse_board(value: &str) -> Option<BoardShape> {
+    let (shape, size) = value.split_once(' ')?;
+    match shape {
+        "rectangle" => {
+            let (width, height) = size.split_once('x')?;
+            Some(BoardShape::Rectangle{ width: width.parse().ok()?, height: height.parse().ok()? })
+        }
+        "hexagon" => Some(BoardShape::Hexagon{ radius: size.parse().ok()? }),
+        "triangle" => Some(BoardShape::Triangle{ size: size.parse().ok()? }),
+        _ => None,
+    }
+}
+
+/// Outer None if the value is invalid, inner None if there's no time control
+fn parse_time_control(value: &str) -> Option<Option<TimeControl>> {
+    if value == "none" {
+        Some(None)
+    } else if let Some(seconds) = value.strip_suffix(" per move") {
+        Some(Some(TimeControl::PerMove{ seconds: seconds.parse().ok()? }))
+    } else {
+        let (budget, increment) = value.split_once('+')?;
+        Some(Some(TimeControl::Clock{ budget_seconds: budget.parse().ok()?, increment_seconds: increment.parse().ok()? }))
+    }
+}
+
+/// Outer None if the value is invalid, inner None for the full deck
+fn parse_deck(value: &str) -> Option<Option<Vec<(u32, u32)>>> {
+    if value == "full" {
+        return Some(None);
+    }
+    value.split_whitespace()
+        .map(|entry| match entry.split_once('*') {
+            Some((id, copies)) => Some((id.parse().ok()?, copies.parse().ok()?)),
+            None => Some((entry.parse().ok()?, 1)),
+        })
+        .collect::<Option<_>>()
+        .map(Some)
+}
+
+fn parse_move(text: &str, board: &BoardShape, catalog: &BaseTileCatalog) -> Option<ParsedMove> {
+    let mut words = text.split_whitespace().peekable();
+    // The move number is optional
+    words.next_if(|word| word.ends_with('.'));
+    let player = words.next()?.parse().ok()?;
+
+    let mv = match words.next()? {
+        "token" => ParsedMove::PlaceToken{ player, port: parse_port(board, words.next()?)? },
+        "tile" => {
+            let (id, rotation) = words.next()?.split_once('r')?;
+            let id = id.parse().ok().filter(|id| *id < catalog.len())?;
+            let num_rotations = catalog.num_distinct_rotations(id)?;
+            let rotation = rotation.parse().ok().filter(|rotation| *rotation < num_rotations)?;
+            ParsedMove::PlaceTile{ player, id, rotation, loc: parse_loc(board, words.next()?)? }
+        }
+        "forfeit" => ParsedMove::Forfeit{ player },
+        _ => return None,
+    };
+    words.next().is_none().then_some(mv)
+}
+
+/// Turns a move of the replay into the move as written, finding the placed tile in the tile catalog.
+/// None if the tile isn't in the player's hand.
+fn written_move(mv: &Move, catalog: &BaseTileCatalog, state: &BaseGameState) -> Option<ParsedMove> {
+    Some(match mv {
+        Move::PlaceToken{ player, port } => ParsedMove::PlaceToken{ player: *player, port: port.clone() },
+        Move::PlaceTile{ player, kind, index, action, loc } => {
+            let tile = state.hand_tile(*player, kind, *index)?.apply_action(action);
+            let (id, rotation) = catalog.id(&tile)?;
+            ParsedMove::PlaceTile{ player: *player, id, rotation: rotation as u32, loc: loc.clone() }
+        }
+        Move::Forfeit{ player } => ParsedMove::Forfeit{ player: *player },
+    })
+}
+
+/// Turns a parsed move into a move of the replay, finding the placed tile in the player's hand.
+/// None if the move is illegal.
+fn resolve_move(mv: ParsedMove, game: &BaseGame, catalog: &BaseTileCatalog, state: &mut BaseGameState, num_players: u32)
+    -> Option<Move>
+{
+    match mv {
+        ParsedMove::PlaceToken{ player, port } => (player < num_players && state.can_place_token(game, player, &port))
+            .then_some(Move::PlaceToken{ player, port }),
+
+        ParsedMove::PlaceTile{ player, id, rotation, loc } => {
+            if player >= num_players {
+                return None;
+            }
+            let num_rotations = catalog.num_distinct_rotations(id)? as i32;
+            let (kind, index, action) = state.player_state(player)?.tiles_vec().into_iter()
+                .flat_map(|(kind, hand)| hand.into_iter().enumerate()
+                    .map(move |(index, hand_tile)| (kind.clone(), index, hand_tile)))
+                .find_map(|(kind, index, hand_tile)| catalog.id(&hand_tile)
+                    .filter(|(hand_id, _)| *hand_id == id)
+                    .map(|(_, hand_rotation)| {
+                        // Both rotations are less than the number of distinct rotations, so this can't overflow
+                        let rotation = (rotation as i32 - hand_rotation).rem_euclid(num_rotations);
+                        (kind, index as u32, hand_tile.rotation_action(rotation))
+                    }))?;
+            state.can_place_tile(game, player, &kind, index, &action, &loc)
+                .then_some(Move::PlaceTile{ player, kind, index, action, loc })
+        }
+
+        ParsedMove::Forfeit{ player } => (player < num_players && state.player_state(player).is_some() && !state.game_over())
+            .then_some(Move::Forfeit{ player }),
+    }
+}
+
+/// Something with a short text form in game records
+trait Notation: Sized {
+    fn notation(&self) -> String;
+
+    fn parse_notation(s: &str) -> Option<Self>;
+}
+
+fn parse_pair<T: FromStr>(s: &str) -> Option<(T, T)> {
+    let (x, y) = s.split_once(',')?;
+    Some((x.parse().ok()?, y.parse().ok()?))
+}
+
+impl Notation for Pt2u {
+    fn notation(&self) -> String {
+        format!("{},{}", self.x, self.y)
+    }
+
+    fn parse_notation(s: &str) -> Option<Self> {
+        parse_pair(s).map(|(x, y)| pt2(x, y))
+    }
+}
+
+impl Notation for Pt2i {
+    fn notation(&self) -> String {
+        format!("{},{}", self.x, self.y)
+    }
+
+    fn parse_notation(s: &str) -> Option<Self> {
+        parse_pair(s).map(|(x, y)| pt2(x, y))
+    }
+}
+
+impl Notation for (Pt2u, bool) {
+    fn notation(&self) -> String {
+        format!("{}{}", self.0.notation(), if self.1 { "v" } else { "" })
+    }
+
+    fn parse_notation(s: &str) -> Option<Self> {
+        match s.strip_suffix('v') {
+            Some(pt) => Some((Pt2u::parse_notation(pt)?, true)),
+            None => Some((Pt2u::parse_notation(s)?, false)),
+        }
+    }
+}
+
+impl Notation for (Pt2u, Vec2u) {
+    fn notation(&self) -> String {
+        format!("{}+{},{}", self.0.notation(), self.1.x, self.1.y)
+    }
+
+    fn parse_notation(s: &str) -> Option<Self> {
+        let (pt, offset) = s.split_once('+')?;
+        let (dx, dy) = parse_pair(offset)?;
+        Some((Pt2u::parse_notation(pt)?, Vec2u::new(dx, dy)))
+    }
+}
+
+impl Notation for (Pt2i, u32) {
+    fn notation(&self) -> String {
+        format!("{}:{}", self.0.notation(), self.1)
+    }
+
+    fn parse_notation(s: &str) -> Option<Self> {
+        let (pt, index) = s.split_once(':')?;
+        Some((Pt2i::parse_notation(pt)?, index.parse().ok()?))
+    }
+}
+
+impl Notation for (Pt2u, u32) {
+    fn notation(&self) -> String {
+        format!("{}:{}", self.0.notation(), self.1)
+    }
+
+    fn parse_notation(s: &str) -> Option<Self> {
+        let (pt, index) = s.split_once(':')?;
+        Some((Pt2u::parse_notation(pt)?, index.parse().ok()?))
+    }
+}
+
+fn loc_notation(loc: &BaseTLoc) -> String {
+    match loc {
+        BaseTLoc::Pt2u(loc) => loc.notation(),
+        BaseTLoc::Pt2i(loc) => loc.notation(),
+        BaseTLoc::Pt2uBool(loc) => loc.notation(),
+    }
+}
+
+fn port_notation(port: &BasePort) -> String {
+    match port {
+        BasePort::Pt2uVec2u(port) => port.notation(),
+        BasePort::Pt2iU32(port) => port.notation(),
+        BasePort::Pt2uU32(port) => port.notation(),
+    }
+}
+
+/// Parses a tile location on a board of some shape
+fn parse_loc(board: &BoardShape, s: &str) -> Option<BaseTLoc> {
+    match board {
+        BoardShape::Rectangle{ .. } => Pt2u::parse_notation(s).map(|loc| loc.wrap_base()),
+        BoardShape::Hexagon{ .. } => Pt2i::parse_notation(s).map(|loc| loc.wrap_base()),
+        BoardShape::Triangle{ .. } => <(Pt2u, bool)>::parse_notation(s).map(|loc| loc.wrap_base()),
+    }
+}
+
+/// Parses a port on a board of some shape
+fn parse_port(board: &BoardShape, s: &str) -> Option<BasePort> {
+    match board {
+        BoardShape::Rectangle{ .. } => <(Pt2u, Vec2u)>::parse_notation(s).map(|port| port.wrap_base()),
+        BoardShape::Hexagon{ .. } => <(Pt2i, u32)>::parse_notation(s).map(|port| port.wrap_base()),
+        BoardShape::Triangle{ .. } => <(Pt2u, u32)>::parse_notation(s).map(|port| port.wrap_base()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::replay::tests::play_first_moves;
+
+    use super::*;
+
+    /// Plays a game by always making the first legal move, then forfeiting if it isn't over, and records it
+    fn play(settings: &GameSettings, num_players: u32, num_tile_moves: u32) -> GameRecord {
+        let game = settings.build().unwrap();
+        let players = (0..num_players).map(|i| format!("player \"{}\"", i)).collect_vec();
+        let (mut replay, mut state) = play_first_moves(&game, players, 1234, num_tile_moves);
+        if !state.game_over() {
+            let player = state.turn_player();
+            state.forfeit(&game, player);
+            replay.push(Move::Forfeit{ player });
+        }
+        GameRecord::new(settings.clone(), replay).unwrap()
+    }
+
+    fn assert_round_trip(record: &GameRecord) {
+        let text = record.to_string();
+        let parsed = text.parse::<GameRecord>().unwrap_or_else(|err| panic!("{}\n{}", err, text));
+        assert_eq!(parsed.settings(), record.settings());
+        assert_eq!(parsed.replay().players(), record.replay().players());
+        assert_eq!(parsed.replay().moves().len(), record.replay().moves().len());
+        assert_eq!(bincode::serialize(&parsed.replay().final_state()).unwrap(),
+            bincode::serialize(&record.replay().final_state()).unwrap());
+        assert_eq!(parsed.to_string(), text);
+    }
+
+    #[test]
+    fn test_notation_round_trip() {
+        assert_round_trip(&play(&GameSettings::default(), 3, 8));
+        assert_round_trip(&play(&GameSettings {
+            board: BoardShape::Hexagon{ radius: 2 },
+            time_control: Some(TimeControl::Clock{ budget_seconds: 300, increment_seconds: 5 }),
+            timeout_policy: TimeoutPolicy::Eliminate,
+            ..GameSettings::default()
+        }, 2, 8));
+        assert_round_trip(&play(&GameSettings {
+            board: BoardShape::Triangle{ size: 4 },
+            max_players: 2,
+            token_placement: TokenPlacement::Simultaneous,
+            time_control: Some(TimeControl::PerMove{ seconds: 30 }),
+            ..GameSettings::default()
+        }, 2, 8));
+
+        let settings = GameSettings {
+            start_ports: StartPorts::Custom(vec![
+                (pt2(0, 0), Vec2u::new(1, 0)).wrap_base(),
+                (pt2(0, 0), Vec2u::new(2, 0)).wrap_base(),
+            ]),
+            max_players: 2,
+            deck: Some(vec![(0, 2), (3, 1), (17, 4)]),
+            ..GameSettings::default()
+        };
+        assert_round_trip(&play(&settings, 2, 4));
+    }
+
+    #[test]
+    fn test_notation_parse() {
+        let text = "\
+            [Board \"rectangle 3x3\"]\n\
+            [Seed \"1\"]\n\
+            [Player \"a\"]\n\
+            [Player \"b\"]\n\
+            \n\
+            ; A comment\n\
+            1. 0 token 0,0+1,0\n\
+            1 token 0,0+2,0\n\
+            0 forfeit\n";
+        let record = text.parse::<GameRecord>().unwrap();
+        assert_eq!(record.settings().board, BoardShape::Rectangle{ width: 3, height: 3 });
+        assert_eq!(record.settings().ports_per_edge, 2);
+        assert_eq!(record.replay().moves().len(), 3);
+        assert!(record.replay().final_state().game_over());
+
+        assert_eq!("[Player \"a\"]\n".parse::<GameRecord>().unwrap_err(), NotationError::MissingTag("Seed"));
+        assert_eq!(text.replace("[Player \"b\"]\n", "").parse::<GameRecord>().unwrap_err(),
+            NotationError::WrongNumberOfPlayers{ num_players: 1 });
+        let too_many = text.replace("[Player \"b\"]\n", "[MaxPlayers \"2\"]\n[Player \"b\"]\n[Player \"c\"]\n");
+        assert_eq!(too_many.parse::<GameRecord>().unwrap_err(), NotationError::WrongNumberOfPlayers{ num_players: 3 });
+        assert_eq!(text.replace("0 forfeit", "0 dance").parse::<GameRecord>().unwrap_err(), NotationError::Syntax{ line: 9 });
+        assert_eq!(text.replace("1 token 0,0+2,0", "1 token 0,0+1,0").parse::<GameRecord>().unwrap_err(),
+            NotationError::IllegalMove{ line: 8 });
+
+        // Rotations out of range are rejected rather than overflowing
+        for rotation in ["-2147483648", "4294967296", "4"] {
+            let tile_move = format!("1 tile 0r{} 0,0", rotation);
+            assert_eq!(text.replace("0 forfeit", &tile_move).parse::<GameRecord>().unwrap_err(),
+                NotationError::Syntax{ line: 9 });
+        }
+    }
+
+    #[test]
+    fn test_notation_new_checks_replay() {
+        let record = play(&GameSettings::default(), 2, 4);
+        let hex = GameSettings { board: BoardShape::Hexagon{ radius: 2 }, ..GameSettings::default() };
+        assert_eq!(GameRecord::new(hex, record.replay().clone()).unwrap_err(), NotationError::MismatchedSettings);
+
+        let settings = GameSettings::default();
+        let game = settings.build().unwrap();
+        let port = game.start_ports().swap_remove(0);
+        let mut replay = Replay::new(game, vec!["a".to_owned(), "b".to_owned()], 1);
+        replay.push(Move::PlaceToken{ player: 0, port: port.clone() });
+        replay.push(Move::PlaceToken{ player: 1, port });
+        assert_eq!(GameRecord::new(settings.clone(), replay.clone()).unwrap_err(), NotationError::IllegalReplayMove{ number: 2 });
+
+        let mut replay = Replay::new(settings.build().unwrap(), vec!["a".to_owned(), "b".to_owned()], 1);
+        replay.push(Move::Forfeit{ player: 3 });
+        assert_eq!(GameRecord::new(settings, replay).unwrap_err(), NotationError::IllegalReplayMove{ number: 1 });
+    }
+
+    #[test]
+    fn test_notation_locs_and_ports() {
+        let rect = BoardShape::Rectangle{ width: 6, height: 6 };
+        let hex = BoardShape::Hexagon{ radius: 2 };
+        let tri = BoardShape::Triangle{ size: 4 };
+
+        assert_eq!(parse_loc(&rect, "2,3"), Some(pt2(2u32, 3).wrap_base()));
+        assert_eq!(parse_loc(&hex, "-1,2"), Some(pt2(-1i32, 2).wrap_base()));
+        assert_eq!(parse_loc(&tri, "1,3v"), Some((pt2(1u32, 3), true).wrap_base()));
+        assert_eq!(parse_loc(&tri, "1,3"), Some((pt2(1u32, 3), false).wrap_base()));
+        assert_eq!(parse_loc(&rect, "-1,3"), None);
+        assert_eq!(parse_port(&rect, "2,3+0,1"), Some((pt2(2u32, 3), Vec2u::new(0, 1)).wrap_base()));
+        assert_eq!(parse_port(&hex, "0,-2:5"), Some((pt2(0i32, -2), 5u32).wrap_base()));
+        assert_eq!(parse_port(&tri, "0,2:4"), Some((pt2(0u32, 2), 4u32).wrap_base()));
+
+        for (board, s) in [(&rect, "2,3"), (&hex, "-1,2"), (&tri, "1,3v"), (&tri, "0,0")] {
+            assert_eq!(loc_notation(&parse_loc(board, s).unwrap()), s);
+        }
+        for (board, s) in [(&rect, "2,3+0,1"), (&hex, "0,-2:5"), (&tri, "0,2:4")] {
+            assert_eq!(port_notation(&parse_port(board, s).unwrap()), s);
+        }
+    }
+}
@@ -17,6 +17,8 @@ macro_rules! for_each_player_state {
         }
         __mac! {
             ($crate::player_state::BasePlayerState)::RegularTile4: $crate::player_state::PlayerState<$crate::tile::RegularTile<4>>,
+            ($crate::player_state::BasePlayerState)::RegularTile6: $crate::player_state::PlayerState<$crate::tile::RegularTile<6>>,
+            ($crate::player_state::BasePlayerState)::RegularTile3: $crate::player_state::PlayerState<$crate::tile::RegularTile<3>>,
         }
     };
 
@@ -47,7 +49,7 @@ for_each_player_state! {
         }
     }
 
-    $($crate::impl_wrap_base!(BasePlayerState::$x($t)))*;
+    $($crate::impl_wrap_base!(BasePlayerState::$x($t));)*
 }
 
 /// Someone that looks at the game
@@ -63,6 +65,12 @@ impl Looker {
     pub fn tag(self) -> LookerTag {
         self.into()
     }
+
+    /// Whether this looker is allowed to see the tiles in `player`'s hand.
+    /// Only the server and the player themselves can; anyone can spectate, so spectators can't.
+    pub fn can_see_hand_of(self, player: u32) -> bool {
+        self == Looker::Server || self == Looker::Player(player)
+    }
 }
 
 /// The state of a player
@@ -108,16 +116,27 @@ impl<T: Tile> PlayerState<T> {
             .remove(index as usize)
     }
 
+    /// Replaces a tile in the player's hand by kind and index, probably because it got revealed.
+    /// For now, assumes the index exists.
+    pub fn replace_tile(&mut self, kind: &T::Kind, index: u32, tile: T) {
+        self.tiles.get_mut(kind).expect("Every kind should have a tile list")[index as usize] = tile;
+    }
+
     /// Removes and returns all tiles from the player's hand, probably because the player is dead.
     pub fn remove_all_tiles(&mut self) -> Vec<T> {
         self.tiles.values_mut().flat_map(|v| std::mem::take(v)).collect_vec()
     }
 
-    /// Returns the state of `player` visible to `looker`
+    /// Returns the state of `player` visible to `looker`.
+    /// Tiles `looker` can't see are replaced with placeholders.
     pub fn visible_state(&self, player: u32, looker: Looker) -> PlayerState<T> {
         let mut result = self.clone();
         for tile in result.tiles.values_mut().into_iter().flatten() {
-            tile.set_visible(looker.tag() != LookerTag::Player || looker == Looker::Player(player));
+            if looker.can_see_hand_of(player) {
+                tile.set_visible(true);
+            } else {
+                *tile = tile.hidden();
+            }
         }
         result
     }
@@ -0,0 +1,129 @@
+use getset::{Getters, CopyGetters};
+use serde::{Deserialize, Serialize};
+
+use crate::board::{BasePort, BaseTLoc};
+use crate::game::BaseGame;
+use crate::game_state::BaseGameState;
+use crate::tile::{BaseGAct, BaseKind};
+
+/// A move made in a game, as recorded in its move log
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Move {
+    /// Player `player` placed their token on port `port`
+    PlaceToken{ player: u32, port: BasePort },
+    /// Player `player` placed the tile of kind `kind` at index `index` in their hand, transformed by `action`, at `loc`
+    PlaceTile{ player: u32, kind: BaseKind, index: u32, action: BaseGAct, loc: BaseTLoc },
+    /// Player `player` forfeited
+    Forfeit{ player: u32 },
+}
+
+impl Move {
+    /// The player who made the move
+    pub fn player(&self) -> u32 {
+        match self {
+            Move::PlaceToken{ player, .. } | Move::PlaceTile{ player, .. } | Move::Forfeit{ player } => *player,
+        }
+    }
+}
+
+/// Everything needed to reproduce a game exactly:
+/// the game, the seed its draw pile was shuffled with, and the moves made in order
+#[derive(Clone, Debug, Getters, CopyGetters, Serialize, Deserialize)]
+pub struct Replay {
+    #[getset(get = "pub")]
+    game: BaseGame,
+    /// Usernames of the players
+    #[getset(get = "pub")]
+    players: Vec<String>,
+    #[getset(get_copy = "pub")]
+    seed: u64,
+    #[getset(get = "pub")]
+    moves: Vec<Move>,
+}
+
+impl Replay {
+    /// Construct a replay with no moves yet
+    pub fn new(game: BaseGame, players: Vec<String>, seed: u64) -> Self {
+        Self { game, players, seed, moves: vec![] }
+    }
+
+    /// Records a move. It must be legal in the state after the moves recorded so far.
+    pub fn push(&mut self, mv: Move) {
+        self.moves.push(mv);
+    }
+
+    /// The state the game started with
+    pub fn initial_state(&self) -> BaseGameState {
+        self.game.new_state_seeded(self.players.len() as u32, self.seed)
+    }
+
+    /// Makes a move on a state
+    pub fn apply(&self, state: &mut BaseGameState, mv: &Move) {
+        match mv {
+            Move::PlaceToken{ player, port } => state.take_turn_placing_token(&self.game, *player, port),
+            Move::PlaceTile{ kind, index, action, loc, .. } => {
+                state.take_turn_placing_tile(&self.game, kind, *index, action, loc);
+            }
+            Move::Forfeit{ player } => {
+                state.forfeit(&self.game, *player);
+            }
+        }
+    }
+
+    /// The state after the first `num_moves` moves
+    pub fn state_after(&self, num_moves: usize) -> BaseGameState {
+        let mut state = self.initial_state();
+        for mv in &self.moves[..num_moves.min(self.moves.len())] {
+            self.apply(&mut state, mv);
+        }
+        state
+    }
+
+    /// The state after all the moves
+    pub fn final_state(&self) -> BaseGameState {
+        self.state_after(self.moves.len())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::game_settings::GameSettings;
+
+    use super::*;
+
+    /// Plays a game by always taking the first free start port and the first legal tile placement,
+    /// stopping after `num_tile_moves` tile placements or when the game ends.
+    /// Returns the replay and the state it ends in.
+    pub(crate) fn play_first_moves(game: &BaseGame, players: Vec<String>, seed: u64, num_tile_moves: u32) -> (Replay, BaseGameState) {
+        let num_players = players.len() as u32;
+        let mut replay = Replay::new(game.clone(), players, seed);
+        let mut state = replay.initial_state();
+
+        let mut ports = game.start_ports().into_iter();
+        for player in 0..num_players {
+            let port = ports.find(|port| state.can_place_player(game, port)).unwrap();
+            state.take_turn_placing_token(game, player, &port);
+            replay.push(Move::PlaceToken{ player, port });
+        }
+        for _ in 0..num_tile_moves {
+            if state.game_over() {
+                break;
+            }
+            let player = state.turn_player();
+            let (kind, index, action, loc) = state.legal_tile_placements(game, player).swap_remove(0);
+            state.take_turn_placing_tile(game, &kind, index, &action, &loc);
+            replay.push(Move::PlaceTile{ player, kind, index, action, loc });
+        }
+        (replay, state)
+    }
+
+    #[test]
+    fn test_replay() {
+        let game = GameSettings::default().build().unwrap();
+        let (replay, state) = play_first_moves(&game, vec!["a".to_owned(), "b".to_owned()], 1234, 6);
+
+        let replayed = replay.final_state();
+        assert_eq!(bincode::serialize(&replayed).unwrap(), bincode::serialize(&state).unwrap());
+        assert_eq!(replay.state_after(2).board_state().tiles_vec().len(), 0);
+    }
+}
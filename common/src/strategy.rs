@@ -0,0 +1,208 @@
+use itertools::Itertools;
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use crate::board::BasePort;
+use crate::game::BaseGame;
+use crate::game_state::{BaseGameState, BaseTilePlacement};
+use crate::player_state::Looker;
+use crate::tile::BaseTile;
+
+/// Something that decides moves for a player, like a bot or a person at a terminal.
+/// Strategies get the state as seen by the player they move for, so they can't peek at hidden tiles.
+pub trait Strategy {
+    /// Chooses a start port for the player looking at `state`. None if there's no port left.
+    fn choose_port(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BasePort>;
+
+    /// Chooses a tile placement for the player looking at `state`, whose turn it is.
+    /// None if there's no legal placement.
+    fn choose_tile_placement(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BaseTilePlacement>;
+}
+
+impl<S: Strategy + ?Sized> Strategy for Box<S> {
+    fn choose_port(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BasePort> {
+        (**self).choose_port(game, state)
+    }
+
+    fn choose_tile_placement(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BaseTilePlacement> {
+        (**self).choose_tile_placement(game, state)
+    }
+}
+
+/// The start ports that are still free
+pub(crate) fn free_ports(game: &BaseGame, state: &BaseGameState) -> Vec<BasePort> {
+    let mut state = state.clone();
+    game.start_ports().into_iter()
+        .filter(|port| state.can_place_player(game, port))
+        .collect_vec()
+}
+
+/// The ways `player` could place one of `tiles` next to their token without being eliminated.
+/// Lets strategies look ahead with tiles they know, since tiles drawn during simulations are hidden from them.
+pub(crate) fn num_safe_placements(game: &BaseGame, state: &BaseGameState, player: u32, tiles: &[BaseTile]) -> usize {
+    let locs = state.open_locs(game, player);
+    tiles.iter()
+        .flat_map(|tile| tile.all_rotations().into_iter().unique())
+        .cartesian_product(&locs)
+        .filter(|(tile, loc)| !state.placing_eliminates(game, player, tile, loc))
+        .count()
+}
+
+/// The tiles in `player`'s hand
+pub(crate) fn hand(state: &BaseGameState, player: u32) -> Vec<BaseTile> {
+    state.player_state(player)
+        .map_or(vec![], |hand| hand.tiles_vec().into_iter().flat_map(|(_, tiles)| tiles).collect())
+}
+
+/// Chooses randomly among the options with the highest score
+pub(crate) fn choose_best<T, S: Ord>(options: Vec<T>, mut score: impl FnMut(&T) -> S, rng: &mut impl Rng) -> Option<T> {
+    let mut scored = options.into_iter().map(|option| (score(&option), option)).collect_vec();
+    let best_score = scored.iter().map(|(score, _)| score).max()?;
+    let best = scored.iter().positions(|(score, _)| score == best_score).collect_vec();
+    let chosen = *best.choose(rng)?;
+    Some(scored.swap_remove(chosen).1)
+}
+
+/// Makes random legal moves
+#[derive(Clone, Debug)]
+pub struct RandomStrategy<R: Rng> {
+    rng: R,
+}
+
+impl<R: Rng> RandomStrategy<R> {
+    pub fn new(rng: R) -> Self {
+        Self { rng }
+    }
+}
+
+impl<R: Rng> Strategy for RandomStrategy<R> {
+    fn choose_port(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BasePort> {
+        free_ports(game, state).choose(&mut self.rng).cloned()
+    }
+
+    fn choose_tile_placement(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BaseTilePlacement> {
+        state.legal_tile_placements(game, state.player_expect()).choose(&mut self.rng).cloned()
+    }
+}
+
+/// Avoids eliminating itself when it can.
+/// Starts on the port where its hand has the most ways to survive the first turn.
+#[derive(Clone, Debug)]
+pub struct GreedySurvivalStrategy<R: Rng> {
+    rng: R,
+}
+
+impl<R: Rng> GreedySurvivalStrategy<R> {
+    pub fn new(rng: R) -> Self {
+        Self { rng }
+    }
+}
+
+impl<R: Rng> Strategy for GreedySurvivalStrategy<R> {
+    fn choose_port(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BasePort> {
+        let player = state.player_expect();
+        let tiles = hand(state, player);
+        choose_best(free_ports(game, state), |port| {
+            let mut state = state.clone();
+            state.place_player(player, port);
+            num_safe_placements(game, &state, player, &tiles)
+        }, &mut self.rng)
+    }
+
+    fn choose_tile_placement(&mut self, game: &BaseGame, state: &BaseGameState) -> Option<BaseTilePlacement> {
+        let player = state.player_expect();
+        choose_best(state.legal_tile_placements(game, player), |(kind, index, action, loc)| {
+            let mut after = state.clone();
+            after.take_turn_placing_tile(game, kind, *index, action, loc);
+            after.player_state(player).is_some()
+        }, &mut self.rng)
+    }
+}
+
+/// Plays a game to the end, with each player's strategy choosing their moves from what they can see.
+/// Players whose strategy has no move for them forfeit.
+/// Returns the number of tiles placed.
+pub fn play_out(game: &BaseGame, state: &mut BaseGameState, strategies: &mut [impl Strategy]) -> u32 {
+    while !state.all_players_placed() && !state.game_over() {
+        for player in state.players_placing_token(game) {
+            let visible = state.visible_state(Looker::Player(player));
+            match strategies[player as usize].choose_port(game, &visible) {
+                Some(port) => state.take_turn_placing_token(game, player, &port),
+                None => {
+                    state.forfeit(game, player);
+                }
+            }
+        }
+    }
+
+    let mut num_tiles_placed = 0;
+    while !state.game_over() {
+        let player = state.turn_player();
+        let visible = state.visible_state(Looker::Player(player));
+        match strategies[player as usize].choose_tile_placement(game, &visible) {
+            Some((kind, index, action, loc)) => {
+                state.take_turn_placing_tile(game, &kind, index, &action, &loc);
+                num_tiles_placed += 1;
+            }
+            None => {
+                state.forfeit(game, player);
+            }
+        }
+    }
+    num_tiles_placed
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::game_settings::GameSettings;
+    use crate::pcg64_seeded;
+
+    use super::*;
+
+    #[test]
+    fn test_strategies_play_legal_moves() {
+        let game = GameSettings{ no_suicide: true, ..GameSettings::default() }.build().unwrap();
+        for seed in 0..3 {
+            let mut state = game.new_state_seeded(2, seed);
+            let mut strategies: Vec<Box<dyn Strategy>> = vec![
+                Box::new(RandomStrategy::new(pcg64_seeded(seed))),
+                Box::new(GreedySurvivalStrategy::new(pcg64_seeded(seed))),
+            ];
+
+            for player in 0..2 {
+                let visible = state.visible_state(Looker::Player(player));
+                let port = strategies[player as usize].choose_port(&game, &visible).unwrap();
+                assert!(state.can_place_token(&game, player, &port));
+                state.take_turn_placing_token(&game, player, &port);
+            }
+            let player = state.turn_player();
+            let visible = state.visible_state(Looker::Player(player));
+            let (kind, index, action, loc) = strategies[player as usize].choose_tile_placement(&game, &visible).unwrap();
+            assert!(state.can_place_tile(&game, player, &kind, index, &action, &loc));
+
+            play_out(&game, &mut state, &mut strategies);
+            assert!(state.game_over());
+        }
+    }
+
+    #[test]
+    fn test_greedy_survival_avoids_elimination() {
+        let game = GameSettings::default().build().unwrap();
+        for seed in 0..10 {
+            let mut state = game.new_state_seeded(2, seed);
+            let ports = game.start_ports();
+            state.take_turn_placing_token(&game, 0, &ports[0]);
+            state.take_turn_placing_token(&game, 1, &ports[ports.len() / 2]);
+
+            let visible = state.visible_state(Looker::Player(0));
+            let (kind, index, action, loc) = GreedySurvivalStrategy::new(pcg64_seeded(seed))
+                .choose_tile_placement(&game, &visible)
+                .unwrap();
+            let can_survive = state.legal_tile_placements(&game, 0).into_iter()
+                .any(|(kind, index, action, loc)| !state.clone().take_turn_placing_tile(&game, &kind, index, &action, &loc)
+                    .dead_players().iter().any(|(player, _)| *player == 0));
+            state.take_turn_placing_tile(&game, &kind, index, &action, &loc);
+            assert_eq!(state.player_state(0).is_some(), can_survive);
+        }
+    }
+}
@@ -1,6 +1,7 @@
-use std::{collections::HashSet, fmt::Debug};
+use std::fmt::{Debug, Display};
 use std::hash::Hash;
 
+use fnv::FnvHashMap;
 use getset::CopyGetters;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
@@ -119,6 +120,8 @@ macro_rules! for_each_tile {
         }
         __mac! {
             ($crate::tile::BaseTile)::RegularTile4: $crate::tile::RegularTile<4>,
+            ($crate::tile::BaseTile)::RegularTile6: $crate::tile::RegularTile<6>,
+            ($crate::tile::BaseTile)::RegularTile3: $crate::tile::RegularTile<3>,
         }
     };
 
@@ -147,6 +150,11 @@ for_each_tile! {
             match self { $($($p)*::$x(s) => s.rotate(num_times).wrap_base()),* }
         }
 
+        /// All rotations of this tile.
+        pub fn all_rotations(&self) -> Vec<Self> {
+            match self { $($($p)*::$x(s) => s.all_rotations().into_iter().map(|tile| tile.wrap_base()).collect()),* }
+        }
+
         /// Generate the identity group action.
         pub fn identity_action(&self) -> BaseGAct {
             match self { $($($p)*::$x(s) => s.identity_action().wrap_base()),* }
@@ -161,9 +169,37 @@ for_each_tile! {
         pub fn apply_action(&self, action: &BaseGAct) -> Self {
             match self { $($($p)*::$x(s) => s.apply_action(GAct::unwrap_base_ref(action)).wrap_base()),* }
         }
+
+        /// An opaque placeholder for this tile that only keeps its kind.
+        pub fn hidden(&self) -> Self {
+            match self { $($($p)*::$x(s) => s.hidden().wrap_base()),* }
+        }
     }
 
-    $($crate::impl_wrap_base!(BaseTile::$x($t)))*;
+    $($crate::impl_wrap_base!(BaseTile::$x($t));)*
+}
+
+/// Why tiles can't be generated from some tile configuration
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TileConfigError {
+    /// Tiles would have no ports at all
+    NoPorts,
+    /// Ports are connected in pairs, so there must be an even number of them
+    OddPortCount{ num_ports: u32 },
+    /// Too many ports to generate every tile in a reasonable amount of time
+    TooManyPorts{ num_ports: u32, max: u32 },
+}
+
+impl Display for TileConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoPorts => write!(f, "tiles must have at least 1 port"),
+            Self::OddPortCount{ num_ports } =>
+                write!(f, "tiles must have an even number of ports, but would have {}", num_ports),
+            Self::TooManyPorts{ num_ports, max } =>
+                write!(f, "tiles can have at most {} ports, but would have {}", max, num_ports),
+        }
+    }
 }
 
 /// A tile in the path game, parameterized by kind
@@ -172,26 +208,20 @@ pub trait Tile: Clone + Debug + Eq + Ord + Hash + Serialize + for<'a> Deserializ
     type GAct: GAct;
     type TileConfig: Clone + Debug;
 
+    /// Checks whether tiles can be generated from `config`.
+    fn check_config(config: &Self::TileConfig) -> Result<(), TileConfigError>;
+
     /// All tiles of this type, in no particular order, but a deterministic order.
     /// Rotations count as separate tiles.
     fn all_including_rotations(config: Self::TileConfig) -> Vec<Self> where Self: Sized;
 
-    /// All tiles of this type, in no particular order, but a deterministic order.
+    /// All tiles of this type in their canonical orientations, sorted.
     /// Rotations do not count as separate tiles.
     fn all(config: Self::TileConfig) -> Vec<Self> where Self: Sized {
-        let mut with_rotations = Self::all_including_rotations(config).into_iter().collect::<HashSet<_>>();
-
-        let mut groups = vec![];
-        while !with_rotations.is_empty() {
-            let tile = with_rotations.iter().next().unwrap().clone();
-            groups.push(tile.all_rotations().into_iter().map(|t| {
-                with_rotations.remove(&t);
-                t
-            }).collect_vec());
-        }
-
-        groups.into_iter().map(|group| group.into_iter().min_by_key(|tile| tile.clone()).unwrap())
+        Self::all_including_rotations(config).into_iter()
+            .map(|tile| tile.canonical())
             .sorted()
+            .dedup()
             .collect_vec()
     }
 
@@ -203,6 +233,12 @@ pub trait Tile: Clone + Debug + Eq + Ord + Hash + Serialize + for<'a> Deserializ
         self.all_rotations().into_iter().min_by_key(|tile| tile.clone()).unwrap()
     }
 
+    /// How many different orientations rotating this tile gives.
+    /// A tile that looks the same after every rotation has 1.
+    fn num_distinct_rotations(&self) -> u32 where Self: Sized {
+        self.all_rotations().into_iter().unique().count() as u32
+    }
+
     /// The kind of the tile
     fn kind(&self) -> &Self::Kind;
 
@@ -221,6 +257,10 @@ pub trait Tile: Clone + Debug + Eq + Ord + Hash + Serialize + for<'a> Deserializ
     /// Apply a group action to this tile.
     fn apply_action(&self, action: &Self::GAct) -> Self;
 
+    /// Whether `action` belongs to this tile's group, so it can be applied.
+    /// Actions can come from clients, which can send anything.
+    fn is_valid_action(&self, action: &Self::GAct) -> bool;
+
     /// The output port of some input port on the tile
     fn output(&self, input: u32) -> u32;
 
@@ -232,6 +272,114 @@ pub trait Tile: Clone + Debug + Eq + Ord + Hash + Serialize + for<'a> Deserializ
 
     /// Set the visibility of this tile
     fn set_visible(&mut self, visible: bool);
+
+    /// An opaque placeholder for this tile that only keeps its kind.
+    /// This is what gets sent to lookers that aren't allowed to see the tile.
+    fn hidden(&self) -> Self;
+}
+
+/// Every canonical tile of some tile configuration, numbered with stable ids.
+/// A tile's id is its index in `Tile::all`, which is sorted, so ids only change if the tiles themselves do.
+#[derive(Clone, Debug)]
+pub struct TileCatalog<T: Tile> {
+    tiles: Vec<T>,
+    /// The id of every rotation of every tile, along with the fewest clockwise rotations that give it from the canonical tile
+    ids: FnvHashMap<T, (u32, i32)>,
+}
+
+impl<T: Tile> TileCatalog<T> {
+    pub fn new(config: T::TileConfig) -> Self {
+        let tiles = T::all(config);
+        let mut ids = FnvHashMap::default();
+        for (id, tile) in tiles.iter().enumerate() {
+            for (rotation, rotated) in tile.all_rotations().into_iter().enumerate() {
+                ids.entry(rotated).or_insert((id as u32, rotation as i32));
+            }
+        }
+        Self { tiles, ids }
+    }
+
+    /// The number of tiles in the catalog
+    pub fn len(&self) -> u32 {
+        self.tiles.len() as u32
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tiles.is_empty()
+    }
+
+    /// The tiles in the catalog in their canonical orientations, by id
+    pub fn tiles(&self) -> &[T] {
+        &self.tiles
+    }
+
+    /// The tile with id `id` in its canonical orientation
+    pub fn tile(&self, id: u32) -> Option<&T> {
+        self.tiles.get(id as usize)
+    }
+
+    /// The tile with id `id`, rotated `rotation` times clockwise from its canonical orientation
+    pub fn tile_rotated(&self, id: u32, rotation: i32) -> Option<T> {
+        self.tile(id).map(|tile| tile.rotate(rotation))
+    }
+
+    /// The id of a tile in any orientation, and the fewest clockwise rotations that give it from the canonical orientation.
+    /// None if the tile isn't in the catalog, for example if it's hidden.
+    pub fn id(&self, tile: &T) -> Option<(u32, i32)> {
+        self.ids.get(tile).copied()
+    }
+
+    /// How many different orientations the tile with id `id` has
+    pub fn num_distinct_rotations(&self, id: u32) -> Option<u32> {
+        self.tile(id).map(|tile| tile.num_distinct_rotations())
+    }
+}
+
+for_each_tile! {
+    p::x, t =>
+    /// A catalog of one of the tile types in `BaseTile`
+    #[derive(Clone, Debug)]
+    pub enum BaseTileCatalog {
+        $($x(TileCatalog<$t>)),*
+    }
+
+    impl BaseTileCatalog {
+        /// The number of tiles in the catalog
+        pub fn len(&self) -> u32 {
+            match self { $(Self::$x(c) => c.len()),* }
+        }
+
+        pub fn is_empty(&self) -> bool {
+            match self { $(Self::$x(c) => c.is_empty()),* }
+        }
+
+        /// The tile with id `id` in its canonical orientation
+        pub fn tile(&self, id: u32) -> Option<BaseTile> {
+            match self { $(Self::$x(c) => c.tile(id).map(|tile| tile.clone().wrap_base())),* }
+        }
+
+        /// The tile with id `id`, rotated `rotation` times clockwise from its canonical orientation
+        pub fn tile_rotated(&self, id: u32, rotation: i32) -> Option<BaseTile> {
+            match self { $(Self::$x(c) => c.tile_rotated(id, rotation).map(|tile| tile.wrap_base())),* }
+        }
+
+        /// The id of a tile in any orientation, and the fewest clockwise rotations that give it from the canonical orientation.
+        /// None if the tile isn't in the catalog or is of another tile type.
+        pub fn id(&self, tile: &BaseTile) -> Option<(u32, i32)> {
+            match (self, tile) {
+                $((Self::$x(c), BaseTile::$x(tile)) => c.id(tile),)*
+                #[allow(unreachable_patterns)]
+                _ => None,
+            }
+        }
+
+        /// How many different orientations the tile with id `id` has
+        pub fn num_distinct_rotations(&self, id: u32) -> Option<u32> {
+            match self { $(Self::$x(c) => c.num_distinct_rotations(id)),* }
+        }
+    }
+
+    $($crate::impl_wrap_base!(BaseTileCatalog::$x(TileCatalog<$t>));)*
 }
 
 /// A regular-polygon-shaped tile with `EDGES` edges.
@@ -243,6 +391,9 @@ pub struct RegularTile<const EDGES: u32> {
 }
 
 impl<const EDGES: u32> RegularTile<EDGES> {
+    /// Maximum number of ports on a tile, since the number of tiles grows factorially with it
+    pub const MAX_PORTS: u32 = 12;
+
     pub fn new(connections: Vec<u32>) -> Self {
         Self { connections, visible: true }
     }
@@ -257,6 +408,21 @@ impl<const EDGES: u32> Tile for RegularTile<EDGES> {
     type GAct = CycleGAct;
     type TileConfig = PortsPerEdgeTileConfig;
 
+    fn check_config(ports_per_edge: &Self::TileConfig) -> Result<(), TileConfigError> {
+        let Some(num_ports) = ports_per_edge.0.checked_mul(EDGES) else {
+            return Err(TileConfigError::TooManyPorts{ num_ports: u32::MAX, max: Self::MAX_PORTS });
+        };
+        if num_ports == 0 {
+            Err(TileConfigError::NoPorts)
+        } else if num_ports % 2 == 1 {
+            Err(TileConfigError::OddPortCount{ num_ports })
+        } else if num_ports > Self::MAX_PORTS {
+            Err(TileConfigError::TooManyPorts{ num_ports, max: Self::MAX_PORTS })
+        } else {
+            Ok(())
+        }
+    }
+
     fn all_including_rotations(ports_per_edge: Self::TileConfig) -> Vec<Self> where Self: Sized {
         assert!(ports_per_edge.0 * EDGES % 2 == 0, "Tried to create {}-sided RegularTile with {} ports per edge, an odd number",
             EDGES, ports_per_edge.0);
@@ -326,9 +492,18 @@ impl<const EDGES: u32> Tile for RegularTile<EDGES> {
         self.rotate(action.rotation)
     }
 
+    fn is_valid_action(&self, action: &Self::GAct) -> bool {
+        action.size == EDGES
+    }
+
     fn rotate(&self, num_times: i32) -> Self {
         let mut result = self.clone();
-        let offset = (num_times * self.ports_per_edge() as i32).rem_euclid(self.num_ports() as i32);
+        // Placeholders have no connections to rotate
+        if self.num_ports() == 0 {
+            return result;
+        }
+        // Reduced first so large rotations can't overflow
+        let offset = (num_times.rem_euclid(EDGES as i32) * self.ports_per_edge() as i32).rem_euclid(self.num_ports() as i32);
         for i in 0..self.num_ports() as i32 {
             result.connections[i as usize] =
                 (self.connections[(i - offset).rem_euclid(self.num_ports() as i32) as usize] as i32 + offset).rem_euclid(self.num_ports() as i32) as u32;
@@ -352,6 +527,10 @@ impl<const EDGES: u32> Tile for RegularTile<EDGES> {
     fn set_visible(&mut self, visible: bool) {
         self.visible = visible;
     }
+
+    fn hidden(&self) -> Self {
+        Self { connections: vec![], visible: false }
+    }
 }
 
 #[cfg(test)]
@@ -373,6 +552,34 @@ mod tests {
         assert_eq!(tile.rotate(-1), expected);
     }
 
+    #[test]
+    fn test_square_tile_large_rotations() {
+        let tile = RegularTile::<4>::new(vec![2, 3, 0, 1, 7, 6, 5, 4]);
+        assert_eq!(tile.rotate(i32::MAX), tile.rotate(3));
+        assert_eq!(tile.rotate(i32::MIN), tile);
+        assert!(tile.is_valid_action(&CycleGAct{ rotation: i32::MAX, size: 4 }));
+        assert!(!tile.is_valid_action(&CycleGAct{ rotation: 1, size: 6 }));
+    }
+
+    #[test]
+    fn test_square_tile_hidden() {
+        let tile = RegularTile::<4>::new(vec![2, 3, 0, 1, 7, 6, 5, 4]);
+        let other = RegularTile::<4>::new(vec![1, 0, 3, 2, 5, 4, 7, 6]);
+        assert_eq!(tile.hidden(), other.hidden());
+        assert!(!tile.hidden().visible());
+        assert_eq!(tile.hidden().rotate(1), tile.hidden());
+    }
+
+    #[test]
+    fn test_regular_tile_check_config() {
+        assert_eq!(RegularTile::<4>::check_config(&PortsPerEdgeTileConfig(2)), Ok(()));
+        assert_eq!(RegularTile::<4>::check_config(&PortsPerEdgeTileConfig(0)), Err(TileConfigError::NoPorts));
+        assert_eq!(RegularTile::<3>::check_config(&PortsPerEdgeTileConfig(1)),
+            Err(TileConfigError::OddPortCount{ num_ports: 3 }));
+        assert_eq!(RegularTile::<4>::check_config(&PortsPerEdgeTileConfig(4)),
+            Err(TileConfigError::TooManyPorts{ num_ports: 16, max: 12 }));
+    }
+
     #[test]
     fn test_triangle_tile_all() {
         let all = RegularTile::<3>::all(PortsPerEdgeTileConfig(2));
@@ -390,4 +597,30 @@ mod tests {
         let all = RegularTile::<4>::all(PortsPerEdgeTileConfig(2));
         assert_eq!(all.len(), 35);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_tile_catalog() {
+        let catalog = TileCatalog::<RegularTile<4>>::new(PortsPerEdgeTileConfig(2));
+        assert_eq!(catalog.len(), 35);
+        assert_eq!(catalog.tiles(), RegularTile::<4>::all(PortsPerEdgeTileConfig(2)).as_slice());
+
+        for id in 0..catalog.len() {
+            let tile = catalog.tile(id).unwrap();
+            assert_eq!(catalog.id(tile), Some((id, 0)));
+            let num_rotations = catalog.num_distinct_rotations(id).unwrap();
+            assert!([1, 2, 4].contains(&num_rotations));
+            for rotation in 0..4 {
+                let rotated = catalog.tile_rotated(id, rotation).unwrap();
+                assert_eq!(catalog.id(&rotated), Some((id, rotation % num_rotations as i32)));
+            }
+        }
+        assert_eq!(catalog.tile(35), None);
+        assert_eq!(catalog.id(&catalog.tile(0).unwrap().hidden()), None);
+
+        // U-turns on two opposite edges and two paths between the other edges look the same after a half turn
+        let symmetric = RegularTile::<4>::new(vec![1, 0, 7, 6, 5, 4, 3, 2]);
+        assert_eq!(symmetric.num_distinct_rotations(), 2);
+        let (id, _) = catalog.id(&symmetric).unwrap();
+        assert_eq!(catalog.num_distinct_rotations(id), Some(2));
+    }
+}
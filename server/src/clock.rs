@@ -0,0 +1,140 @@
+use std::time::{Duration, Instant};
+
+use common::game_settings::TimeControl;
+use getset::{CopyGetters, Getters};
+
+/// Keeps track of how much time each player of a game has left under some time control
+#[derive(Debug, Getters, CopyGetters)]
+pub struct Clock {
+    time_control: TimeControl,
+    /// Time left for each player, as of `updated`
+    remaining: Vec<Duration>,
+    /// Players whose clocks are ticking
+    #[getset(get = "pub")]
+    running: Vec<u32>,
+    /// When `remaining` was last updated
+    updated: Instant,
+    /// Changes whenever the clock starts or stops, so that timeouts scheduled earlier can be told apart
+    #[getset(get_copy = "pub")]
+    generation: u64,
+}
+
+impl Clock {
+    /// Construct a stopped clock for `num_players` players
+    pub fn new(time_control: TimeControl, num_players: u32, now: Instant) -> Self {
+        let initial = match time_control {
+            TimeControl::PerMove{ seconds } => Duration::from_secs(seconds as u64),
+            TimeControl::Clock{ budget_seconds, .. } => Duration::from_secs(budget_seconds as u64),
+        };
+        Self {
+            time_control,
+            remaining: vec![initial; num_players as usize],
+            running: vec![],
+            updated: now,
+            generation: 0,
+        }
+    }
+
+    /// Charges the time passed since the last update to the running clocks
+    fn tick(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated);
+        for player in &self.running {
+            let remaining = &mut self.remaining[*player as usize];
+            *remaining = remaining.saturating_sub(elapsed);
+        }
+        self.updated = now;
+    }
+
+    /// Runs the clocks of `running` and stops the rest, after `moved` made a move.
+    /// Players who moved get their increment, and with per-move time, players starting a new move get a fresh clock.
+    /// Returns how long until the first running clock runs out, or None if no clocks are running.
+    pub fn start(&mut self, moved: &[u32], running: Vec<u32>, now: Instant) -> Option<Duration> {
+        self.tick(now);
+        match self.time_control {
+            TimeControl::PerMove{ seconds } => {
+                for player in &running {
+                    if moved.contains(player) || !self.running.contains(player) {
+                        self.remaining[*player as usize] = Duration::from_secs(seconds as u64);
+                    }
+                }
+            }
+            TimeControl::Clock{ increment_seconds, .. } => {
+                for player in moved {
+                    self.remaining[*player as usize] += Duration::from_secs(increment_seconds as u64);
+                }
+            }
+        }
+        self.running = running;
+        self.generation += 1;
+        self.running.iter().map(|player| self.remaining[*player as usize]).min()
+    }
+
+    /// Stops every clock
+    pub fn stop(&mut self, now: Instant) {
+        self.tick(now);
+        self.running.clear();
+        self.generation += 1;
+    }
+
+    /// The running players whose time ran out.
+    /// Empty if the clock started or stopped since generation `generation`.
+    pub fn timed_out(&mut self, generation: u64, now: Instant) -> Vec<u32> {
+        if generation != self.generation {
+            return vec![];
+        }
+        self.tick(now);
+        self.running.iter().copied()
+            .filter(|player| self.remaining[*player as usize].is_zero())
+            .collect()
+    }
+
+    /// Time left for each player in milliseconds, as of `now`
+    pub fn remaining_ms(&self, now: Instant) -> Vec<u64> {
+        let elapsed = now.saturating_duration_since(self.updated);
+        self.remaining.iter().enumerate()
+            .map(|(player, remaining)| if self.running.contains(&(player as u32)) {
+                remaining.saturating_sub(elapsed)
+            } else {
+                *remaining
+            }.as_millis() as u64)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock_per_move() {
+        let now = Instant::now();
+        let mut clock = Clock::new(TimeControl::PerMove{ seconds: 10 }, 2, now);
+        assert_eq!(clock.start(&[], vec![0], now), Some(Duration::from_secs(10)));
+        let generation = clock.generation();
+
+        let now = now + Duration::from_secs(4);
+        assert_eq!(clock.remaining_ms(now), vec![6000, 10000]);
+        assert!(clock.timed_out(generation, now).is_empty());
+
+        // Player 0 moves again, so they get a fresh clock
+        assert_eq!(clock.start(&[0], vec![0], now), Some(Duration::from_secs(10)));
+        let now = now + Duration::from_secs(10);
+        assert!(clock.timed_out(generation, now).is_empty(), "Stale timeout");
+        assert_eq!(clock.timed_out(clock.generation(), now), vec![0]);
+    }
+
+    #[test]
+    fn test_clock_budget() {
+        let now = Instant::now();
+        let mut clock = Clock::new(TimeControl::Clock{ budget_seconds: 60, increment_seconds: 5 }, 2, now);
+        clock.start(&[], vec![0], now);
+
+        let now = now + Duration::from_secs(20);
+        assert_eq!(clock.start(&[0], vec![1], now), Some(Duration::from_secs(60)));
+        assert_eq!(clock.remaining_ms(now), vec![45000, 60000]);
+
+        let now = now + Duration::from_secs(30);
+        clock.stop(now);
+        assert_eq!(clock.remaining_ms(now + Duration::from_secs(30)), vec![45000, 30000]);
+    }
+}
@@ -1,14 +1,20 @@
-use std::net::SocketAddr;
+use std::{net::SocketAddr, time::{Duration, Instant}};
 
-use common::{game::{BaseGame, GameId}, game_state::BaseGameState};
+use common::{bot::BotLevel, game::{BaseGame, GameId}, game_settings::GameSettings, game_state::BaseGameState, message::Response, player_state::Looker, replay::{Move, Replay}};
 use getset::{Getters, CopyGetters};
 
+use crate::{clock::Clock, persist::SavedGame};
+
 #[derive(Clone, Debug, Getters, CopyGetters)]
 pub struct Player {
     #[getset(get_copy = "pub")]
     addr: SocketAddr,
     #[getset(get = "pub")]
     username: String,
+    /// How well the player plays if it's a bot, or None for people.
+    /// Bots get placeholder addresses, so they can make moves the same way people do.
+    #[getset(get_copy = "pub")]
+    bot: Option<BotLevel>,
 }
 
 #[derive(Debug, Getters, CopyGetters)]
@@ -17,6 +23,9 @@ pub struct GameInstance {
     id: GameId,
     #[getset(get = "pub")]
     game: BaseGame,
+    /// The settings the game was created with
+    #[getset(get = "pub")]
+    settings: GameSettings,
     /// None if the game hasn't started
     #[getset(get = "pub")]
     state: Option<BaseGameState>,
@@ -25,71 +34,147 @@ pub struct GameInstance {
     players: Vec<Player>, 
     #[getset(get = "pub")]
     spectators: Vec<Player>,
+    /// None if the game has no time control or hasn't started
+    clock: Option<Clock>,
+    /// The seed and moves of the game so far. None if the game hasn't started.
+    #[getset(get = "pub")]
+    replay: Option<Replay>,
+    /// When the game ended. None if it's still going or hasn't started.
+    #[getset(get_copy = "pub")]
+    ended: Option<Instant>,
+    /// Whether the game changed since it was last saved
+    #[getset(get_copy = "pub")]
+    unsaved: bool,
 }
 
 impl GameInstance {
-    pub fn new(id: GameId, game: BaseGame) -> Self {
+    pub fn new(id: GameId, game: BaseGame, settings: GameSettings) -> Self {
         Self {
             id,
             game,
+            settings,
             state: None,
             players: vec![],
-            spectators: vec![]
+            spectators: vec![],
+            clock: None,
+            ended: None,
+            replay: None,
+            unsaved: true,
+        }
+    }
+
+    /// The game as it should be saved. It doesn't count as unsaved anymore.
+    pub fn take_saved(&mut self) -> SavedGame {
+        self.unsaved = false;
+        SavedGame {
+            game: self.to_common(),
+            replay: self.replay.clone(),
+            bots: self.players.iter().map(|player| player.bot).collect(),
+        }
+    }
+
+    /// Restores a game saved with `take_saved`, with each player at the address `addr` gives for their username
+    /// and whether they're a bot.
+    /// Clocks start over, and ended games are kept for as long as if they had just ended.
+    pub fn restore(saved: SavedGame, mut addr: impl FnMut(&str, bool) -> SocketAddr, now: Instant) -> Self {
+        let (id, game, settings, state, usernames) = saved.game.into_fields();
+        let players = usernames.into_iter().zip(saved.bots)
+            .map(|(username, bot)| Player { addr: addr(&username, bot.is_some()), username, bot })
+            .collect();
+        let mut inst = Self { players, replay: saved.replay, unsaved: false, ..Self::new(id, game, settings) };
+        if let Some(state) = state {
+            inst.ended = state.game_over().then_some(now);
+            inst.state = Some(state);
+            inst.clock = inst.settings.time_control
+                .map(|time_control| Clock::new(time_control, inst.num_players(), now));
         }
+        inst
     }
 
     pub fn to_common(&self) -> common::GameInstance {
         common::GameInstance::new(
             self.id,
             self.game.clone(),
+            self.settings.clone(),
             self.state.clone(),
             self.players.iter().map(|player| player.username().clone()).collect(),
         )
     }
 
+    /// The game as it should be listed in the lobby, with the state as a spectator sees it
+    /// so lobby peers can't read hands or the draw pile.
+    pub fn to_lobby(&self) -> common::GameInstance {
+        let mut inst = self.to_common();
+        if self.started() {
+            inst.set_looker(Looker::Spectator);
+        }
+        inst
+    }
+
     /// Whether the game has started
     pub fn started(&self) -> bool {
         self.state.is_some()
     }
 
-    /// Adds a player to the game by address and username, replacing the address
-    /// if the username is already in the game. Does not add new players if the game has started.
-    /// Returns the player's index if they got added or their address got replaced.
+    /// Adds a player to the game by address and username.
+    /// Does not add new players if the game has started or is full.
+    /// Returns the player's index if they got added or were already in the game.
     pub fn add_player(&mut self, addr: SocketAddr, username: String) -> Option<u32> {
-        if let Some((index, player)) = self.players.iter_mut().enumerate()
-            .find(|(_i, player)| player.username == username)
-        {
-            player.addr = addr;
-            Some(index as u32)
-        } else if !self.started() {
-            self.players.push(Player { addr, username });
+        if let Some(index) = self.player_index(addr) {
+            Some(index)
+        } else if !self.started() && self.num_players() < self.settings.max_players {
+            self.players.push(Player { addr, username, bot: None });
+            self.unsaved = true;
+            Some(self.players.len() as u32 - 1)
+        } else { None }
+    }
+
+    /// Seats a bot at a placeholder address, named after its level.
+    /// Does not add bots if the game has started or is full.
+    /// Returns the bot's index if it got added.
+    pub fn add_bot(&mut self, addr: SocketAddr, level: BotLevel) -> Option<u32> {
+        if !self.started() && self.num_players() < self.settings.max_players {
+            let number = self.players.iter().filter(|player| player.bot.is_some()).count() + 1;
+            let username = format!("{} bot {}", level, number);
+            self.players.push(Player { addr, username, bot: Some(level) });
+            self.unsaved = true;
             Some(self.players.len() as u32 - 1)
         } else { None }
     }
 
     /// Removes a player from the game. Returns whether the player was in the game.
-    /// TODO: If the game has started, kill the player token.
+    /// Players can't be removed once the game has started, since they must forfeit instead.
     pub fn remove_player(&mut self, addr: SocketAddr) -> bool {
         if !self.started() {
             if let Some(pos) = self.players.iter().position(|player| player.addr == addr) {
                 self.players.remove(pos);
+                self.unsaved = true;
                 true
             } else { false }
         } else { false }
     }
 
-    /// Adds a spectator to the game by address and username, replacing the address if the
-    /// username already exists.
+    /// Adds a spectator to the game by address and username. Does nothing if they're already spectating.
     pub fn add_spectator(&mut self, addr: SocketAddr, username: String) {
-        if let Some((_index, spectator)) = self.spectators.iter_mut().enumerate()
-            .find(|(_i, spectator)| spectator.username == username)
-        {
-            spectator.addr = addr;
-        } else {
-            self.spectators.push(Player { addr, username })
+        if !self.spectators.iter().any(|spectator| spectator.addr == addr) {
+            self.spectators.push(Player { addr, username, bot: None })
+        }
+    }
+
+    /// Moves a player or spectator to a new address, for when they resume their session
+    pub fn replace_addr(&mut self, old: SocketAddr, new: SocketAddr) {
+        for user in self.players.iter_mut().chain(&mut self.spectators) {
+            if user.addr == old {
+                user.addr = new;
+            }
         }
     }
 
+    /// Whether the address belongs to a player or spectator of the game
+    pub fn has_addr(&self, addr: SocketAddr) -> bool {
+        self.players_and_spectators().any(|user| user.addr == addr)
+    }
+
     /// Removes a spectator from the game. Does nothing if they weren't in the game.
     pub fn remove_spectator(&mut self, addr: SocketAddr) {
         if let Some(pos) = self.spectators.iter().position(|player| player.addr == addr) {
@@ -97,13 +182,86 @@ impl GameInstance {
         }
     }
 
+    /// The index of the player at some address. None if the address isn't a player of the game.
+    pub fn player_index(&self, addr: SocketAddr) -> Option<u32> {
+        self.players.iter().position(|player| player.addr == addr).map(|i| i as u32)
+    }
+
     pub fn num_players(&self) -> u32 {
         self.players.len() as u32
     }
 
+    /// Whether there are enough players to start the game
+    pub fn can_start(&self) -> bool {
+        self.num_players() >= self.settings.min_players
+    }
+
     /// Start the game. Adding players is not allowed afterward.
     pub fn start(&mut self) {
-        self.state = Some(self.game.new_state(self.players.len() as u32));
+        let seed = common::random_seed();
+        let usernames = self.players.iter().map(|player| player.username().clone()).collect();
+        self.replay = Some(Replay::new(self.game.clone(), usernames, seed));
+        self.state = Some(self.game.new_state_seeded(self.players.len() as u32, seed));
+        self.clock = self.settings.time_control
+            .map(|time_control| Clock::new(time_control, self.num_players(), Instant::now()));
+        self.unsaved = true;
+    }
+
+    /// Adds a move to the move log. Does nothing if the game hasn't started.
+    /// Every move made on the state gets recorded, so this is what marks moves to be saved.
+    pub fn record(&mut self, mv: Move) {
+        if let Some(replay) = &mut self.replay {
+            replay.push(mv);
+            self.unsaved = true;
+        }
+    }
+
+    /// Marks the game as ended, so it can be removed after a while. Does nothing if it was already marked.
+    pub fn end(&mut self, now: Instant) {
+        self.ended.get_or_insert(now);
+    }
+
+    /// Whether no people are in the game. Bots don't count.
+    pub fn is_empty(&self) -> bool {
+        self.players_and_spectators().all(|user| user.bot.is_some())
+    }
+
+    /// The level of the bot at some address. None if the address isn't a bot of the game.
+    pub fn bot_level(&self, addr: SocketAddr) -> Option<BotLevel> {
+        self.players.iter().find(|player| player.addr == addr).and_then(|player| player.bot)
+    }
+
+    /// Runs the clocks of the players who have to move now, after `moved` made a move, and stops the rest.
+    /// Returns the clock's generation and how long until the first running clock runs out,
+    /// or None if no clocks are running.
+    pub fn restart_clock(&mut self, moved: &[u32], now: Instant) -> Option<(u64, Duration)> {
+        let (clock, state) = (self.clock.as_mut()?, self.state.as_ref()?);
+        if state.game_over() {
+            clock.stop(now);
+            None
+        } else {
+            let running = if state.all_players_placed() {
+                vec![state.turn_player()]
+            } else {
+                state.players_placing_token(&self.game)
+            };
+            clock.start(moved, running, now).map(|delay| (clock.generation(), delay))
+        }
+    }
+
+    /// The running players whose time ran out.
+    /// Empty if there's no clock or it started or stopped since generation `generation`.
+    pub fn timed_out(&mut self, generation: u64, now: Instant) -> Vec<u32> {
+        self.clock.as_mut().map_or(vec![], |clock| clock.timed_out(generation, now))
+    }
+
+    /// The time left for each player. None if there's no clock.
+    pub fn clock_response(&self, now: Instant) -> Option<Response> {
+        self.clock.as_ref().map(|clock| Response::Clock{
+            id: self.id,
+            remaining_ms: clock.remaining_ms(now),
+            running: clock.running().clone(),
+        })
     }
 
     /// Gets the state mutably
@@ -1,18 +1,29 @@
+pub mod clock;
+pub mod persist;
 pub mod processor;
 pub mod game;
 pub mod state;
 
-use std::{sync::Arc};
+use std::{sync::Arc, time::Duration};
 
 use async_std::{net::{SocketAddr, TcpListener, TcpStream}, sync::Mutex};
 use async_tungstenite::{accept_async, tungstenite::{Error, Message, Result}};
-use common::{message::{Request}};
+use common::{message::{Request, SessionToken}};
 
 use futures::{StreamExt, future::{self, Either}, pin_mut, prelude::*};
 use futures::channel::mpsc::{self};
 use log::*;
 
-use crate::{processor::{respond_to_request}, state::State};
+use crate::{persist::Store, processor::{remove_ended_games, respond_to_request, resume_restored_games}, state::State};
+
+/// How long a disconnected peer has to resume their session before they leave their games
+const SESSION_GRACE_PERIOD: Duration = Duration::from_secs(60);
+/// Where the server state is saved, unless the `TSURUST_SAVE_DIR` environment variable says otherwise
+const DEFAULT_SAVE_DIR: &str = "save";
+/// How long ended games stay around, so that their final state can be looked at
+const ENDED_GAME_RETENTION: Duration = Duration::from_secs(10 * 60);
+/// How often to check for ended games to remove
+const ENDED_GAME_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
 
 async fn accept_connection(peer: SocketAddr, stream: TcpStream, state: Arc<Mutex<State>>) {
     if let Err(e) = handle_connection(peer, stream, Arc::clone(&state)).await {
@@ -66,15 +77,49 @@ async fn handle_connection(peer: SocketAddr, stream: TcpStream, state: Arc<Mutex
         Either::Right(result) => result.0,
     };
     info!("{} disconnected", peer);
-    state.lock().await.remove_peer(peer);
-    respond_to_request(Request::RemovePeer, peer, &state).await;
+    let session = state.lock().await.remove_peer(peer);
+    if let Some(token) = session {
+        expire_session(token, peer, &state).await;
+    } else {
+        respond_to_request(Request::RemovePeer, peer, &state).await;
+    }
     result
 }
 
+/// Waits out the grace period of a disconnected peer's session, then removes the peer
+/// unless the session was resumed on another connection.
+async fn expire_session(token: SessionToken, peer: SocketAddr, state: &Arc<Mutex<State>>) {
+    async_std::task::sleep(SESSION_GRACE_PERIOD).await;
+    if state.lock().await.expire_session(token, peer) {
+        info!("{}'s session expired", peer);
+        respond_to_request(Request::RemovePeer, peer, state).await;
+    }
+}
+
 async fn run() {
     env_logger::builder().filter_level(log::LevelFilter::Debug).parse_default_env().init();
 
-    let state = Arc::new(Mutex::new(State::new()));
+    let save_dir = std::env::var("TSURUST_SAVE_DIR").unwrap_or_else(|_| DEFAULT_SAVE_DIR.to_owned());
+    let store = Store::new(&save_dir).unwrap_or_else(|err| panic!("Can't open save directory {}: {}", save_dir, err));
+    let state = State::load(store).unwrap_or_else(|err| panic!("Can't load saved state from {}: {}", save_dir, err));
+    info!("Loaded {} games from {}", state.games().len(), save_dir);
+
+    // Players of restored games get the usual time to reconnect
+    let sessions = state.disconnected_sessions();
+    let state = Arc::new(Mutex::new(state));
+    resume_restored_games(&state).await;
+    for (token, addr) in sessions {
+        let state = Arc::clone(&state);
+        async_std::task::spawn(async move { expire_session(token, addr, &state).await });
+    }
+
+    let sweep_state = Arc::clone(&state);
+    async_std::task::spawn(async move {
+        loop {
+            async_std::task::sleep(ENDED_GAME_SWEEP_INTERVAL).await;
+            remove_ended_games(ENDED_GAME_RETENTION, &sweep_state).await;
+        }
+    });
 
     info!("Attempting to listen to {}", common::HOST_ADDRESS);
     let listener = TcpListener::bind(common::HOST_ADDRESS).await
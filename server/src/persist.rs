@@ -0,0 +1,228 @@
+use std::{fs, io::{self, Write}, path::{Path, PathBuf}, sync::mpsc, thread};
+
+use common::{bot::BotLevel, game::GameId, message::SessionToken, replay::Replay};
+use log::*;
+use serde::{Deserialize, Serialize};
+
+/// Saves server state to a directory so that running games survive a restart.
+/// Each game gets its own file, which is rewritten whenever the game changes,
+/// so saving after a move doesn't touch the other games.
+#[derive(Debug)]
+pub struct Store {
+    dir: PathBuf,
+}
+
+/// A game as saved, along with its move log
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedGame {
+    pub game: common::GameInstance,
+    pub replay: Option<Replay>,
+    /// The level of each player that's a bot, or None for people
+    pub bots: Vec<Option<BotLevel>>,
+}
+
+/// Everything saved outside the games
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SavedMeta {
+    pub id_counter: u32,
+    /// Sessions that can be resumed, with their usernames
+    pub sessions: Vec<(SessionToken, String)>,
+}
+
+/// What changed since the last save
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// Games to save, or None for games whose file should be deleted
+    pub games: Vec<(GameId, Option<SavedGame>)>,
+    /// None if nothing outside the games changed
+    pub meta: Option<SavedMeta>,
+}
+
+impl Changes {
+    pub fn is_empty(&self) -> bool {
+        self.games.is_empty() && self.meta.is_none()
+    }
+}
+
+/// Writes changes to a store on a background thread, in the order they were sent,
+/// so the server doesn't wait on the disk while holding its state.
+/// Dropping it waits until everything sent so far is written.
+#[derive(Debug)]
+pub struct Flusher {
+    tx: Option<mpsc::Sender<Changes>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Flusher {
+    pub fn new(store: Store) -> Self {
+        let (tx, rx) = mpsc::channel::<Changes>();
+        let thread = thread::spawn(move || rx.into_iter().for_each(|changes| store.write(changes)));
+        Self { tx: Some(tx), thread: Some(thread) }
+    }
+
+    pub fn send(&self, changes: Changes) {
+        if let Some(Err(err)) = self.tx.as_ref().map(|tx| tx.send(changes)) {
+            error!("Failed to save changes, the writing thread stopped: {:?}", err.0);
+        }
+    }
+}
+
+impl Drop for Flusher {
+    fn drop(&mut self) {
+        // Closing the channel lets the thread finish once it has written everything
+        self.tx.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Store {
+    const META_FILE: &'static str = "meta.bin";
+    const GAMES_DIR: &'static str = "games";
+
+    /// Opens a store in a directory, creating the directory if needed
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        let dir = dir.as_ref().to_owned();
+        fs::create_dir_all(dir.join(Self::GAMES_DIR))?;
+        Ok(Self { dir })
+    }
+
+    fn game_path(&self, id: GameId) -> PathBuf {
+        self.dir.join(Self::GAMES_DIR).join(format!("{}.bin", id.0))
+    }
+
+    /// Writes a file by renaming a temporary one over it, so a crash can't leave it half-written.
+    /// The temporary file is synced to disk first, so the rename can't land before its contents.
+    fn write_atomic(path: &Path, value: &impl Serialize) -> io::Result<()> {
+        let bytes = bincode::serialize(value).map_err(io::Error::other)?;
+        let temp = path.with_extension("tmp");
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(&bytes)?;
+        file.sync_all()?;
+        fs::rename(temp, path)
+    }
+
+    fn read<T: for<'de> Deserialize<'de>>(path: &Path) -> io::Result<T> {
+        bincode::deserialize(&fs::read(path)?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Saves a game, or deletes its file if it's None
+    pub fn save_game(&self, id: GameId, game: Option<&SavedGame>) -> io::Result<()> {
+        match game {
+            Some(game) => Self::write_atomic(&self.game_path(id), game),
+            None => match fs::remove_file(self.game_path(id)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    pub fn save_meta(&self, meta: &SavedMeta) -> io::Result<()> {
+        Self::write_atomic(&self.dir.join(Self::META_FILE), meta)
+    }
+
+    /// Saves every change, logging the ones that fail
+    pub fn write(&self, changes: Changes) {
+        for (id, game) in changes.games {
+            if let Err(err) = self.save_game(id, game.as_ref()) {
+                error!("Failed to save game {:?}: {}", id, err);
+            }
+        }
+        if let Some(meta) = changes.meta {
+            if let Err(err) = self.save_meta(&meta) {
+                error!("Failed to save state: {}", err);
+            }
+        }
+    }
+
+    /// Loads everything saved, sorted by game id. A store that was never saved to loads as empty.
+    pub fn load(&self) -> io::Result<(SavedMeta, Vec<SavedGame>)> {
+        let meta_path = self.dir.join(Self::META_FILE);
+        let meta = if meta_path.exists() { Self::read(&meta_path)? } else { SavedMeta::default() };
+
+        let mut games = vec![];
+        for entry in fs::read_dir(self.dir.join(Self::GAMES_DIR))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "bin") {
+                games.push(Self::read::<SavedGame>(&path)?);
+            }
+        }
+        games.sort_by_key(|saved| saved.game.id());
+        Ok((meta, games))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use common::{game_settings::GameSettings, message::{Request, Response}};
+    use futures::channel::mpsc;
+
+    use crate::processor::{process_request, tests::{addr, set_up_started_game, started_game, top_port}};
+    use crate::state::State;
+
+    use super::*;
+
+    #[test]
+    fn test_restore_game() {
+        let dir = std::env::temp_dir().join(format!("tsurust-test-restore-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+
+        let mut state = State::load(Store::new(&dir).unwrap()).unwrap();
+        set_up_started_game(&mut state, GameSettings::default());
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        let token = state.remove_peer(addr(1)).expect("Expected a session");
+        state.save();
+        // Waits for the writes
+        drop(state);
+
+        let mut state = State::load(Store::new(&dir).unwrap()).unwrap();
+        assert_eq!(state.disconnected_sessions().len(), 3);
+        state.add_peer(addr(4), mpsc::unbounded().0);
+        let responses = process_request(Request::Resume{ token }, addr(4), &mut state);
+        let game = responses.iter().find_map(|(_, resp)| match resp {
+            Response::JoinedGame{ game } => Some(game),
+            _ => None,
+        }).expect("Expected to rejoin the game");
+        assert_eq!(game.players(), &["a".to_owned(), "b".to_owned()]);
+        assert_eq!(game.state().as_ref().unwrap().board_state().player_port(0), Some(top_port(0)));
+
+        // New games don't reuse ids
+        process_request(Request::CreateGame{ settings: GameSettings::default() }, addr(4), &mut state);
+        assert!(state.game(GameId(1)).is_some());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_restored_sessions_get_distinct_addrs() {
+        let dir = std::env::temp_dir().join(format!("tsurust-test-sessions-{}", std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+
+        // More than fit in a port number
+        let store = Store::new(&dir).unwrap();
+        let sessions = (0..70_000).map(|i| (SessionToken(i), format!("player {}", i))).collect();
+        store.save_meta(&SavedMeta{ id_counter: 0, sessions }).unwrap();
+        let state = State::load(store).unwrap();
+        let addrs = state.disconnected_sessions().into_iter().map(|(_, addr)| addr).collect::<HashSet<_>>();
+        assert_eq!(addrs.len(), 70_000);
+
+        drop(state);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_only_changed_games_saved() {
+        let mut state = started_game();
+        state.game_mut(GameId(0)).unwrap().take_saved();
+
+        // Rejected moves don't change anything worth saving
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(2), &mut state);
+        assert!(!state.game(GameId(0)).unwrap().unsaved());
+
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        assert!(state.game(GameId(0)).unwrap().unsaved());
+    }
+}
@@ -1,23 +1,28 @@
-use std::{net::SocketAddr, collections::VecDeque};
+use std::{net::SocketAddr, collections::VecDeque, sync::Arc, time::{Duration, Instant}};
 
 
 use async_std::sync::{Mutex};
-use common::{message::{Request, Response}, player_state::Looker, board::{RectangleBoard, Board, BasePort, BaseTLoc}, game::{PathGame, GameId}, WrapBase, tile::{BaseKind, BaseGAct}};
+use common::{message::{Request, Response, RejectReason, SessionToken}, player_state::Looker, board::{BasePort, BaseTLoc}, game::{BaseGame, GameId, TokenPlacement}, game_settings::{GameSettings, TimeoutPolicy}, tile::{BaseKind, BaseGAct}};
+use common::game_state::{BaseGameState, drawn_tiles_visible_to};
+use common::replay::Move;
+use common::bot::{Bot, BotLevel};
+use common::strategy::{RandomStrategy, Strategy};
 
 use itertools::{Itertools};
 use log::*;
 
-use crate::state::State;
+use crate::state::{State, Timeout};
 
 /// A request for which a simple action is done.
 /// This can generate more `ElementaryRequest`s as well as responses.
 #[derive(Clone, Debug)]
 pub enum ElementaryRequest {
     SetUsername{ username: String },
+    Resume{ token: SessionToken },
     JoinLobby,
     /// Elementary only. Does not send a response.
     LeaveLobby,
-    CreateGame,
+    CreateGame{ settings: GameSettings },
     JoinGame{ id: GameId },
     /// Elementary only. Does not send a response.
     LeaveGame{ id: GameId },
@@ -27,22 +32,38 @@ pub enum ElementaryRequest {
     NotifyChangePlayers{ id: GameId },
     /// Elementary only. Notifies the lobby that a game changed.
     NotifyChangeGame{ id: GameId },
+    /// Elementary only. Marks a game that just ended, so that it gets removed after a while.
+    EndGame{ id: GameId },
+    /// Elementary only. Removes a game and notifies the lobby.
+    RemoveGame{ id: GameId },
+    /// Elementary only. Adds a move to a game's move log.
+    RecordMove{ id: GameId, mv: Move },
+    /// Elementary only. Runs the clocks of the players who have to move after `moved` made a move,
+    /// and notifies the players of the game.
+    UpdateClock{ id: GameId, moved: Vec<u32> },
+    AddBot{ id: GameId, level: BotLevel },
     StartGame{ id: GameId },
-    PlaceToken{ id: GameId, player: u32, port: BasePort },
-    PlaceTile{ id: GameId, player: u32, kind: BaseKind, index: u32, action: BaseGAct, loc: BaseTLoc },
+    PlaceToken{ id: GameId, port: BasePort },
+    PlaceTile{ id: GameId, kind: BaseKind, index: u32, action: BaseGAct, loc: BaseTLoc },
+    Forfeit{ id: GameId },
+    GetReplay{ id: GameId },
 }
 
 impl ElementaryRequest {
     fn vec_from_request(req: Request) -> Vec<Self> {
         match req {
             Request::SetUsername{ username } => vec![Self::SetUsername{ username }],
+            Request::Resume{ token } => vec![Self::Resume{ token }],
             Request::JoinLobby => vec![Self::LeaveGames, Self::JoinLobby],
-            Request::CreateGame => vec![Self::CreateGame],
+            Request::CreateGame{ settings } => vec![Self::CreateGame{ settings }],
             Request::JoinGame{ id } => vec![Self::LeaveLobby, Self::JoinGame{ id }],
+            Request::AddBot{ id, level } => vec![Self::AddBot{ id, level }],
             Request::StartGame{ id } => vec![Self::StartGame{ id }],
-            Request::PlaceToken{ id, player, port } => vec![Self::PlaceToken{ id, player, port }],
-            Request::PlaceTile{ id, player, kind, index, action, loc } =>
-                vec![Self::PlaceTile{ id, player, kind, index, action, loc }],
+            Request::PlaceToken{ id, port } => vec![Self::PlaceToken{ id, port }],
+            Request::PlaceTile{ id, kind, index, action, loc } =>
+                vec![Self::PlaceTile{ id, kind, index, action, loc }],
+            Request::Forfeit{ id } => vec![Self::Forfeit{ id }],
+            Request::GetReplay{ id } => vec![Self::GetReplay{ id }],
             Request::RemovePeer => vec![Self::LeaveGames, Self::LeaveLobby],
         }
     }
@@ -50,6 +71,12 @@ impl ElementaryRequest {
 
 /// Processes a request, and returns a list of responses to send to peers.
 pub(crate) fn process_request(req: Request, requester: SocketAddr, state: &mut State) -> Vec<(SocketAddr, Response)> {
+    let responses = process_single_request(req, requester, state);
+    drive_bots(responses, state)
+}
+
+/// Processes a request without having bots respond, and returns a list of responses, including ones to bots.
+fn process_single_request(req: Request, requester: SocketAddr, state: &mut State) -> Vec<(SocketAddr, Response)> {
     let elem_req = ElementaryRequest::vec_from_request(req);
 
     let mut to_process = elem_req.into_iter().collect::<VecDeque<_>>();
@@ -57,26 +84,39 @@ pub(crate) fn process_request(req: Request, requester: SocketAddr, state: &mut S
     while let Some(req) = to_process.pop_front() {
         responses.extend(match req {
             ElementaryRequest::SetUsername{ username: name } => {
-                if state.set_username(requester, name.clone()) {
+                if let Some(token) = state.set_username(requester, name.clone()) {
                     to_process.push_back(ElementaryRequest::JoinLobby);
-                    vec![]
+                    vec![(requester, Response::Session{ username: name, token })]
                 } else {
                     vec![(requester, Response::RejectedUsername)]
                 }
             },
 
-            ElementaryRequest::CreateGame => {
-                let board = RectangleBoard::new(6, 6, 2);
-                let start_ports = board.boundary_ports();
-                let game = PathGame::new(
-                    RectangleBoard::new(6, 6, 2),
-                    start_ports,
-                    [((), 3)],
-                ).wrap_base();
-                
-                let game = state.add_game(game).to_common();
-                to_process.push_back(ElementaryRequest::NotifyChangeGame{ id: game.id() });
-                vec![]
+            ElementaryRequest::Resume{ token } => {
+                if state.resume_session(requester, token).is_some() {
+                    // Send the peer back to where they were
+                    if let Some(game) = state.games().iter().find(|game| game.has_addr(requester)) {
+                        to_process.push_back(ElementaryRequest::JoinGame{ id: game.id() });
+                    } else {
+                        to_process.push_back(ElementaryRequest::JoinLobby);
+                    }
+                    let username = state.peer(requester).expect("Peer doesn't exist").username().clone();
+                    vec![(requester, Response::Session{ username, token })]
+                } else {
+                    vec![(requester, Response::RejectedSession)]
+                }
+            },
+
+            ElementaryRequest::CreateGame{ settings } => {
+                match settings.build() {
+                    Ok(game) => {
+                        let game = state.add_game(game, settings).to_common();
+                        to_process.push_back(ElementaryRequest::NotifyChangeGame{ id: game.id() });
+                        vec![]
+                    }
+
+                    Err(error) => vec![(requester, Response::RejectedGameSettings{ error })],
+                }
             }
 
             ElementaryRequest::JoinGame{ id } => {
@@ -103,20 +143,37 @@ pub(crate) fn process_request(req: Request, requester: SocketAddr, state: &mut S
                             Looker::Spectator
                         })
                     };
+                    let (placing_token, turn) = game.state().as_ref().zip(index).map_or((false, None), |(state, index)| (
+                        state.players_placing_token(game.game()).contains(&index),
+                        (state.all_players_placed() && !state.game_over() && index == state.turn_player())
+                            .then(|| your_turn(id, game.game(), state)),
+                    ));
                     [
                         Some((requester, Response::JoinedGame{ game: game_inst } )),
-                        game.state().as_ref().map_or(false, |state| index == Some(state.turn_player()))
-                            .then(|| (requester, Response::YourTurn{ id }))
+                        game.clock_response(Instant::now()).map(|response| (requester, response)),
+                        placing_token.then_some((requester, Response::YourTurnPlaceToken{ id })),
+                        turn.map(|response| (requester, response)),
                     ].into_iter().flatten().collect()
-                } else { vec![(requester, Response::Rejected{ id })] }
+                } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NoSuchGame })] }
             }
 
             ElementaryRequest::LeaveGame{ id } => {
                 if let Some(game) = state.game_mut(id) {
-                    if game.remove_player(requester) {
+                    let forfeits = game.player_index(requester).zip(game.state().as_ref())
+                        .is_some_and(|(player, state)| !state.game_over() && state.player_state(player).is_some());
+                    if forfeits {
+                        to_process.push_back(ElementaryRequest::Forfeit{ id });
+                        game.remove_spectator(requester);
+                        vec![]
+                    } else if game.remove_player(requester) {
+                        // Games that everyone left before starting are dropped
                         to_process.extend([
                             ElementaryRequest::NotifyChangePlayers{ id },
-                            ElementaryRequest::NotifyChangeGame{ id },
+                            if game.is_empty() {
+                                ElementaryRequest::RemoveGame{ id }
+                            } else {
+                                ElementaryRequest::NotifyChangeGame{ id }
+                            },
                         ]);
                         vec![]
                     } else {
@@ -134,7 +191,7 @@ pub(crate) fn process_request(req: Request, requester: SocketAddr, state: &mut S
             ElementaryRequest::JoinLobby => {
                 let username = state.peer(requester).expect("Peer doesn't exist").username().clone();
                 state.add_to_lobby(username, requester);
-                let games = state.games().iter().map(|game| game.to_common()).collect();
+                let games = state.games().iter().map(|game| game.to_lobby()).collect();
                 vec![(requester, Response::JoinedLobby{ games })]
             }
 
@@ -148,10 +205,35 @@ pub(crate) fn process_request(req: Request, requester: SocketAddr, state: &mut S
                 let game = state.game(id).expect("NotifyChangeGame requested on nonexistent game");
 
                 state.lobby().iter().map(|(_, addr)|
-                    (*addr, Response::ChangedGame{ game: game.to_common() })
+                    (*addr, Response::ChangedGame{ game: game.to_lobby() })
                 ).collect()
             }
 
+            ElementaryRequest::EndGame{ id } => {
+                state.game_mut(id).expect("EndGame requested on nonexistent game").end(Instant::now());
+                vec![]
+            }
+
+            ElementaryRequest::RemoveGame{ id } => {
+                remove_game(id, state)
+            }
+
+            ElementaryRequest::RecordMove{ id, mv } => {
+                state.game_mut(id).expect("RecordMove requested on nonexistent game").record(mv);
+                vec![]
+            }
+
+            ElementaryRequest::GetReplay{ id } => {
+                if let Some(game) = state.game(id) {
+                    // Replays of running games would give away the draw pile
+                    if let Some(replay) = game.replay().as_ref().filter(|_| game.ended().is_some()) {
+                        vec![(requester, Response::Replay{ id, replay: replay.clone() })]
+                    } else {
+                        vec![(requester, Response::Rejected{ id, reason: RejectReason::WrongPhase })]
+                    }
+                } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NoSuchGame })] }
+            }
+
             ElementaryRequest::NotifyChangePlayers{ id } => {
                 // This can be proven to work without relying on the user input being good
                 let game = state.game(id).expect("NotifyChangePlayers requested on nonexistent game");
@@ -163,14 +245,56 @@ pub(crate) fn process_request(req: Request, requester: SocketAddr, state: &mut S
                 ).collect()
             }
 
+            ElementaryRequest::UpdateClock{ id, moved } => {
+                let now = Instant::now();
+                let game = state.game_mut(id).expect("UpdateClock requested on nonexistent game");
+                let timeout = game.restart_clock(&moved, now);
+                let responses = game.clock_response(now).into_iter()
+                    .flat_map(|response| game.players_and_spectators()
+                        .map(|user| (user.addr(), response.clone()))
+                        .collect_vec())
+                    .collect_vec();
+
+                if let Some((generation, delay)) = timeout {
+                    state.push_timeout(Timeout{ id, generation, delay });
+                }
+                responses
+            }
+
+            ElementaryRequest::AddBot{ id, level } => {
+                let addr = state.next_bot_addr();
+                if let Some(game) = state.game_mut(id) {
+                    if game.player_index(requester).is_none() {
+                        vec![(requester, Response::Rejected{ id, reason: RejectReason::NotAPlayer })]
+                    } else if game.started() {
+                        vec![(requester, Response::Rejected{ id, reason: RejectReason::WrongPhase })]
+                    } else if game.add_bot(addr, level).is_some() {
+                        to_process.extend([
+                            ElementaryRequest::NotifyChangePlayers{ id },
+                            ElementaryRequest::NotifyChangeGame{ id },
+                        ]);
+                        vec![]
+                    } else {
+                        vec![(requester, Response::Rejected{ id, reason: RejectReason::GameFull })]
+                    }
+                } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NoSuchGame })] }
+            }
+
             ElementaryRequest::StartGame{ id } => {
                 if let Some(game) = state.game_mut(id) {
                     let players_spectators = game.players_and_spectators().cloned().collect_vec();
-                    if !game.started() {
+                    if game.started() {
+                        vec![(requester, Response::Rejected{ id, reason: RejectReason::WrongPhase })]
+                    } else if !game.can_start() {
+                        vec![(requester, Response::Rejected{ id, reason: RejectReason::NotEnoughPlayers })]
+                    } else {
                         game.start();
                         let game = state.game(id).unwrap(); // no more need for the mutable borrow
 
-                        to_process.push_back(ElementaryRequest::NotifyChangeGame{ id });
+                        to_process.extend([
+                            ElementaryRequest::NotifyChangeGame{ id },
+                            ElementaryRequest::UpdateClock{ id, moved: vec![] },
+                        ]);
 
                         let game_state = game.state().as_ref()
                             .expect("Game started, there should be a state");
@@ -182,67 +306,174 @@ pub(crate) fn process_request(req: Request, requester: SocketAddr, state: &mut S
                                 });
                             (user.addr(), Response::StartedGame { id, state: this_state })
                         })
+                        .chain(game_state.players_placing_token(game.game()).into_iter().map(|player| (
+                            game.players()[player as usize].addr(), Response::YourTurnPlaceToken{ id }
+                        )))
                         .chain(state.lobby().values().map(|addr| (
-                            *addr, Response::ChangedGame{ game: game.to_common() }
+                            *addr, Response::ChangedGame{ game: game.to_lobby() }
                         )))
                         .collect()
-                    } else { vec![(requester, Response::Rejected{ id })] }
-                } else { vec![(requester, Response::Rejected{ id })] }
+                    }
+                } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NoSuchGame })] }
             }
 
-            ElementaryRequest::PlaceToken{ id, player, port } => {
+            ElementaryRequest::PlaceToken{ id, port } => {
                 if let Some(inst) = state.game_mut(id) {
-                    if let (game, Some(game_state)) = inst.game_and_state_mut() {
-                        if game_state.can_place_player(game, &port) {
-                            game_state.place_player(player, &port);
-                            let all_placed = game_state.all_players_placed();
-                            let turn_player = game_state.turn_player();
-
-                            inst.players_and_spectators().into_iter()
-                                .flat_map(|user| { vec![
-                                    Some((user.addr(), Response::PlacedToken { id, player, port: port.clone() })),
-                                    all_placed.then(|| (user.addr(), Response::AllPlacedTokens{ id })),
-                                ].into_iter().flatten()})
-                                .chain(all_placed.then(|| (inst.players()[turn_player as usize].addr(), Response::YourTurn{ id })))
-                                .collect()
+                    if let Some(player) = inst.player_index(requester) {
+                        if let (game, Some(game_state)) = inst.game_and_state_mut() {
+                            if game_state.all_players_placed() {
+                                vec![(requester, Response::Rejected{ id, reason: RejectReason::WrongPhase })]
+                            } else if game_state.board_state().player_port(player).is_some() {
+                                // Tokens can only be placed once
+                                vec![(requester, Response::Rejected{ id, reason: RejectReason::IllegalMove })]
+                            } else if !game_state.players_placing_token(game).contains(&player) {
+                                vec![(requester, Response::Rejected{ id, reason: RejectReason::NotYourTurn })]
+                            } else if game_state.can_place_token(game, player, &port) {
+                                game_state.take_turn_placing_token(game, player, &port);
+                                to_process.extend([
+                                    ElementaryRequest::RecordMove{ id, mv: Move::PlaceToken{ player, port: port.clone() } },
+                                    ElementaryRequest::UpdateClock{ id, moved: vec![player] },
+                                ]);
+                                let all_placed = game_state.all_players_placed();
+                                let turn_player = game_state.turn_player();
+                                let turn = all_placed.then(|| your_turn(id, game, game_state));
+                                let next_placing = match game.token_placement() {
+                                    TokenPlacement::Sequential => game_state.players_placing_token(game),
+                                    // Everyone was already prompted at the start
+                                    TokenPlacement::Simultaneous => vec![],
+                                };
+
+                                inst.players_and_spectators().into_iter()
+                                    .flat_map(|user| { vec![
+                                        Some((user.addr(), Response::PlacedToken { id, player, port: port.clone() })),
+                                        all_placed.then(|| (user.addr(), Response::AllPlacedTokens{ id })),
+                                    ].into_iter().flatten()})
+                                    .chain(next_placing.into_iter().map(|player| (inst.players()[player as usize].addr(), Response::YourTurnPlaceToken{ id })))
+                                    .chain(turn.map(|response| (inst.players()[turn_player as usize].addr(), response)))
+                                    .collect()
+                            } else {
+                                vec![(requester, Response::Rejected{ id, reason: RejectReason::IllegalMove })]
+                            }
                         } else {
-                            vec![(requester, Response::Rejected{ id })]
+                            vec![(requester, Response::Rejected{ id, reason: RejectReason::WrongPhase })]
                         }
-                    } else {
-                        warn!("Game state is missing");
-                        vec![(requester, Response::Rejected{ id })]
-                    }
-                } else { vec![(requester, Response::Rejected{ id })] }
+                    } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NotAPlayer })] }
+                } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NoSuchGame })] }
             }
 
-            ElementaryRequest::PlaceTile{ id, player, kind, index, action, loc } => {
+            ElementaryRequest::Forfeit{ id } => {
                 if let Some(inst) = state.game_mut(id) {
-                    if let (game, Some(game_state)) = inst.game_and_state_mut() {
-                        if game_state.can_place_tile(game, player, &kind, index, &action, &loc) {
-                            let result = game_state.take_turn_placing_tile(game, &kind, index, &action, &loc);
-                            let turn_player = game_state.turn_player();
-                            let game_over = result.game_over();
-                            
-                            if game_over {
-                                to_process.push_back(ElementaryRequest::NotifyChangeGame{ id });
+                    if let Some(player) = inst.player_index(requester) {
+                        if let (game, Some(game_state)) = inst.game_and_state_mut() {
+                            if game_state.game_over() || game_state.player_state(player).is_none() {
+                                vec![(requester, Response::Rejected{ id, reason: RejectReason::WrongPhase })]
+                            } else {
+                                let was_turn = game_state.turn_player() == player;
+                                let was_placed = game_state.all_players_placed();
+                                let drawn_tiles = game_state.forfeit(game, player);
+                                to_process.extend([
+                                    ElementaryRequest::RecordMove{ id, mv: Move::Forfeit{ player } },
+                                    ElementaryRequest::UpdateClock{ id, moved: vec![] },
+                                ]);
+                                let all_placed = game_state.all_players_placed();
+                                let turn_player = game_state.turn_player();
+                                let game_over = game_state.game_over();
+
+                                // Whoever the turn passed to needs to be prompted
+                                let turn = if game_over {
+                                    None
+                                } else if all_placed && (was_turn || !was_placed) {
+                                    Some(your_turn(id, game, game_state))
+                                } else if !all_placed && was_turn && game.token_placement() == TokenPlacement::Sequential {
+                                    Some(Response::YourTurnPlaceToken{ id })
+                                } else {
+                                    None
+                                };
+
+                                if game_over {
+                                    to_process.extend([
+                                        ElementaryRequest::NotifyChangeGame{ id },
+                                        ElementaryRequest::EndGame{ id },
+                                    ]);
+                                }
+
+                                let num_players = inst.num_players();
+                                inst.players_and_spectators().enumerate()
+                                    .flat_map(|(i, user)| {
+                                        let looker = if (i as u32) < num_players {
+                                            Looker::Player(i as u32)
+                                        } else {
+                                            Looker::Spectator
+                                        };
+                                        vec![
+                                            Some((user.addr(), Response::Forfeited {
+                                                id, player, drawn_tiles: drawn_tiles_visible_to(&drawn_tiles, looker),
+                                            })),
+                                            (all_placed && !was_placed).then(|| (user.addr(), Response::AllPlacedTokens{ id })),
+                                        ].into_iter().flatten()
+                                    })
+                                    .chain(turn.map(|response| (inst.players()[turn_player as usize].addr(), response)))
+                                    .collect()
                             }
+                        } else {
+                            vec![(requester, Response::Rejected{ id, reason: RejectReason::WrongPhase })]
+                        }
+                    } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NotAPlayer })] }
+                } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NoSuchGame })] }
+            }
+
+            ElementaryRequest::PlaceTile{ id, kind, index, action, loc } => {
+                if let Some(inst) = state.game_mut(id) {
+                    if let Some(player) = inst.player_index(requester) {
+                        if let (game, Some(game_state)) = inst.game_and_state_mut() {
+                            if !game_state.all_players_placed() || game_state.game_over() {
+                                vec![(requester, Response::Rejected{ id, reason: RejectReason::WrongPhase })]
+                            } else if game_state.turn_player() != player {
+                                vec![(requester, Response::Rejected{ id, reason: RejectReason::NotYourTurn })]
+                            } else if game_state.can_place_tile(game, player, &kind, index, &action, &loc) {
+                                let tile = game_state.hand_tile(player, &kind, index)
+                                    .expect("Tile placement was checked, so the tile should exist");
+                                let result = game_state.take_turn_placing_tile(game, &kind, index, &action, &loc);
+                                to_process.extend([
+                                    ElementaryRequest::RecordMove{ id, mv: Move::PlaceTile{
+                                        player, kind: kind.clone(), index, action: action.clone(), loc: loc.clone(),
+                                    } },
+                                    ElementaryRequest::UpdateClock{ id, moved: vec![player] },
+                                ]);
+                                let turn_player = game_state.turn_player();
+                                let game_over = result.game_over();
+                                let turn = (!game_over).then(|| your_turn(id, game, game_state));
+
+                                if game_over {
+                                    to_process.extend([
+                                        ElementaryRequest::NotifyChangeGame{ id },
+                                        ElementaryRequest::EndGame{ id },
+                                    ]);
+                                }
 
-                            inst.players_and_spectators().into_iter()
-                                .map(|user| { 
-                                    (user.addr(), Response::PlacedTile {
-                                        id, player, kind: kind.clone(), index: index as u32, action: action.clone(), loc: loc.clone()
+                                let num_players = inst.num_players();
+                                inst.players_and_spectators().into_iter().enumerate()
+                                    .map(|(i, user)| {
+                                        let looker = if (i as u32) < num_players {
+                                            Looker::Player(i as u32)
+                                        } else {
+                                            Looker::Spectator
+                                        };
+                                        (user.addr(), Response::PlacedTile {
+                                            id, player, kind: kind.clone(), index, action: action.clone(), loc: loc.clone(),
+                                            tile: tile.clone(), drawn_tiles: result.drawn_tiles_visible_to(looker),
+                                        })
                                     })
-                                })
-                                .chain((!game_over).then(|| (inst.players()[turn_player as usize].addr(), Response::YourTurn{ id })))
-                                .collect()
+                                    .chain(turn.map(|response| (inst.players()[turn_player as usize].addr(), response)))
+                                    .collect()
+                            } else {
+                                vec![(requester, Response::Rejected{ id, reason: RejectReason::IllegalMove })]
+                            }
                         } else {
-                            vec![(requester, Response::Rejected{ id })]
+                            vec![(requester, Response::Rejected{ id, reason: RejectReason::WrongPhase })]
                         }
-                    } else {
-                        warn!("Game state is missing");
-                        vec![(requester, Response::Rejected{ id })]
-                    }
-                } else { vec![(requester, Response::Rejected{ id })] }
+                    } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NotAPlayer })] }
+                } else { vec![(requester, Response::Rejected{ id, reason: RejectReason::NoSuchGame })] }
             }
         })
     }
@@ -250,12 +481,159 @@ pub(crate) fn process_request(req: Request, requester: SocketAddr, state: &mut S
     responses
 }
 
+/// Has bots make their moves when they're told it's their turn.
+/// Bots aren't peers, so every other response to them is dropped.
+/// The responses to their moves are queued rather than processed recursively, so a game of bots doesn't play out on the stack.
+fn drive_bots(responses: Vec<(SocketAddr, Response)>, state: &mut State) -> Vec<(SocketAddr, Response)> {
+    let mut to_process = VecDeque::from(responses);
+    let mut result = vec![];
+    while let Some((addr, response)) = to_process.pop_front() {
+        if !state.is_bot(addr) {
+            result.push((addr, response));
+        } else if let Response::YourTurn{ id, .. } | Response::YourTurnPlaceToken{ id } = response {
+            if let Some(request) = bot_request(id, addr, state) {
+                to_process.extend(process_single_request(request, addr, state));
+            }
+        }
+    }
+    result
+}
+
+/// The move the bot at `addr` makes in game `id`. None if it doesn't have to move right now.
+/// Bots with no legal moves left forfeit.
+fn bot_request(id: GameId, addr: SocketAddr, state: &State) -> Option<Request> {
+    let inst = state.game(id)?;
+    let (player, level) = inst.player_index(addr).zip(inst.bot_level(addr))?;
+    let (game, game_state) = (inst.game(), inst.state().as_ref()?);
+    let mut bot = Bot::new(level, rand::thread_rng());
+    let visible = game_state.visible_state(Looker::Player(player));
+
+    let request = if game_state.game_over() {
+        return None;
+    } else if game_state.all_players_placed() && game_state.turn_player() == player {
+        bot.choose_tile_placement(game, &visible)
+            .map(|(kind, index, action, loc)| Request::PlaceTile{ id, kind, index, action, loc })
+    } else if game_state.players_placing_token(game).contains(&player) {
+        bot.choose_port(game, &visible)
+            .map(|port| Request::PlaceToken{ id, port })
+    } else {
+        return None;
+    };
+    Some(request.unwrap_or(Request::Forfeit{ id }))
+}
+
+/// Has the bots of every game make the moves they're waiting on, for games restored from a save.
+/// Returns a list of responses to send to peers.
+pub(crate) fn process_waiting_bots(state: &mut State) -> Vec<(SocketAddr, Response)> {
+    let prompts = state.games().iter()
+        .flat_map(|game| game.players().iter()
+            .filter(|player| player.bot().is_some())
+            .map(|player| (player.addr(), Response::YourTurnPlaceToken{ id: game.id() }))
+            .collect_vec())
+        .collect_vec();
+    drive_bots(prompts, state)
+}
+
+/// Removes a game and tells the lobby it's gone
+fn remove_game(id: GameId, state: &mut State) -> Vec<(SocketAddr, Response)> {
+    if state.remove_game(id).is_some() {
+        info!("Removed game {:?}", id);
+        state.lobby().values().map(|addr| (*addr, Response::RemovedGame{ id })).collect()
+    } else { vec![] }
+}
+
+/// Removes games that ended more than `keep_for` ago, sending everyone still in them back to the lobby.
+/// Returns a list of responses to send to peers.
+pub(crate) fn process_ended_games(now: Instant, keep_for: Duration, state: &mut State) -> Vec<(SocketAddr, Response)> {
+    let expired = state.games().iter()
+        .filter(|game| game.ended().is_some_and(|ended| now.saturating_duration_since(ended) >= keep_for))
+        .map(|game| (game.id(), game.players_and_spectators().map(|user| user.addr()).collect_vec()))
+        .collect_vec();
+
+    expired.into_iter().flat_map(|(id, addrs)| {
+        let mut responses = remove_game(id, state);
+        for addr in addrs {
+            // Disconnected peers go back to the lobby if they resume their session
+            if state.peer(addr).is_some() {
+                responses.extend(process_request(Request::JoinLobby, addr, state));
+            }
+        }
+        responses
+    }).collect()
+}
+
+/// Tells the turn player that it's their turn, along with the placements they're restricted to
+fn your_turn(id: GameId, game: &BaseGame, state: &BaseGameState) -> Response {
+    Response::YourTurn{ id, safe_placements: state.safe_tile_placements(game, state.turn_player()) }
+}
+
+/// Makes the moves that the game's time control calls for if the running clocks
+/// of game `id` ran out since generation `generation`, and returns a list of responses to send to peers.
+pub(crate) fn process_timeout(id: GameId, generation: u64, now: Instant, state: &mut State) -> Vec<(SocketAddr, Response)> {
+    let timed_out = state.game_mut(id).map_or(vec![], |inst| inst.timed_out(generation, now));
+    let mut responses = vec![];
+    for player in timed_out {
+        // Each move is chosen after the ones before it are made, so players placing their tokens
+        // at the same time can't both pick the same port
+        if let Some(request) = timeout_request(id, player, state) {
+            info!("Player {} of game {:?} ran out of time", player, id);
+            let addr = state.game(id).expect("Game should still exist").players()[player as usize].addr();
+            responses.extend(process_request(request, addr, state));
+        }
+    }
+    responses
+}
+
+/// The move made for `player` in game `id` when their time runs out, following the game's timeout policy.
+/// Players with no legal moves left are eliminated as well. None if the player doesn't have to move anymore.
+fn timeout_request(id: GameId, player: u32, state: &State) -> Option<Request> {
+    let inst = state.game(id)?;
+    let (game, game_state) = (inst.game(), inst.state().as_ref()?);
+    if game_state.game_over() || game_state.player_state(player).is_none() {
+        return None;
+    }
+    let visible = game_state.visible_state(Looker::Player(player));
+    let mut strategy = RandomStrategy::new(rand::thread_rng());
+    let request = match inst.settings().timeout_policy {
+        TimeoutPolicy::Eliminate => None,
+        TimeoutPolicy::RandomMove => if game_state.all_players_placed() {
+            strategy.choose_tile_placement(game, &visible)
+                .map(|(kind, index, action, loc)| Request::PlaceTile{ id, kind, index, action, loc })
+        } else {
+            strategy.choose_port(game, &visible)
+                .map(|port| Request::PlaceToken{ id, port })
+        },
+    };
+    Some(request.unwrap_or(Request::Forfeit{ id }))
+}
+
 /// Processes and responds to a request.
-pub(crate) async fn respond_to_request(req: Request, requester: SocketAddr, state: &Mutex<State>) {
+pub(crate) async fn respond_to_request(req: Request, requester: SocketAddr, state: &Arc<Mutex<State>>) {
     info!("Received request from {}: {:?}", requester, req);
-    let mut state = state.lock().await;
+    let mut guard = state.lock().await;
     
-    let responses = process_request(req, requester, &mut state);
+    let responses = process_request(req, requester, &mut guard);
+    send_responses(responses, &mut guard, state);
+}
+
+/// Removes games that ended more than `keep_for` ago and notifies the peers affected.
+pub(crate) async fn remove_ended_games(keep_for: Duration, state: &Arc<Mutex<State>>) {
+    let mut guard = state.lock().await;
+    let responses = process_ended_games(Instant::now(), keep_for, &mut guard);
+    send_responses(responses, &mut guard, state);
+}
+
+/// Schedules the timeouts of games restored from a save, and has their bots make the moves they're waiting on.
+pub(crate) async fn resume_restored_games(state: &Arc<Mutex<State>>) {
+    let mut guard = state.lock().await;
+    let responses = process_waiting_bots(&mut guard);
+    send_responses(responses, &mut guard, state);
+}
+
+/// Saves the changes processing made, sends responses to peers, and schedules the timeouts that processing asked for.
+/// `shared` is the same state as `state`, which timeouts lock when they fire.
+fn send_responses(responses: Vec<(SocketAddr, Response)>, state: &mut State, shared: &Arc<Mutex<State>>) {
+    state.save();
     for (addr, resp) in responses {
         if let Some(peer) = state.peer(addr) {
             if let Err(resp) = peer.tx().unbounded_send(resp) {
@@ -265,4 +643,527 @@ pub(crate) async fn respond_to_request(req: Request, requester: SocketAddr, stat
             warn!("Failed to send response to {}: peer was disconnected, attempted response: {:?}", addr, resp);
         }
     }
-}
\ No newline at end of file
+
+    for timeout in state.take_timeouts() {
+        let shared = Arc::clone(shared);
+        async_std::task::spawn(async move {
+            async_std::task::sleep(timeout.delay).await;
+            let mut state = shared.lock().await;
+            let responses = process_timeout(timeout.id, timeout.generation, Instant::now(), &mut state);
+            send_responses(responses, &mut state, &shared);
+        });
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::time::Duration;
+
+    use common::{board::{Port, TLoc}, game_settings::{BoardShape, StartPorts, TimeControl}, math::{pt2, Vec2u}, tile::{GAct, Kind, RegularTile, Tile}};
+    use fnv::FnvHashSet;
+    use futures::channel::mpsc;
+
+    use super::*;
+
+    pub(crate) fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    /// Connects a peer at some port and sets its username, returning the session token
+    pub(crate) fn connect(state: &mut State, port: u16, username: &str) -> SessionToken {
+        state.add_peer(addr(port), mpsc::unbounded().0);
+        let responses = process_request(Request::SetUsername{ username: username.to_owned() }, addr(port), state);
+        match responses.as_slice() {
+            [(_, Response::Session{ token, .. }), ..] => *token,
+            _ => panic!("Expected a session token"),
+        }
+    }
+
+    /// Connects peers a, b and c at ports 1, 2 and 3, then has a create and join a game with id 0.
+    /// b and c stay in the lobby.
+    pub(crate) fn set_up_game(state: &mut State, settings: GameSettings) {
+        for (port, name) in [(1, "a"), (2, "b"), (3, "c")] {
+            connect(state, port, name);
+        }
+        process_request(Request::CreateGame{ settings }, addr(1), state);
+        process_request(Request::JoinGame{ id: GameId(0) }, addr(1), state);
+    }
+
+    /// Like `set_up_game`, but b joins too, a starts the game, and c spectates
+    pub(crate) fn set_up_started_game(state: &mut State, settings: GameSettings) {
+        set_up_game(state, settings);
+        process_request(Request::JoinGame{ id: GameId(0) }, addr(2), state);
+        process_request(Request::StartGame{ id: GameId(0) }, addr(1), state);
+        process_request(Request::JoinGame{ id: GameId(0) }, addr(3), state);
+    }
+
+    /// A state set up with `set_up_game`
+    fn open_game_with(settings: GameSettings) -> State {
+        let mut state = State::new();
+        set_up_game(&mut state, settings);
+        state
+    }
+
+    /// Sets up a state with a started 2-player game with id 0 and a spectator.
+    /// Players are at ports 1 and 2, and the spectator is at port 3.
+    pub(crate) fn started_game() -> State {
+        started_game_with(GameSettings::default())
+    }
+
+    /// Like `started_game`, but with specific settings
+    fn started_game_with(settings: GameSettings) -> State {
+        let mut state = State::new();
+        set_up_started_game(&mut state, settings);
+        state
+    }
+
+    /// A start port on the top edge of the board, in front of tile (x, 0)
+    pub(crate) fn top_port(x: u32) -> BasePort {
+        (pt2(x, 0), Vec2u::new(1, 0)).wrap_base()
+    }
+
+    fn rejection(responses: &[(SocketAddr, Response)]) -> Option<RejectReason> {
+        match responses {
+            [(_, Response::Rejected{ reason, .. })] => Some(*reason),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_create_game_rejects_invalid_settings() {
+        let mut state = State::new();
+        connect(&mut state, 1, "a");
+
+        let settings = GameSettings { ports_per_edge: 0, ..GameSettings::default() };
+        let responses = process_request(Request::CreateGame{ settings }, addr(1), &mut state);
+        assert!(matches!(responses.as_slice(), [(_, Response::RejectedGameSettings{ .. })]));
+        assert!(state.games().is_empty());
+    }
+
+    /// Asserts that a game listed in the lobby doesn't reveal any hand or the draw pile
+    fn assert_lobby_safe(game: &common::GameInstance) {
+        let game_state = game.state().as_ref().expect("Expected the game to have started");
+        assert_eq!(game_state.looker(), Looker::Spectator);
+        for player in 0..game_state.num_players() {
+            let tiles = game_state.player_state(player).unwrap().tiles_vec();
+            assert!(tiles.iter().flat_map(|(_, tiles)| tiles).all(|tile| tile == &tile.hidden()));
+        }
+    }
+
+    #[test]
+    fn test_lobby_hides_tiles() {
+        let mut state = open_game_with(GameSettings::default());
+        process_request(Request::JoinGame{ id: GameId(0) }, addr(2), &mut state);
+
+        // Peer 3 is in the lobby when the game starts
+        let responses = process_request(Request::StartGame{ id: GameId(0) }, addr(1), &mut state);
+        let changed = responses.iter()
+            .filter_map(|(a, resp)| match resp {
+                Response::ChangedGame{ game } if *a == addr(3) => Some(game),
+                _ => None,
+            })
+            .collect_vec();
+        assert!(!changed.is_empty());
+        changed.into_iter().for_each(assert_lobby_safe);
+
+        let responses = process_request(Request::JoinLobby, addr(3), &mut state);
+        match responses.as_slice() {
+            [(_, Response::JoinedLobby{ games })] => games.iter().for_each(assert_lobby_safe),
+            _ => panic!("Expected to join the lobby"),
+        }
+    }
+
+    #[test]
+    fn test_player_limits() {
+        let mut state = open_game_with(GameSettings { min_players: 2, max_players: 2, ..GameSettings::default() });
+
+        let responses = process_request(Request::StartGame{ id: GameId(0) }, addr(1), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::NotEnoughPlayers));
+
+        process_request(Request::JoinGame{ id: GameId(0) }, addr(2), &mut state);
+        process_request(Request::JoinGame{ id: GameId(0) }, addr(3), &mut state);
+        let game = state.game(GameId(0)).unwrap();
+        assert_eq!(game.num_players(), 2);
+        assert_eq!(game.spectators().len(), 1);
+    }
+
+    #[test]
+    fn test_place_token_uses_requester_identity() {
+        let mut state = started_game();
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        let responses = process_request(Request::PlaceToken{ id: GameId(0), port: top_port(2) }, addr(2), &mut state);
+        assert!(responses.iter().any(|(_, resp)| matches!(resp, Response::PlacedToken{ player: 1, .. })));
+
+        let game_state = state.game(GameId(0)).unwrap().state().as_ref().unwrap();
+        assert_eq!(game_state.board_state().player_port(1), Some(top_port(2)));
+        assert_eq!(game_state.board_state().player_port(0), Some(top_port(0)));
+    }
+
+    #[test]
+    fn test_place_token_in_turn_order() {
+        let mut state = started_game();
+        let responses = process_request(Request::PlaceToken{ id: GameId(0), port: top_port(2) }, addr(2), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::NotYourTurn));
+
+        let responses = process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        assert!(responses.iter().any(|(a, resp)| *a == addr(2) && matches!(resp, Response::YourTurnPlaceToken{ .. })));
+
+        // No second placement
+        let responses = process_request(Request::PlaceToken{ id: GameId(0), port: top_port(1) }, addr(1), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::IllegalMove));
+
+        // No taking someone else's port
+        let responses = process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(2), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::IllegalMove));
+
+        let responses = process_request(Request::PlaceToken{ id: GameId(0), port: top_port(2) }, addr(2), &mut state);
+        assert!(responses.iter().any(|(a, resp)| *a == addr(1) && matches!(resp, Response::YourTurn{ .. })));
+    }
+
+    #[test]
+    fn test_place_token_rejects_non_player() {
+        let mut state = started_game();
+        let responses = process_request(Request::PlaceToken{ id: GameId(0), port: top_port(2) }, addr(3), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::NotAPlayer));
+
+        let responses = process_request(Request::PlaceToken{ id: GameId(1), port: top_port(2) }, addr(1), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::NoSuchGame));
+    }
+
+    #[test]
+    fn test_moves_of_wrong_type_rejected() {
+        let mut state = started_game();
+        let hex_port = BasePort::Pt2iU32((pt2(0, 0), 0));
+        let responses = process_request(Request::PlaceToken{ id: GameId(0), port: hex_port }, addr(1), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::IllegalMove));
+
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(3) }, addr(2), &mut state);
+        let tile = RegularTile::<4>::new(vec![]);
+        let responses = process_request(Request::PlaceTile {
+            id: GameId(0),
+            kind: ().wrap_base(),
+            index: 0,
+            action: tile.identity_action().wrap_base(),
+            loc: BaseTLoc::Pt2i(pt2(0, 0)),
+        }, addr(1), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::IllegalMove));
+    }
+
+    #[test]
+    fn test_place_tile_rejects_out_of_turn() {
+        let mut state = started_game();
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(3) }, addr(2), &mut state);
+
+        let tile = RegularTile::<4>::new(vec![]);
+        let place_tile = |x: u32| Request::PlaceTile {
+            id: GameId(0),
+            kind: ().wrap_base(),
+            index: 0,
+            action: tile.identity_action().wrap_base(),
+            loc: pt2(x, 0).wrap_base(),
+        };
+
+        // Player 1 tries to move on player 0's turn
+        let responses = process_request(place_tile(3), addr(2), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::NotYourTurn));
+
+        // Spectators can't move at all
+        let responses = process_request(place_tile(0), addr(3), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::NotAPlayer));
+
+        // Player 0 can't place a tile away from their token
+        let responses = process_request(place_tile(3), addr(1), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::IllegalMove));
+
+        let responses = process_request(place_tile(0), addr(1), &mut state);
+        assert!(responses.iter().any(|(_, resp)| matches!(resp, Response::PlacedTile{ player: 0, .. })));
+    }
+
+    #[test]
+    fn test_place_tile_rejects_before_tokens_placed() {
+        let mut state = started_game();
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+
+        let responses = process_request(Request::PlaceTile {
+            id: GameId(0),
+            kind: ().wrap_base(),
+            index: 0,
+            action: RegularTile::<4>::new(vec![]).identity_action().wrap_base(),
+            loc: pt2(0, 0).wrap_base(),
+        }, addr(1), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::WrongPhase));
+    }
+
+    #[test]
+    fn test_resume_session() {
+        let mut state = started_game();
+        let token = state.remove_peer(addr(1)).expect("Expected a session");
+
+        // Typing the same username doesn't take over the seat
+        state.add_peer(addr(4), mpsc::unbounded().0);
+        let responses = process_request(Request::SetUsername{ username: "a".to_owned() }, addr(4), &mut state);
+        assert!(matches!(responses.as_slice(), [(_, Response::RejectedUsername)]));
+        let responses = process_request(Request::Resume{ token: SessionToken(0) }, addr(4), &mut state);
+        assert!(matches!(responses.as_slice(), [(_, Response::RejectedSession)]));
+
+        // But the token does
+        let responses = process_request(Request::Resume{ token }, addr(4), &mut state);
+        assert!(matches!(responses.first(), Some((_, Response::Session{ .. }))));
+        assert!(responses.iter().any(|(a, resp)| *a == addr(4) && matches!(resp, Response::JoinedGame{ .. })));
+        assert!(responses.iter().any(|(a, resp)| *a == addr(4) && matches!(resp, Response::YourTurnPlaceToken{ .. })));
+        assert_eq!(state.game(GameId(0)).unwrap().player_index(addr(4)), Some(0));
+
+        // A session can't be resumed twice at once
+        state.add_peer(addr(5), mpsc::unbounded().0);
+        let responses = process_request(Request::Resume{ token }, addr(5), &mut state);
+        assert!(matches!(responses.as_slice(), [(_, Response::RejectedSession)]));
+    }
+
+    #[test]
+    fn test_resume_second_session_rejected() {
+        let mut state = started_game();
+        let token = state.remove_peer(addr(1)).unwrap();
+
+        // Player b can't also take a's seat
+        let responses = process_request(Request::Resume{ token }, addr(2), &mut state);
+        assert!(matches!(responses.as_slice(), [(_, Response::RejectedSession)]));
+        assert_eq!(state.game(GameId(0)).unwrap().player_index(addr(2)), Some(1));
+    }
+
+    #[test]
+    fn test_removed_peer_leaves_lobby() {
+        let mut state = State::new();
+        connect(&mut state, 1, "a");
+        assert_eq!(state.lobby().get("a"), Some(&addr(1)));
+        state.remove_peer(addr(1));
+        assert!(state.lobby().is_empty());
+    }
+
+    #[test]
+    fn test_expired_session() {
+        let mut state = State::new();
+        let token = connect(&mut state, 1, "a");
+        state.remove_peer(addr(1));
+        assert!(state.expire_session(token, addr(1)));
+
+        state.add_peer(addr(2), mpsc::unbounded().0);
+        let responses = process_request(Request::Resume{ token }, addr(2), &mut state);
+        assert!(matches!(responses.as_slice(), [(_, Response::RejectedSession)]));
+        connect(&mut state, 2, "a");
+    }
+
+    #[test]
+    fn test_forfeit() {
+        let mut state = started_game();
+        let responses = process_request(Request::Forfeit{ id: GameId(0) }, addr(3), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::NotAPlayer));
+
+        let responses = process_request(Request::Forfeit{ id: GameId(0) }, addr(2), &mut state);
+        for port in 1..=3 {
+            assert!(responses.iter().any(|(a, resp)| *a == addr(port) && matches!(resp, Response::Forfeited{ player: 1, .. })));
+        }
+        let game_state = state.game(GameId(0)).unwrap().state().as_ref().unwrap();
+        assert!(game_state.won(0));
+
+        let responses = process_request(Request::Forfeit{ id: GameId(0) }, addr(1), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::WrongPhase));
+    }
+
+    #[test]
+    fn test_leaving_started_game_forfeits() {
+        let mut state = started_game();
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(3) }, addr(2), &mut state);
+
+        // The turn player leaves, so the game doesn't wait for them
+        let responses = process_request(Request::JoinLobby, addr(1), &mut state);
+        assert!(responses.iter().any(|(a, resp)| *a == addr(2) && matches!(resp, Response::Forfeited{ player: 0, .. })));
+        let game_state = state.game(GameId(0)).unwrap().state().as_ref().unwrap();
+        assert!(game_state.won(1));
+    }
+
+    /// Fires the only pending timeout as if its delay passed, returning the responses
+    fn fire_timeout(state: &mut State) -> Vec<(SocketAddr, Response)> {
+        let timeouts = state.take_timeouts();
+        assert_eq!(timeouts.len(), 1);
+        let Timeout{ id, generation, delay } = timeouts[0];
+        process_timeout(id, generation, Instant::now() + delay, state)
+    }
+
+    #[test]
+    fn test_timeout_random_move() {
+        let mut state = started_game_with(GameSettings {
+            time_control: Some(TimeControl::PerMove{ seconds: 10 }),
+            ..GameSettings::default()
+        });
+        let responses = fire_timeout(&mut state);
+        assert!(responses.iter().any(|(_, resp)| matches!(resp, Response::PlacedToken{ player: 0, .. })));
+        assert!(responses.iter().any(|(a, resp)| *a == addr(2) && matches!(resp, Response::Clock{ running, .. } if running == &vec![1])));
+
+        fire_timeout(&mut state);
+        let responses = fire_timeout(&mut state);
+        assert!(responses.iter().any(|(_, resp)| matches!(resp, Response::PlacedTile{ player: 0, .. })));
+    }
+
+    #[test]
+    fn test_timeout_simultaneous_placement() {
+        // With only two start ports, moves chosen against the same state would often clash
+        for _ in 0..10 {
+            let mut state = started_game_with(GameSettings {
+                start_ports: StartPorts::Custom(vec![top_port(0), top_port(1)]),
+                max_players: 2,
+                token_placement: TokenPlacement::Simultaneous,
+                time_control: Some(TimeControl::PerMove{ seconds: 10 }),
+                ..GameSettings::default()
+            });
+            let responses = fire_timeout(&mut state);
+            assert!(!responses.iter().any(|(_, resp)| matches!(resp, Response::Rejected{ .. })));
+            let game_state = state.game(GameId(0)).unwrap().state().as_ref().unwrap();
+            assert!(game_state.all_players_placed());
+        }
+    }
+
+    #[test]
+    fn test_timeout_eliminate() {
+        let mut state = started_game_with(GameSettings {
+            time_control: Some(TimeControl::Clock{ budget_seconds: 60, increment_seconds: 5 }),
+            timeout_policy: TimeoutPolicy::Eliminate,
+            ..GameSettings::default()
+        });
+        let timeout = state.take_timeouts()[0];
+
+        // Moving in time makes the timeout stale
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        let later = Instant::now() + Duration::from_secs(120);
+        assert!(process_timeout(timeout.id, timeout.generation, later, &mut state).is_empty());
+
+        let responses = fire_timeout(&mut state);
+        assert!(responses.iter().any(|(_, resp)| matches!(resp, Response::Forfeited{ player: 1, .. })));
+        let game_state = state.game(GameId(0)).unwrap().state().as_ref().unwrap();
+        assert!(game_state.won(0));
+        assert!(state.take_timeouts().is_empty());
+    }
+
+    #[test]
+    fn test_abandoned_game_removed() {
+        let mut state = open_game_with(GameSettings::default());
+        process_request(Request::JoinGame{ id: GameId(0) }, addr(2), &mut state);
+
+        process_request(Request::JoinLobby, addr(1), &mut state);
+        assert!(state.game(GameId(0)).is_some());
+
+        let responses = process_request(Request::JoinLobby, addr(2), &mut state);
+        assert!(state.game(GameId(0)).is_none());
+        assert!(responses.iter().any(|(a, resp)| *a == addr(3) && matches!(resp, Response::RemovedGame{ id: GameId(0) })));
+    }
+
+    #[test]
+    fn test_ended_game_removed() {
+        let mut state = started_game();
+        let keep_for = Duration::from_secs(600);
+        assert!(process_ended_games(Instant::now() + keep_for, keep_for, &mut state).is_empty());
+
+        process_request(Request::Forfeit{ id: GameId(0) }, addr(2), &mut state);
+        let ended = state.game(GameId(0)).unwrap().ended().expect("Game should have ended");
+
+        // The final state is kept for a while
+        assert!(process_ended_games(ended + keep_for / 2, keep_for, &mut state).is_empty());
+        assert!(state.game(GameId(0)).is_some());
+
+        let responses = process_ended_games(ended + keep_for, keep_for, &mut state);
+        assert!(state.game(GameId(0)).is_none());
+        for port in 1..=3 {
+            assert!(responses.iter().any(|(a, resp)| *a == addr(port) && matches!(resp, Response::JoinedLobby{ games } if games.is_empty())));
+        }
+    }
+
+    #[test]
+    fn test_get_replay() {
+        let mut state = started_game();
+        let responses = process_request(Request::GetReplay{ id: GameId(0) }, addr(3), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::WrongPhase));
+
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(3) }, addr(2), &mut state);
+        process_request(Request::Forfeit{ id: GameId(0) }, addr(1), &mut state);
+
+        let responses = process_request(Request::GetReplay{ id: GameId(0) }, addr(3), &mut state);
+        let replay = match responses.as_slice() {
+            [(_, Response::Replay{ replay, .. })] => replay,
+            _ => panic!("Expected a replay"),
+        };
+        assert_eq!(replay.moves().len(), 3);
+        let final_state = replay.final_state();
+        assert!(final_state.game_over());
+        assert!(final_state.won(1));
+        assert_eq!(final_state.board_state().player_port(1), Some(top_port(3)));
+    }
+
+    #[test]
+    fn test_bots_play() {
+        let mut state = open_game_with(GameSettings::default());
+
+        let responses = process_request(Request::AddBot{ id: GameId(0), level: BotLevel::Hard }, addr(2), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::NotAPlayer));
+        process_request(Request::AddBot{ id: GameId(0), level: BotLevel::Hard }, addr(1), &mut state);
+        process_request(Request::AddBot{ id: GameId(0), level: BotLevel::Easy }, addr(1), &mut state);
+        assert_eq!(state.game(GameId(0)).unwrap().to_common().players(), &["a", "Hard bot 1", "Easy bot 2"]);
+
+        // The human moves first, and the bots move right after
+        process_request(Request::StartGame{ id: GameId(0) }, addr(1), &mut state);
+        let responses = process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+        assert!(responses.iter().all(|(a, _)| *a == addr(1)), "Responses sent to bots");
+        assert!(responses.iter().any(|(_, resp)| matches!(resp, Response::PlacedToken{ player: 2, .. })));
+
+        while let Some(game_state) = state.game(GameId(0)).unwrap().state().as_ref().filter(|state| !state.game_over()) {
+            let game = state.game(GameId(0)).unwrap().game();
+            assert_eq!(game_state.turn_player(), 0, "Bots didn't move");
+            let request = match game_state.legal_tile_placements(game, 0).pop() {
+                Some((kind, index, action, loc)) => Request::PlaceTile{ id: GameId(0), kind, index, action, loc },
+                None => Request::Forfeit{ id: GameId(0) },
+            };
+            process_request(request, addr(1), &mut state);
+        }
+        assert!(state.game(GameId(0)).unwrap().ended().is_some());
+    }
+
+    #[test]
+    fn test_bots_play_out_game_without_people() {
+        let mut state = open_game_with(GameSettings { board: BoardShape::Rectangle{ width: 12, height: 12 }, ..GameSettings::default() });
+        for _ in 0..3 {
+            process_request(Request::AddBot{ id: GameId(0), level: BotLevel::Easy }, addr(1), &mut state);
+        }
+        process_request(Request::StartGame{ id: GameId(0) }, addr(1), &mut state);
+        process_request(Request::PlaceToken{ id: GameId(0), port: top_port(0) }, addr(1), &mut state);
+
+        // The bots take every turn left in the game while handling the forfeit
+        process_request(Request::Forfeit{ id: GameId(0) }, addr(1), &mut state);
+        let game = state.game(GameId(0)).unwrap();
+        assert!(game.state().as_ref().unwrap().game_over());
+        assert!(game.ended().is_some());
+    }
+
+    #[test]
+    fn test_bot_addrs_never_repeat() {
+        // More than fit in a port number
+        let mut state = State::new();
+        let addrs = (0..70_000).map(|_| state.next_bot_addr()).collect::<FnvHashSet<_>>();
+        assert_eq!(addrs.len(), 70_000);
+        assert!(addrs.iter().all(|addr| addr.port() != 0));
+    }
+
+    #[test]
+    fn test_add_bot_to_full_game() {
+        let mut state = open_game_with(GameSettings { min_players: 2, max_players: 2, ..GameSettings::default() });
+
+        process_request(Request::AddBot{ id: GameId(0), level: BotLevel::Medium }, addr(1), &mut state);
+        let responses = process_request(Request::AddBot{ id: GameId(0), level: BotLevel::Medium }, addr(1), &mut state);
+        assert_eq!(rejection(&responses), Some(RejectReason::GameFull));
+
+        // Bots don't keep a game around once everyone left
+        process_request(Request::JoinLobby, addr(1), &mut state);
+        assert!(state.games().is_empty());
+    }
+}
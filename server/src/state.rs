@@ -1,39 +1,87 @@
-use std::{net::SocketAddr, collections::{HashMap, hash_map}};
+use std::{net::{Ipv6Addr, SocketAddr}, collections::{HashMap, hash_map}, io, time::{Duration, Instant}};
 
-use common::{message::Response};
+use common::message::{Response, SessionToken};
 use common::game::{GameId, BaseGame};
+use common::game_settings::GameSettings;
 
-use fnv::FnvHashMap;
+use fnv::{FnvHashMap, FnvHashSet};
 use futures::channel::mpsc::UnboundedSender;
 use getset::{Getters, MutGetters};
+use rand::Rng;
 
 use crate::game::{GameInstance};
+use crate::persist::{Changes, Flusher, SavedMeta, Store};
 
 type PeerMap = FnvHashMap<SocketAddr, Peer>;
 
+/// Placeholder addresses are in the IPv6 documentation range, so they can't clash with peers.
+/// Restored sessions and bots each get their own block in it.
+const SESSION_ADDR_BLOCK: u128 = 0x2001_0db8_0000_0001 << 64;
+const BOT_ADDR_BLOCK: u128 = 0x2001_0db8_0000_0002 << 64;
+
+/// Placeholder address number `n` in a block of the documentation range
+fn placeholder_addr(block: u128, n: u64) -> SocketAddr {
+    SocketAddr::from((Ipv6Addr::from(block | n as u128), 1))
+}
+
 #[derive(Debug, Getters, MutGetters)]
 pub struct Peer {
     #[getset(get = "pub")]
     username: String,
     #[getset(get = "pub")]
     tx: UnboundedSender<Response>,
+    /// None if the peer hasn't set a username yet
+    session: Option<SessionToken>,
 }
 
 impl Peer {
 }
 
+/// A check for whether a game's running clocks ran out, to do after some delay
+#[derive(Clone, Copy, Debug)]
+pub struct Timeout {
+    pub id: GameId,
+    /// The generation of the game's clock when the timeout was scheduled
+    pub generation: u64,
+    pub delay: Duration,
+}
+
+/// A username claimed by a peer, which outlives the peer's connection for a while
+#[derive(Debug)]
+struct Session {
+    username: String,
+    /// The address of the peer that last used this session
+    addr: SocketAddr,
+    /// Whether that peer is still connected
+    connected: bool,
+}
+
 #[derive(Debug, Getters, MutGetters)]
 pub struct State {
     #[getset(get = "pub")]
     peers: PeerMap,
-    /// Maps usernames to addresses
+    /// Maps usernames to addresses, including those of disconnected peers whose sessions can still be resumed
     inv_peers: HashMap<String, SocketAddr>,
+    sessions: FnvHashMap<SessionToken, Session>,
     #[getset(get = "pub", get_mut = "pub")]
     games: Vec<GameInstance>,
     /// Map of players outside any game to their addresses
     #[getset(get = "pub")]
     lobby: HashMap<String, SocketAddr>,
     id_counter: u32,
+    /// Timeouts waiting to be scheduled
+    timeouts: Vec<Timeout>,
+    /// Where to save the state. None if it isn't saved.
+    flusher: Option<Flusher>,
+    /// Games removed since the last save, whose files have to be deleted.
+    /// The other games keep track of their own changes.
+    removed_games: FnvHashSet<GameId>,
+    /// Whether anything saved outside the games changed since the last save
+    changed_meta: bool,
+    /// Number of restored session addresses handed out so far
+    session_counter: u64,
+    /// Number of bot addresses handed out so far
+    bot_counter: u64,
 }
 
 impl State {
@@ -41,9 +89,97 @@ impl State {
         Self {
             peers: FnvHashMap::default(),
             inv_peers: HashMap::default(),
+            sessions: FnvHashMap::default(),
             games: vec![],
             lobby: HashMap::default(),
             id_counter: 0,
+            timeouts: vec![],
+            flusher: None,
+            removed_games: FnvHashSet::default(),
+            changed_meta: false,
+            session_counter: 0,
+            bot_counter: 0,
+        }
+    }
+
+    /// Loads the state saved in a store, and keeps saving to it.
+    /// Everyone who was in a game gets a disconnected session, at a placeholder address, that they can resume.
+    pub fn load(store: Store) -> io::Result<Self> {
+        let (meta, games) = store.load()?;
+        let mut state = Self { id_counter: meta.id_counter, ..Self::new() };
+        for (token, username) in meta.sessions {
+            state.restore_session(token, username);
+        }
+
+        let now = Instant::now();
+        for game in games {
+            let game = GameInstance::restore(game, |username, is_bot| if is_bot {
+                state.next_bot_addr()
+            } else {
+                state.inv_peers.get(username).copied().unwrap_or_else(|| {
+                    // The player's session expired, but they still need an address in the game
+                    let token = SessionToken(rand::thread_rng().gen());
+                    state.restore_session(token, username.to_owned())
+                })
+            }, now);
+            state.games.push(game);
+        }
+
+        for game in &mut state.games {
+            if let Some((generation, delay)) = game.restart_clock(&[], now) {
+                state.timeouts.push(Timeout{ id: game.id(), generation, delay });
+            }
+        }
+        state.flusher = Some(Flusher::new(store));
+        Ok(state)
+    }
+
+    /// Adds a disconnected session at a placeholder address never handed out before, returning the address
+    fn restore_session(&mut self, token: SessionToken, username: String) -> SocketAddr {
+        self.session_counter += 1;
+        let addr = placeholder_addr(SESSION_ADDR_BLOCK, self.session_counter);
+        self.inv_peers.insert(username.clone(), addr);
+        self.sessions.insert(token, Session { username, addr, connected: false });
+        self.changed_meta = true;
+        addr
+    }
+
+    /// A placeholder address for a new bot, never handed out before.
+    /// These can't clash with peers or restored sessions.
+    pub fn next_bot_addr(&mut self) -> SocketAddr {
+        self.bot_counter += 1;
+        placeholder_addr(BOT_ADDR_BLOCK, self.bot_counter)
+    }
+
+    /// Whether an address belongs to a bot
+    pub fn is_bot(&self, addr: SocketAddr) -> bool {
+        self.games.iter().any(|game| game.bot_level(addr).is_some())
+    }
+
+    /// Sessions whose peers are disconnected, with their last addresses
+    pub fn disconnected_sessions(&self) -> Vec<(SessionToken, SocketAddr)> {
+        self.sessions.iter()
+            .filter(|(_, session)| !session.connected)
+            .map(|(token, session)| (*token, session.addr))
+            .collect()
+    }
+
+    /// Saves whatever changed since the last save, if there's a store.
+    /// The files are written in the background, so this doesn't wait on the disk.
+    pub fn save(&mut self) {
+        let Some(flusher) = &self.flusher else { return };
+        let mut changes = Changes { games: self.removed_games.drain().map(|id| (id, None)).collect(), meta: None };
+        changes.games.extend(self.games.iter_mut()
+            .filter(|game| game.unsaved())
+            .map(|game| (game.id(), Some(game.take_saved()))));
+        if std::mem::take(&mut self.changed_meta) {
+            changes.meta = Some(SavedMeta {
+                id_counter: self.id_counter,
+                sessions: self.sessions.iter().map(|(token, session)| (*token, session.username.clone())).collect(),
+            });
+        }
+        if !changes.is_empty() {
+            flusher.send(changes);
         }
     }
 
@@ -56,38 +192,101 @@ impl State {
     }
 
     pub fn remove_from_lobby_by_addr(&mut self, addr: SocketAddr) {
-        if let Some(peer) = self.peers.get(&addr) {
-            self.lobby.remove(peer.username());
-        }
+        // The peer may have disconnected already, so don't look up its username
+        self.lobby.retain(|_, lobby_addr| *lobby_addr != addr);
     }
 
     /// Add a peer with a placeholder username
     pub fn add_peer(&mut self, addr: SocketAddr, tx: UnboundedSender<Response>) {
-        self.peers.insert(addr, Peer { username: "???".to_owned(), tx });
+        self.peers.insert(addr, Peer { username: "???".to_owned(), tx, session: None });
     }
     
-    /// Removes a peer
-    pub fn remove_peer(&mut self, addr: SocketAddr) {
-        if let Some(username) = self.peers.get(&addr).map(|peer| peer.username()) {
-            self.inv_peers.remove(username);
+    /// Removes a peer, and takes them out of the lobby. Their session, if any, is kept so that they can resume it.
+    /// Returns the session token.
+    pub fn remove_peer(&mut self, addr: SocketAddr) -> Option<SessionToken> {
+        self.remove_from_lobby_by_addr(addr);
+        let token = self.peers.remove(&addr).and_then(|peer| peer.session);
+        if let Some(session) = token.and_then(|token| self.sessions.get_mut(&token)) {
+            session.connected = false;
         }
-        self.peers.remove(&addr);
+        token
     }
     
-    /// Set the username of a peer, assuming it exists.
-    /// Returns false instead if the username is not unique.
-    pub fn set_username(&mut self, addr: SocketAddr, username: String) -> bool {
+    /// Set the username of a peer, assuming it exists, and start a new session for it.
+    /// Returns the session token, or None instead if the username is not unique.
+    pub fn set_username(&mut self, addr: SocketAddr, username: String) -> Option<SessionToken> {
         if let hash_map::Entry::Vacant(e) = self.inv_peers.entry(username.clone()) {
-            self.peers.get_mut(&addr)
-                .expect("Expected peer to exist")
-                .username = username;
             e.insert(addr);
+            let token = SessionToken(rand::thread_rng().gen());
+            self.sessions.insert(token, Session { username: username.clone(), addr, connected: true });
+            self.changed_meta = true;
+
+            let peer = self.peers.get_mut(&addr).expect("Expected peer to exist");
+            peer.username = username;
+            if let Some(old) = peer.session.replace(token) {
+                self.end_session(old);
+            }
+            Some(token)
+        } else {
+            None
+        }
+    }
+
+    /// Have a peer, assuming it exists, take over a session whose peer disconnected.
+    /// Returns the address the session was at, or None if there's no such session, it's still connected,
+    /// or the peer already has a session, since its address would then be seated in both sessions' games.
+    pub fn resume_session(&mut self, addr: SocketAddr, token: SessionToken) -> Option<SocketAddr> {
+        if self.peers.get(&addr).expect("Expected peer to exist").session.is_some() {
+            return None;
+        }
+        let session = self.sessions.get_mut(&token).filter(|session| !session.connected)?;
+        let old_addr = session.addr;
+        session.addr = addr;
+        session.connected = true;
+        let username = session.username.clone();
+        self.inv_peers.insert(username.clone(), addr);
+
+        let peer = self.peers.get_mut(&addr).expect("Expected peer to exist");
+        peer.username = username;
+        peer.session = Some(token);
+
+        if let Some(username) = self.lobby.iter().find(|(_, a)| **a == old_addr).map(|(name, _)| name.clone()) {
+            self.lobby.insert(username, addr);
+        }
+        for game in &mut self.games {
+            game.replace_addr(old_addr, addr);
+        }
+        Some(old_addr)
+    }
+
+    /// Ends a session if its peer is still disconnected from address `addr`, freeing up its username.
+    /// Returns whether the session ended.
+    pub fn expire_session(&mut self, token: SessionToken, addr: SocketAddr) -> bool {
+        if self.sessions.get(&token).is_some_and(|session| !session.connected && session.addr == addr) {
+            self.end_session(token);
             true
         } else {
             false
         }
     }
 
+    fn end_session(&mut self, token: SessionToken) {
+        if let Some(session) = self.sessions.remove(&token) {
+            self.inv_peers.remove(&session.username);
+            self.changed_meta = true;
+        }
+    }
+
+    /// Asks for a timeout to be scheduled
+    pub fn push_timeout(&mut self, timeout: Timeout) {
+        self.timeouts.push(timeout);
+    }
+
+    /// Takes the timeouts waiting to be scheduled
+    pub fn take_timeouts(&mut self) -> Vec<Timeout> {
+        std::mem::take(&mut self.timeouts)
+    }
+
     /// Get the peer, if it exists.
     pub fn peer(&self, addr: SocketAddr) -> Option<&Peer> {
         self.peers.get(&addr)
@@ -98,13 +297,20 @@ impl State {
     }
 
     /// Adds a game to the list and returns a reference to it.
-    pub fn add_game(&mut self, game: BaseGame) -> &GameInstance {
+    pub fn add_game(&mut self, game: BaseGame, settings: GameSettings) -> &GameInstance {
         let id = GameId(self.id_counter);
         self.id_counter += 1;
-        self.games.push(GameInstance::new(id, game));
+        self.changed_meta = true;
+        self.games.push(GameInstance::new(id, game, settings));
         self.games.last().unwrap()
     }
 
+    /// Removes a game by id, returning it if it existed
+    pub fn remove_game(&mut self, id: GameId) -> Option<GameInstance> {
+        self.removed_games.insert(id);
+        self.game_index(id).map(|i| self.games.remove(i))
+    }
+
     fn game_index(&self, id: GameId) -> Option<usize> {
         self.games.binary_search_by_key(&id, |game| game.id()).ok()
     }
@@ -0,0 +1,474 @@
+use std::time::Instant;
+
+use common::board::{BasePort, BaseTLoc};
+use common::bot::BotLevel;
+use common::game::{BaseGame, GameId};
+use common::game_settings::GameSettings;
+use common::game_state::{BaseGameState, BaseTilePlacement};
+use common::message::{Request, Response};
+use common::player_state::Looker;
+use common::tile::{BaseKind, BaseTile};
+use common::GameInstance;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use itertools::Itertools;
+
+/// What the player is doing in a started game
+#[derive(Clone, Debug)]
+pub enum Turn {
+    /// Waiting for other players
+    Wait,
+    /// A move was sent and the server hasn't accepted it yet. Goes back to the turn inside if it's rejected.
+    Sent(Box<Turn>),
+    /// Choosing a start port among `ports`
+    PlaceToken{ ports: Vec<BasePort>, selected: usize },
+    /// Choosing a tile from the hand, how many times to rotate it clockwise, and which open location to put it on.
+    /// If the game forbids suicide, `safe_placements` are the only placements allowed.
+    PlaceTile{ safe_placements: Option<Vec<BaseTilePlacement>>, tile: usize, rotation: i32, loc: usize },
+}
+
+/// Time left on each player's clock, as of when it was received
+#[derive(Clone, Debug)]
+pub struct ClockDisplay {
+    pub remaining_ms: Vec<u64>,
+    pub running: Vec<u32>,
+    pub received: Instant,
+}
+
+impl ClockDisplay {
+    /// Time left on a player's clock in milliseconds, counting down if it's running
+    pub fn remaining_ms(&self, player: u32) -> u64 {
+        let remaining = self.remaining_ms.get(player as usize).copied().unwrap_or(0);
+        if self.running.contains(&player) {
+            remaining.saturating_sub(self.received.elapsed().as_millis() as u64)
+        } else {
+            remaining
+        }
+    }
+}
+
+/// A game that was joined, started or not
+#[derive(Clone, Debug)]
+pub struct GameView {
+    pub id: GameId,
+    pub game: BaseGame,
+    pub settings: GameSettings,
+    pub players: Vec<String>,
+    /// None if the game hasn't started
+    pub state: Option<BaseGameState>,
+    pub turn: Turn,
+    pub clock: Option<ClockDisplay>,
+}
+
+impl GameView {
+    fn new(game: GameInstance) -> Self {
+        let (id, game, settings, state, players) = game.into_fields();
+        Self { id, game, settings, players, state, turn: Turn::Wait, clock: None }
+    }
+
+    /// The player this client plays as. None for spectators and games that haven't started.
+    pub fn player(&self) -> Option<u32> {
+        match self.state.as_ref()?.looker() {
+            Looker::Player(player) => Some(player),
+            _ => None,
+        }
+    }
+
+    /// The tiles in this client's hand, with their kinds and indices
+    pub fn hand(&self) -> Vec<(BaseKind, u32, BaseTile)> {
+        self.state.as_ref().zip(self.player())
+            .and_then(|(state, player)| state.player_state(player))
+            .map_or(vec![], |hand| hand.tiles_vec().into_iter()
+                .flat_map(|(kind, tiles)| tiles.into_iter().enumerate()
+                    .map(move |(index, tile)| (kind.clone(), index as u32, tile)))
+                .collect())
+    }
+
+    /// The locations this client's token faces
+    pub fn open_locs(&self) -> Vec<BaseTLoc> {
+        self.state.as_ref().zip(self.player())
+            .map_or(vec![], |(state, player)| state.open_locs(&self.game, player))
+    }
+
+    /// The tile placement currently chosen, if this client is placing a tile
+    pub fn chosen_placement(&self) -> Option<BaseTilePlacement> {
+        if let Turn::PlaceTile{ tile, rotation, loc, .. } = &self.turn {
+            let (kind, index, tile) = self.hand().into_iter().nth(*tile)?;
+            let loc = self.open_locs().into_iter().nth(*loc)?;
+            Some((kind, index, tile.rotation_action(*rotation), loc))
+        } else {
+            None
+        }
+    }
+
+    /// The start port currently chosen, if this client is placing their token
+    pub fn chosen_port(&self) -> Option<&BasePort> {
+        if let Turn::PlaceToken{ ports, selected } = &self.turn {
+            ports.get(*selected)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the chosen tile placement would eliminate this client's player
+    pub fn chosen_placement_eliminates(&self) -> bool {
+        self.state.as_ref().zip(self.player()).zip(self.chosen_placement())
+            .is_some_and(|((state, player), (kind, index, action, loc))| state.hand_tile(player, &kind, index)
+                .is_some_and(|tile| state.placing_eliminates(&self.game, player, &tile.apply_action(&action), &loc)))
+    }
+
+    /// Start ports that are still free
+    fn free_ports(&self) -> Vec<BasePort> {
+        self.state.as_ref().map_or(vec![], |state| {
+            let mut state = state.clone();
+            self.game.start_ports().into_iter()
+                .filter(|port| state.can_place_player(&self.game, port))
+                .collect()
+        })
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, message: &mut String, requests: &mut Vec<Request>) {
+        let id = self.id;
+        let num_tiles = self.hand().len();
+        let num_locs = self.open_locs().len();
+        let placement = self.chosen_placement();
+
+        match (&mut self.turn, key.code) {
+            (_, KeyCode::Esc) => requests.push(Request::JoinLobby),
+            (_, KeyCode::Char('F')) if self.state.is_some() => requests.push(Request::Forfeit{ id }),
+
+            (Turn::PlaceToken{ ports, selected }, KeyCode::Left | KeyCode::Up | KeyCode::Char('h' | 'k')) =>
+                *selected = (*selected + ports.len().max(1) - 1) % ports.len().max(1),
+            (Turn::PlaceToken{ ports, selected }, KeyCode::Right | KeyCode::Down | KeyCode::Char('l' | 'j')) =>
+                *selected = (*selected + 1) % ports.len().max(1),
+            (Turn::PlaceToken{ ports, selected }, KeyCode::Enter) => if let Some(port) = ports.get(*selected).cloned() {
+                requests.push(Request::PlaceToken{ id, port });
+                self.turn = Turn::Sent(Box::new(self.turn.clone()));
+            },
+
+            (Turn::PlaceTile{ tile, .. }, KeyCode::Char(c @ '1'..='9')) => {
+                let index = c.to_digit(10).unwrap() as usize - 1;
+                if index < num_tiles {
+                    *tile = index;
+                }
+            }
+            (Turn::PlaceTile{ tile, .. }, KeyCode::Tab) => *tile = (*tile + 1) % num_tiles.max(1),
+            (Turn::PlaceTile{ rotation, .. }, KeyCode::Char('r')) => *rotation += 1,
+            (Turn::PlaceTile{ rotation, .. }, KeyCode::Char('R')) => *rotation -= 1,
+            (Turn::PlaceTile{ loc, .. }, KeyCode::Left | KeyCode::Up | KeyCode::Char('h' | 'k')) =>
+                *loc = (*loc + num_locs.max(1) - 1) % num_locs.max(1),
+            (Turn::PlaceTile{ loc, .. }, KeyCode::Right | KeyCode::Down | KeyCode::Char('l' | 'j')) =>
+                *loc = (*loc + 1) % num_locs.max(1),
+            (Turn::PlaceTile{ safe_placements, .. }, KeyCode::Enter) => if let Some((kind, index, action, loc)) = placement {
+                let allowed = safe_placements.as_ref().is_none_or(|safe| safe.iter()
+                    .any(|(k, i, a, l)| (k, i, l) == (&kind, &index, &loc) &&
+                        self.state.as_ref().and_then(|state| state.hand_tile(state.player_expect(), k, *i))
+                            .is_some_and(|tile| tile.apply_action(a) == tile.apply_action(&action))));
+                if allowed {
+                    requests.push(Request::PlaceTile{ id, kind, index, action, loc });
+                    self.turn = Turn::Sent(Box::new(self.turn.clone()));
+                } else {
+                    *message = "That placement would eliminate you, and you have a safer one".to_owned();
+                }
+            },
+
+            // Games that haven't started
+            (_, KeyCode::Char('s')) if self.state.is_none() => requests.push(Request::StartGame{ id }),
+            (_, KeyCode::Char(c @ '1'..='3')) if self.state.is_none() =>
+                requests.push(Request::AddBot{ id, level: BotLevel::ALL[c.to_digit(10).unwrap() as usize - 1] }),
+
+            _ => {}
+        }
+    }
+
+    fn handle_response(&mut self, response: Response, message: &mut String) {
+        let player = self.player();
+        match response {
+            Response::ChangedPlayers{ id, names } if id == self.id => self.players = names,
+
+            Response::StartedGame{ id, state } if id == self.id => {
+                self.state = Some(state);
+                self.turn = Turn::Wait;
+            }
+
+            Response::YourTurnPlaceToken{ id } if id == self.id => {
+                self.turn = Turn::PlaceToken{ ports: self.free_ports(), selected: 0 };
+                *message = "Your turn to place your token".to_owned();
+            }
+
+            Response::YourTurn{ id, safe_placements } if id == self.id => {
+                self.turn = Turn::PlaceTile{ safe_placements, tile: 0, rotation: 0, loc: 0 };
+                *message = "Your turn to place a tile".to_owned();
+            }
+
+            Response::AllPlacedTokens{ id } if id == self.id => *message = "Everyone placed their tokens".to_owned(),
+
+            Response::PlacedToken{ id, player: placer, port } if id == self.id => {
+                if let Some(state) = &mut self.state {
+                    state.place_player(placer, &port);
+                }
+                if Some(placer) == player {
+                    self.turn = Turn::Wait;
+                } else if let Turn::PlaceToken{ ports, selected } = &mut self.turn {
+                    // Someone else took a port this client could have picked
+                    ports.retain(|free| free != &port);
+                    *selected = (*selected).min(ports.len().saturating_sub(1));
+                }
+            }
+
+            Response::PlacedTile{ id, player: placer, kind, index, action, loc, tile, drawn_tiles } if id == self.id => {
+                if let Some(state) = &mut self.state {
+                    state.reveal_hand_tile(placer, &kind, index, &tile);
+                    let result = state.take_turn_placing_tile(&self.game, &kind, index, &action, &loc);
+                    for (player, index, tile) in drawn_tiles {
+                        state.reveal_hand_tile(player, &tile.kind(), index, &tile);
+                    }
+                    if result.dead_players().iter().any(|(dead, _)| Some(*dead) == player) {
+                        *message = "You were eliminated".to_owned();
+                    }
+                }
+                if Some(placer) == player {
+                    self.turn = Turn::Wait;
+                }
+            }
+
+            Response::Forfeited{ id, player: forfeiter, drawn_tiles } if id == self.id => {
+                if let Some(state) = &mut self.state {
+                    state.forfeit(&self.game, forfeiter);
+                    for (player, index, tile) in drawn_tiles {
+                        state.reveal_hand_tile(player, &tile.kind(), index, &tile);
+                    }
+                }
+                if Some(forfeiter) == player {
+                    self.turn = Turn::Wait;
+                }
+            }
+
+            Response::Clock{ id, remaining_ms, running } if id == self.id =>
+                self.clock = Some(ClockDisplay{ remaining_ms, running, received: Instant::now() }),
+
+            Response::Rejected{ id, reason } if id == self.id => {
+                if let Turn::Sent(turn) = &self.turn {
+                    self.turn = (**turn).clone();
+                }
+                *message = format!("The server rejected that: {:?}", reason);
+            }
+
+            _ => {}
+        }
+    }
+}
+
+/// What the terminal is showing
+#[derive(Clone, Debug)]
+pub enum Screen {
+    /// Typing a username
+    EnterUsername{ input: String },
+    /// Waiting for the server to accept the username
+    WaitSession,
+    /// Games to join, sorted by id
+    Lobby{ games: Vec<GameInstance>, selected: usize },
+    /// Waiting to join game `id`, with the lobby to go back to if that fails
+    WaitJoinGame{ id: GameId, games: Vec<GameInstance>, selected: usize },
+    Game(Box<GameView>),
+}
+
+/// The terminal client, which turns keys into requests and keeps track of what the server says
+#[derive(Clone, Debug)]
+pub struct App {
+    pub screen: Screen,
+    pub username: Option<String>,
+    /// A line of feedback, like why a move was rejected
+    pub message: String,
+    pub quit: bool,
+}
+
+/// Adds or replaces a game in a list of games sorted by id
+fn upsert_game(games: &mut Vec<GameInstance>, game: GameInstance) {
+    match games.binary_search_by_key(&game.id(), |game| game.id()) {
+        Ok(pos) => games[pos] = game,
+        Err(pos) => games.insert(pos, game),
+    }
+}
+
+impl App {
+    /// Construct an app, along with the requests to send first.
+    /// Asks for a username unless one is given.
+    pub fn new(username: Option<String>) -> (Self, Vec<Request>) {
+        let (screen, requests) = match username {
+            Some(username) => (Screen::WaitSession, vec![Request::SetUsername{ username }]),
+            None => (Screen::EnterUsername{ input: String::new() }, vec![]),
+        };
+        (Self { screen, username: None, message: String::new(), quit: false }, requests)
+    }
+
+    /// Handles a key press and returns the requests to send
+    pub fn handle_key(&mut self, key: KeyEvent) -> Vec<Request> {
+        let mut requests = vec![];
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return requests;
+        }
+
+        match &mut self.screen {
+            Screen::EnterUsername{ input } => match key.code {
+                KeyCode::Char(c) => input.push(c),
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Enter if !input.trim().is_empty() => {
+                    requests.push(Request::SetUsername{ username: input.trim().to_owned() });
+                    self.screen = Screen::WaitSession;
+                }
+                KeyCode::Esc => self.quit = true,
+                _ => {}
+            },
+
+            Screen::WaitSession | Screen::WaitJoinGame{ .. } => {}
+
+            Screen::Lobby{ games, selected } => match key.code {
+                KeyCode::Up | KeyCode::Char('k') => *selected = selected.saturating_sub(1),
+                KeyCode::Down | KeyCode::Char('j') => *selected = (*selected + 1).min(games.len().saturating_sub(1)),
+                KeyCode::Enter => if let Some(game) = games.get(*selected) {
+                    let id = game.id();
+                    requests.push(Request::JoinGame{ id });
+                    self.screen = Screen::WaitJoinGame{ id, games: std::mem::take(games), selected: *selected };
+                },
+                KeyCode::Char('c') => requests.push(Request::CreateGame{ settings: GameSettings::default() }),
+                KeyCode::Char('q') | KeyCode::Esc => self.quit = true,
+                _ => {}
+            },
+
+            Screen::Game(view) => view.handle_key(key, &mut self.message, &mut requests),
+        }
+        requests
+    }
+
+    /// Handles a response from the server and returns the requests to send
+    pub fn handle_response(&mut self, response: Response) -> Vec<Request> {
+        match (&mut self.screen, response) {
+            (_, Response::Session{ username, .. }) => {
+                self.username = Some(username);
+                self.message.clear();
+            }
+
+            (_, Response::RejectedUsername) => {
+                self.message = "That username is taken".to_owned();
+                self.screen = Screen::EnterUsername{ input: String::new() };
+            }
+
+            (_, Response::JoinedLobby{ games }) => {
+                let games = games.into_iter().sorted_by_key(|game| game.id()).collect();
+                self.screen = Screen::Lobby{ games, selected: 0 };
+                self.message.clear();
+            }
+
+            (_, Response::JoinedGame{ game }) => {
+                self.screen = Screen::Game(Box::new(GameView::new(game)));
+                self.message.clear();
+            }
+
+            (Screen::Lobby{ games, .. } | Screen::WaitJoinGame{ games, .. }, Response::ChangedGame{ game }) =>
+                upsert_game(games, game),
+
+            (Screen::Lobby{ games, selected } | Screen::WaitJoinGame{ games, selected, .. }, Response::RemovedGame{ id }) => {
+                games.retain(|game| game.id() != id);
+                *selected = (*selected).min(games.len().saturating_sub(1));
+            }
+
+            (Screen::Lobby{ .. }, Response::RejectedGameSettings{ error }) =>
+                self.message = format!("Could not create the game: {}", error),
+
+            (Screen::WaitJoinGame{ id, games, selected }, Response::Rejected{ id: rejected, reason }) if *id == rejected => {
+                self.message = format!("Could not join the game: {:?}", reason);
+                self.screen = Screen::Lobby{ games: std::mem::take(games), selected: *selected };
+            }
+
+            (Screen::Game(view), response) => view.handle_response(response, &mut self.message),
+
+            _ => {}
+        }
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common::game_state::BaseGameState;
+
+    use super::*;
+
+    fn key(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+
+    /// An app in a started game of two players, as player 0
+    fn started_game() -> (App, BaseGameState) {
+        let settings = GameSettings::default();
+        let game = settings.build().unwrap();
+        let state = game.new_state_seeded(2, 0);
+        let instance = GameInstance::new(GameId(3), game, settings, None, vec!["a".to_owned(), "b".to_owned()]);
+
+        let (mut app, requests) = App::new(Some("a".to_owned()));
+        assert!(matches!(requests.as_slice(), [Request::SetUsername{ .. }]));
+        app.handle_response(Response::JoinedGame{ game: instance });
+        app.handle_response(Response::StartedGame{ id: GameId(3), state: state.visible_state(Looker::Player(0)) });
+        (app, state)
+    }
+
+    #[test]
+    fn test_lobby() {
+        let (mut app, _) = App::new(None);
+        for c in "me".chars() {
+            app.handle_key(key(KeyCode::Char(c)));
+        }
+        assert!(matches!(app.handle_key(key(KeyCode::Enter)).as_slice(), [Request::SetUsername{ username }] if username == "me"));
+
+        let settings = GameSettings::default();
+        let game = |id| GameInstance::new(GameId(id), settings.build().unwrap(), settings.clone(), None, vec![]);
+        app.handle_response(Response::JoinedLobby{ games: vec![game(4), game(1)] });
+        app.handle_response(Response::ChangedGame{ game: game(2) });
+        app.handle_key(key(KeyCode::Down));
+        assert!(matches!(app.handle_key(key(KeyCode::Enter)).as_slice(), [Request::JoinGame{ id: GameId(2) }]));
+
+        // Rejected, so back to the lobby
+        app.handle_response(Response::Rejected{ id: GameId(2), reason: common::message::RejectReason::GameFull });
+        assert!(matches!(&app.screen, Screen::Lobby{ games, selected: 1 } if games.len() == 3));
+    }
+
+    #[test]
+    fn test_place_token_and_tile() {
+        let (mut app, mut state) = started_game();
+        let id = GameId(3);
+        app.handle_response(Response::YourTurnPlaceToken{ id });
+        app.handle_key(key(KeyCode::Right));
+        let port = match app.handle_key(key(KeyCode::Enter)).as_slice() {
+            [Request::PlaceToken{ port, .. }] => port.clone(),
+            _ => panic!("Expected to place a token"),
+        };
+        assert!(state.can_place_token(&GameSettings::default().build().unwrap(), 0, &port));
+        app.handle_response(Response::PlacedToken{ id, player: 0, port: port.clone() });
+
+        let Screen::Game(view) = &app.screen else { panic!("Expected to be in the game") };
+        let game = view.game.clone();
+        state.take_turn_placing_token(&game, 0, &port);
+        let other = game.start_ports().into_iter().find(|port| state.can_place_player(&game, port)).unwrap();
+        state.take_turn_placing_token(&game, 1, &other);
+        app.handle_response(Response::PlacedToken{ id, player: 1, port: other });
+
+        app.handle_response(Response::YourTurn{ id, safe_placements: None });
+        app.handle_key(key(KeyCode::Char('2')));
+        app.handle_key(key(KeyCode::Char('r')));
+        match app.handle_key(key(KeyCode::Enter)).as_slice() {
+            [Request::PlaceTile{ kind, index, action, loc, .. }] => {
+                assert_eq!(*index, 1);
+                assert!(state.can_place_tile(&game, 0, kind, *index, action, loc));
+            }
+            _ => panic!("Expected to place a tile"),
+        }
+
+        // Rejected moves can be made again
+        app.handle_response(Response::Rejected{ id, reason: common::message::RejectReason::IllegalMove });
+        let Screen::Game(view) = &app.screen else { panic!("Expected to be in the game") };
+        assert!(matches!(view.turn, Turn::PlaceTile{ tile: 1, rotation: 1, .. }));
+    }
+}
@@ -0,0 +1,83 @@
+use std::io;
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use common::message::{Request, Response};
+use tungstenite::{Message, WebSocket};
+
+/// How long the connection thread waits for a message before checking for requests to send
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// The connection to the server closed
+#[derive(Clone, Copy, Debug)]
+pub struct Disconnected;
+
+/// A websocket connection to the server. Messages are sent and received on a thread of its own,
+/// so the terminal never waits on the network.
+pub struct Connection {
+    requests: Sender<Request>,
+    responses: Receiver<Response>,
+}
+
+impl Connection {
+    /// Connects to a server at `address`, like `127.0.0.1:7878`
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let stream = TcpStream::connect(address).map_err(|err| err.to_string())?;
+        let (socket, _) = tungstenite::client(format!("ws://{}", address), stream).map_err(|err| err.to_string())?;
+        socket.get_ref().set_read_timeout(Some(POLL_INTERVAL)).map_err(|err| err.to_string())?;
+
+        let (request_tx, request_rx) = mpsc::channel();
+        let (response_tx, response_rx) = mpsc::channel();
+        thread::spawn(move || run(socket, request_rx, response_tx));
+        Ok(Self { requests: request_tx, responses: response_rx })
+    }
+
+    pub fn send(&self, request: Request) {
+        // If the connection thread stopped, `try_recv` reports it
+        self.requests.send(request).ok();
+    }
+
+    /// The next response received, if any. Err once the connection is closed.
+    pub fn try_recv(&self) -> Result<Option<Response>, Disconnected> {
+        match self.responses.try_recv() {
+            Ok(response) => Ok(Some(response)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(Disconnected),
+        }
+    }
+}
+
+/// Sends requests and receives responses until the socket closes or the terminal hangs up
+fn run(mut socket: WebSocket<TcpStream>, requests: Receiver<Request>, responses: Sender<Response>) {
+    loop {
+        loop {
+            match requests.try_recv() {
+                Ok(request) => {
+                    let bytes = bincode::serialize(&request).expect("Requests should serialize");
+                    if socket.write_message(Message::Binary(bytes)).is_err() {
+                        return;
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    socket.close(None).ok();
+                    return;
+                }
+            }
+        }
+
+        match socket.read_message() {
+            Ok(Message::Binary(bytes)) => match bincode::deserialize::<Response>(&bytes) {
+                Ok(response) => if responses.send(response).is_err() {
+                    return;
+                },
+                Err(_) => return,
+            },
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(err)) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+            Err(_) => return,
+        }
+    }
+}
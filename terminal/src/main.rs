@@ -0,0 +1,139 @@
+pub mod app;
+pub mod connection;
+pub mod render;
+
+use std::io::{self, Write};
+use std::process;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event};
+use crossterm::terminal::{self, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{cursor, execute};
+
+use crate::app::App;
+use crate::connection::Connection;
+
+const USAGE: &str = "\
+Plays Tsurust in the terminal.
+
+Usage: terminal [options]
+
+Options:
+    -a, --address <host:port>    server to connect to (default 127.0.0.1:7878)
+    -n, --name <username>        username to join with, instead of typing one in
+    -h, --help                   prints this message and exits";
+
+/// How long to wait for a key before checking for responses
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How often to redraw when nothing happens, so clocks tick
+const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// What to do, from the command line
+struct Args {
+    address: String,
+    username: Option<String>,
+}
+
+fn exit_with_error(message: impl AsRef<str>) -> ! {
+    eprintln!("error: {}\n\n{}", message.as_ref(), USAGE);
+    process::exit(2);
+}
+
+fn parse_args() -> Args {
+    let mut args = Args { address: common::HOST_ADDRESS.to_owned(), username: None };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| exit_with_error(format!("missing value for {}", arg)));
+        match arg.as_str() {
+            "-a" | "--address" => args.address = value(),
+            "-n" | "--name" => args.username = Some(value()),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ => exit_with_error(format!("unknown option {}", arg)),
+        }
+    }
+    args
+}
+
+/// Puts the terminal back the way it was
+fn restore_terminal() {
+    execute!(io::stdout(), LeaveAlternateScreen, cursor::Show).ok();
+    terminal::disable_raw_mode().ok();
+}
+
+/// Runs the app until the player quits. Returns why it stopped, if it wasn't the player.
+fn run(connection: &Connection, app: &mut App, out: &mut impl Write) -> io::Result<Option<String>> {
+    let mut dirty = true;
+    let mut last_draw = Instant::now();
+    loop {
+        if event::poll(POLL_INTERVAL)? {
+            match event::read()? {
+                Event::Key(key) => {
+                    app.handle_key(key).into_iter().for_each(|request| connection.send(request));
+                    dirty = true;
+                }
+                Event::Resize(..) => dirty = true,
+                _ => {}
+            }
+        }
+        if app.quit {
+            return Ok(None);
+        }
+
+        loop {
+            match connection.try_recv() {
+                Ok(Some(response)) => {
+                    app.handle_response(response).into_iter().for_each(|request| connection.send(request));
+                    dirty = true;
+                }
+                Ok(None) => break,
+                Err(_) => return Ok(Some("Lost the connection to the server".to_owned())),
+            }
+        }
+
+        if dirty || last_draw.elapsed() >= REDRAW_INTERVAL {
+            let (width, height) = terminal::size()?;
+            render::render(app, width, height).draw(out)?;
+            dirty = false;
+            last_draw = Instant::now();
+        }
+    }
+}
+
+fn main() {
+    let args = parse_args();
+    let connection = Connection::connect(&args.address)
+        .unwrap_or_else(|err| {
+            eprintln!("error: can't connect to {}: {}", args.address, err);
+            process::exit(1);
+        });
+    let (mut app, requests) = App::new(args.username);
+    requests.into_iter().for_each(|request| connection.send(request));
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+
+    let mut out = io::stdout();
+    let result = terminal::enable_raw_mode()
+        .and_then(|_| execute!(out, EnterAlternateScreen, cursor::Hide))
+        .and_then(|_| run(&connection, &mut app, &mut out));
+    restore_terminal();
+
+    match result {
+        Ok(None) => {}
+        Ok(Some(reason)) => {
+            eprintln!("{}", reason);
+            process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1);
+        }
+    }
+}
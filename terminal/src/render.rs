@@ -0,0 +1,487 @@
+use std::io::{self, Write};
+
+use common::board::{BaseBoard, BasePort, BaseTLoc, Board, RectangleBoard};
+use common::game_state::BaseGameState;
+use common::math::{Pt2u, Vec2u};
+use common::tile::{BaseTile, RegularTile, Tile};
+use crossterm::cursor::MoveTo;
+use crossterm::queue;
+use crossterm::style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor};
+use itertools::iproduct;
+
+use crate::app::{App, GameView, Screen, Turn};
+
+/// Colors of the players' tokens, repeating after 8 players
+const PLAYER_COLORS: [Color; 8] = [
+    Color::Red, Color::Blue, Color::Green, Color::Yellow,
+    Color::Magenta, Color::Cyan, Color::DarkYellow, Color::White,
+];
+
+/// Background of the tile about to be placed, or red if placing it is a bad idea
+const PREVIEW_COLOR: Color = Color::DarkGrey;
+const ELIMINATING_PREVIEW_COLOR: Color = Color::DarkRed;
+const SELECTED_COLOR: Color = Color::DarkBlue;
+
+pub fn player_color(player: u32) -> Color {
+    PLAYER_COLORS[player as usize % PLAYER_COLORS.len()]
+}
+
+/// A character cell of the terminal
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self { ch: ' ', fg: Color::Reset, bg: Color::Reset }
+    }
+}
+
+/// A grid of cells to draw on before writing everything to the terminal at once.
+/// Drawing outside the canvas does nothing, so small terminals just cut things off.
+#[derive(Clone, Debug)]
+pub struct Canvas {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+}
+
+impl Canvas {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self { width, height, cells: vec![Cell::default(); width as usize * height as usize] }
+    }
+
+    fn cell_mut(&mut self, x: i32, y: i32) -> Option<&mut Cell> {
+        (x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32)
+            .then(|| &mut self.cells[y as usize * self.width as usize + x as usize])
+    }
+
+    /// The cell at (x, y). None if it's outside the canvas.
+    pub fn cell(&self, x: i32, y: i32) -> Option<Cell> {
+        (x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32)
+            .then(|| self.cells[y as usize * self.width as usize + x as usize])
+    }
+
+    /// Puts a character at (x, y), keeping the background
+    pub fn put(&mut self, x: i32, y: i32, ch: char, fg: Color) {
+        if let Some(cell) = self.cell_mut(x, y) {
+            cell.ch = ch;
+            cell.fg = fg;
+        }
+    }
+
+    /// Writes text starting at (x, y)
+    pub fn text(&mut self, x: i32, y: i32, text: &str, fg: Color) {
+        for (i, ch) in text.chars().enumerate() {
+            self.put(x + i as i32, y, ch, fg);
+        }
+    }
+
+    /// Sets the background of a rectangle of cells
+    pub fn highlight(&mut self, x: i32, y: i32, width: i32, height: i32, bg: Color) {
+        for (y, x) in iproduct!(y..y + height, x..x + width) {
+            if let Some(cell) = self.cell_mut(x, y) {
+                cell.bg = bg;
+            }
+        }
+    }
+
+    /// Writes the canvas to the terminal, changing colors only when needed
+    pub fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut colors = None;
+        for y in 0..self.height {
+            queue!(out, MoveTo(0, y))?;
+            for cell in &self.cells[y as usize * self.width as usize..][..self.width as usize] {
+                if colors != Some((cell.fg, cell.bg)) {
+                    queue!(out, SetForegroundColor(cell.fg), SetBackgroundColor(cell.bg))?;
+                    colors = Some((cell.fg, cell.bg));
+                }
+                queue!(out, Print(cell.ch))?;
+            }
+        }
+        queue!(out, ResetColor)?;
+        out.flush()
+    }
+}
+
+/// Where the parts of a rectangle board go on the canvas.
+/// Tiles are twice as wide as they are tall so they look square, and every port
+/// gets its own row or column so the markers next to ports don't overlap.
+#[derive(Clone, Debug)]
+pub struct BoardLayout {
+    board: RectangleBoard,
+    /// Top left corner of the board
+    x: i32,
+    y: i32,
+    /// Rows per port along a vertical edge
+    scale: i32,
+}
+
+impl BoardLayout {
+    pub fn new(board: RectangleBoard, x: i32, y: i32) -> Self {
+        // With 1 port per edge, the markers of the top and bottom ports would share a row
+        let scale = if board.ports_per_edge() == 1 { 2 } else { 1 };
+        Self { board, x, y, scale }
+    }
+
+    /// Height of a tile in cells, not counting the bottom edge
+    pub fn tile_rows(&self) -> i32 {
+        self.scale * (self.board.ports_per_edge() as i32 + 1)
+    }
+
+    /// Width of a tile in cells, not counting the right edge
+    pub fn tile_cols(&self) -> i32 {
+        2 * self.tile_rows()
+    }
+
+    /// Size of the whole board in cells
+    pub fn size(&self) -> (i32, i32) {
+        (self.board.width() as i32 * self.tile_cols() + 1, self.board.height() as i32 * self.tile_rows() + 1)
+    }
+
+    /// Top left corner of a tile location, on the grid lines
+    pub fn loc_position(&self, loc: &Pt2u) -> (i32, i32) {
+        (self.x + loc.x as i32 * self.tile_cols(), self.y + loc.y as i32 * self.tile_rows())
+    }
+
+    /// Where a port is, on the grid lines
+    pub fn port_position(&self, (pt, offset): &(Pt2u, Vec2u)) -> (i32, i32) {
+        let (x, y) = self.loc_position(pt);
+        (x + 2 * self.scale * offset.x as i32, y + self.scale * offset.y as i32)
+    }
+
+    /// Where the marker of a port goes on the tile at `loc`, one cell inside the tile
+    pub fn marker_position(&self, loc: &Pt2u, port: &(Pt2u, Vec2u)) -> (i32, i32) {
+        let (x, y) = self.port_position(port);
+        match (port.1.y == 0, port.0 == *loc) {
+            (true, true) => (x, y + 1),
+            (true, false) => (x, y - 1),
+            (false, true) => (x + 1, y),
+            (false, false) => (x - 1, y),
+        }
+    }
+
+    /// Draws the grid lines
+    pub fn draw_grid(&self, canvas: &mut Canvas) {
+        let (width, height) = (self.board.width() as i32, self.board.height() as i32);
+        let (cols, rows) = (self.tile_cols(), self.tile_rows());
+        for (gy, gx) in iproduct!(0..=height, 0..=width) {
+            let (x, y) = (self.x + gx * cols, self.y + gy * rows);
+            canvas.put(x, y, grid_junction(gy > 0, gy < height, gx > 0, gx < width), Color::Grey);
+            if gx < width {
+                for dx in 1..cols {
+                    canvas.put(x + dx, y, '─', Color::Grey);
+                }
+            }
+            if gy < height {
+                for dy in 1..rows {
+                    canvas.put(x, y + dy, '│', Color::Grey);
+                }
+            }
+        }
+    }
+
+    /// Draws a tile at `loc` as letters next to its ports, with connected ports sharing a letter
+    pub fn draw_tile(&self, canvas: &mut Canvas, loc: &Pt2u, tile: &RegularTile<4>, fg: Color) {
+        let ports = self.board.loc_ports(loc);
+        let mut letters = vec![None; ports.len()];
+        let mut next_letter = 'a';
+        for (i, port) in ports.iter().enumerate() {
+            let letter = *letters[i].get_or_insert_with(|| {
+                let letter = next_letter;
+                next_letter = char::from_u32(next_letter as u32 + 1).unwrap_or('?');
+                letter
+            });
+            letters[tile.output(i as u32) as usize] = Some(letter);
+            let (x, y) = self.marker_position(loc, port);
+            canvas.put(x, y, letter, fg);
+        }
+    }
+
+    /// Highlights the inside of a tile location
+    pub fn highlight_loc(&self, canvas: &mut Canvas, loc: &Pt2u, bg: Color) {
+        let (x, y) = self.loc_position(loc);
+        canvas.highlight(x + 1, y + 1, self.tile_cols() - 1, self.tile_rows() - 1, bg);
+    }
+}
+
+/// The box drawing character where grid lines meet, given which directions they go in
+fn grid_junction(up: bool, down: bool, left: bool, right: bool) -> char {
+    match (up, down, left, right) {
+        (false, true, false, true) => '┌',
+        (false, true, true, false) => '┐',
+        (true, false, false, true) => '└',
+        (true, false, true, false) => '┘',
+        (false, true, true, true) => '┬',
+        (true, false, true, true) => '┴',
+        (true, true, false, true) => '├',
+        (true, true, true, false) => '┤',
+        _ => '┼',
+    }
+}
+
+fn rectangle_loc(loc: &BaseTLoc) -> Option<&Pt2u> {
+    match loc {
+        BaseTLoc::Pt2u(loc) => Some(loc),
+        _ => None,
+    }
+}
+
+fn rectangle_port(port: &BasePort) -> Option<&(Pt2u, Vec2u)> {
+    match port {
+        BasePort::Pt2uVec2u(port) => Some(port),
+        _ => None,
+    }
+}
+
+fn square_tile(tile: &BaseTile) -> Option<&RegularTile<4>> {
+    match tile {
+        BaseTile::RegularTile4(tile) => Some(tile),
+        _ => None,
+    }
+}
+
+/// Formats milliseconds as minutes and seconds
+fn format_clock(ms: u64) -> String {
+    let seconds = ms.div_ceil(1000);
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// Draws the whole screen
+pub fn render(app: &App, width: u16, height: u16) -> Canvas {
+    let mut canvas = Canvas::new(width, height);
+    let bottom = height as i32 - 1;
+
+    let help = match &app.screen {
+        Screen::EnterUsername{ input } => {
+            canvas.text(2, 1, "Tsurust", Color::Yellow);
+            canvas.text(2, 3, &format!("Username: {}_", input), Color::Reset);
+            "Enter: connect   Esc: quit"
+        }
+        Screen::WaitSession => {
+            canvas.text(2, 1, "Connecting...", Color::Reset);
+            "Ctrl-C: quit"
+        }
+        Screen::Lobby{ games, selected } | Screen::WaitJoinGame{ games, selected, .. } => {
+            render_lobby(&mut canvas, app, games, *selected);
+            if matches!(app.screen, Screen::WaitJoinGame{ .. }) {
+                "Joining..."
+            } else {
+                "↑/↓: select   Enter: join   c: create game   q: quit"
+            }
+        }
+        Screen::Game(view) => render_game(&mut canvas, view),
+    };
+
+    canvas.text(0, bottom - 1, &app.message, Color::Yellow);
+    canvas.text(0, bottom, help, Color::DarkGrey);
+    canvas
+}
+
+fn render_lobby(canvas: &mut Canvas, app: &App, games: &[common::GameInstance], selected: usize) {
+    let title = match &app.username {
+        Some(username) => format!("Lobby - signed in as {}", username),
+        None => "Lobby".to_owned(),
+    };
+    canvas.text(2, 1, &title, Color::Yellow);
+    if games.is_empty() {
+        canvas.text(4, 3, "No games yet. Press c to create one.", Color::DarkGrey);
+    }
+    for (i, game) in games.iter().enumerate() {
+        let y = 3 + i as i32;
+        let status = if game.state().is_some() { "started" } else { "waiting" };
+        let line = format!("Game {:<4} {:>2}/{:<2} players  {:<8} {}",
+            game.id().0, game.players().len(), game.settings().max_players, status, game.players().join(", "));
+        canvas.text(4, y, &line, Color::Reset);
+        if i == selected {
+            canvas.put(2, y, '>', Color::Yellow);
+            canvas.highlight(4, y, line.chars().count() as i32, 1, SELECTED_COLOR);
+        }
+    }
+}
+
+fn render_game(canvas: &mut Canvas, view: &GameView) -> &'static str {
+    let board = match view.game.board() {
+        BaseBoard::RectangleBoard(board) => board,
+        _ => {
+            canvas.text(2, 1, &format!("Game {}", view.id.0), Color::Yellow);
+            canvas.text(2, 3, "This board's shape can't be shown in the terminal.", Color::Reset);
+            return "F: forfeit   Esc: leave";
+        }
+    };
+
+    let layout = BoardLayout::new(board, 1, 1);
+    layout.draw_grid(canvas);
+    let sidebar_x = layout.size().0 + 4;
+
+    let state = match &view.state {
+        Some(state) => state,
+        None => {
+            render_players(canvas, view, None, sidebar_x);
+            canvas.text(sidebar_x, 3 + view.players.len() as i32, "Waiting for the game to start", Color::DarkGrey);
+            return "s: start   1/2/3: add easy/medium/hard bot   Esc: leave";
+        }
+    };
+
+    let board_state = state.board_state();
+    for (loc, tile) in board_state.tiles_vec() {
+        if let (Some(loc), Some(tile)) = (rectangle_loc(&loc), square_tile(&tile)) {
+            layout.draw_tile(canvas, loc, tile, Color::Reset);
+        }
+    }
+
+    match &view.turn {
+        Turn::PlaceToken{ ports, selected } => for (i, port) in ports.iter().enumerate() {
+            if let Some(port) = rectangle_port(port) {
+                let (x, y) = layout.port_position(port);
+                if i == *selected {
+                    canvas.put(x, y, '●', Color::Yellow);
+                    canvas.highlight(x, y, 1, 1, SELECTED_COLOR);
+                } else {
+                    canvas.put(x, y, '○', Color::DarkGrey);
+                }
+            }
+        },
+        Turn::PlaceTile{ .. } => if let Some((kind, index, action, loc)) = view.chosen_placement() {
+            let tile = state.hand_tile(state.player_expect(), &kind, index).map(|tile| tile.apply_action(&action));
+            if let (Some(loc), Some(tile)) = (rectangle_loc(&loc), tile.as_ref().and_then(square_tile)) {
+                let bg = if view.chosen_placement_eliminates() { ELIMINATING_PREVIEW_COLOR } else { PREVIEW_COLOR };
+                layout.highlight_loc(canvas, loc, bg);
+                layout.draw_tile(canvas, loc, tile, Color::White);
+            }
+        },
+        _ => {}
+    }
+
+    for player in 0..state.num_players() {
+        if let Some(port) = board_state.player_port(player) {
+            if let Some(port) = rectangle_port(&port) {
+                let (x, y) = layout.port_position(port);
+                let color = if state.player_state(player).is_some() { player_color(player) } else { Color::DarkGrey };
+                canvas.put(x, y, char::from_digit((player + 1) % 10, 10).unwrap(), color);
+            }
+        }
+    }
+
+    let y = render_players(canvas, view, Some(state), sidebar_x);
+    render_hand(canvas, view, state, sidebar_x, y + 1);
+
+    match &view.turn {
+        Turn::PlaceToken{ .. } => "←/→: choose port   Enter: place token   F: forfeit   Esc: leave",
+        Turn::PlaceTile{ .. } => "1-9/Tab: tile   r/R: rotate   ←/→: location   Enter: place   F: forfeit   Esc: leave",
+        Turn::Sent(_) => "Waiting for the server...",
+        Turn::Wait => "F: forfeit   Esc: leave",
+    }
+}
+
+/// Lists the players next to the board. Returns the row after the list.
+fn render_players(canvas: &mut Canvas, view: &GameView, state: Option<&BaseGameState>, x: i32) -> i32 {
+    canvas.text(x, 1, &format!("Game {}", view.id.0), Color::Yellow);
+    let winners = state.filter(|state| state.game_over()).map(|state| state.winners());
+    let turn_player = state.filter(|state| state.all_players_placed() && !state.game_over())
+        .map(|state| state.turn_player());
+
+    for (i, name) in view.players.iter().enumerate() {
+        let player = i as u32;
+        let y = 3 + i as i32;
+        let alive = state.is_none_or(|state| state.player_state(player).is_some());
+        let color = if alive { player_color(player) } else { Color::DarkGrey };
+        canvas.text(x, y, &format!("{} {}", player + 1, name), color);
+
+        let mut notes = vec![];
+        if view.player() == Some(player) {
+            notes.push("you".to_owned());
+        }
+        if let Some(clock) = &view.clock {
+            notes.push(format_clock(clock.remaining_ms(player)));
+        }
+        if winners.as_ref().is_some_and(|winners| winners.contains(&player)) {
+            notes.push("won!".to_owned());
+        } else if !alive {
+            notes.push("out".to_owned());
+        } else if turn_player == Some(player) {
+            notes.push("<- turn".to_owned());
+        }
+        canvas.text(x + 16, y, &notes.join("  "), Color::Reset);
+    }
+    3 + view.players.len() as i32
+}
+
+/// Draws the tiles in this client's hand, with the selected one rotated as it would be placed
+fn render_hand(canvas: &mut Canvas, view: &GameView, state: &BaseGameState, x: i32, y: i32) {
+    let hand = view.hand();
+    if view.player().is_none() || state.player_state(view.player().unwrap()).is_none() {
+        return;
+    }
+    canvas.text(x, y, "Your tiles", Color::Reset);
+
+    let ports_per_edge = hand.first().and_then(|(_, _, tile)| square_tile(tile)).map_or(1, |tile| tile.ports_per_edge());
+    let mut layout = BoardLayout::new(RectangleBoard::new(1, 1, ports_per_edge), x, y + 1);
+    for (i, (_, _, tile)) in hand.iter().enumerate() {
+        let (selected, rotation) = match view.turn {
+            Turn::PlaceTile{ tile, rotation, .. } => (tile == i, if tile == i { rotation } else { 0 }),
+            _ => (false, 0),
+        };
+        let tile = tile.rotate(rotation);
+        if let Some(tile) = square_tile(&tile) {
+            layout.draw_grid(canvas);
+            if selected {
+                layout.highlight_loc(canvas, &Pt2u::origin(), PREVIEW_COLOR);
+            }
+            layout.draw_tile(canvas, &Pt2u::origin(), tile, Color::Reset);
+            let (width, height) = layout.size();
+            canvas.text(layout.x + width / 2, layout.y + height, &(i + 1).to_string(), Color::Yellow);
+            layout.x += width + 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+
+    fn row(canvas: &Canvas, y: i32) -> String {
+        (0..canvas.width as i32).map(|x| canvas.cell(x, y).unwrap().ch).collect()
+    }
+
+    #[test]
+    fn test_board_layout() {
+        let layout = BoardLayout::new(RectangleBoard::new(3, 2, 2), 1, 1);
+        assert_eq!((layout.tile_cols(), layout.tile_rows()), (6, 3));
+        assert_eq!(layout.size(), (19, 7));
+        assert_eq!(layout.port_position(&(Pt2u::new(1, 0), Vec2u::new(2, 0))), (11, 1));
+        assert_eq!(layout.port_position(&(Pt2u::new(1, 1), Vec2u::new(0, 1))), (7, 5));
+
+        // Markers go inside the tile, whichever side of it the port is on
+        let loc = Pt2u::new(1, 0);
+        assert_eq!(layout.marker_position(&loc, &(Pt2u::new(1, 0), Vec2u::new(2, 0))), (11, 2));
+        assert_eq!(layout.marker_position(&loc, &(Pt2u::new(1, 1), Vec2u::new(2, 0))), (11, 3));
+        assert_eq!(layout.marker_position(&loc, &(Pt2u::new(2, 0), Vec2u::new(0, 1))), (12, 2));
+
+        // Markers of opposite ports don't share a cell
+        let layout = BoardLayout::new(RectangleBoard::new(1, 1, 1), 0, 0);
+        let loc = Pt2u::new(0, 0);
+        let ports = RectangleBoard::new(1, 1, 1).loc_ports(&loc);
+        let markers = ports.iter().map(|port| layout.marker_position(&loc, port)).collect_vec();
+        assert!(markers.iter().all_unique());
+    }
+
+    #[test]
+    fn test_draw_tile() {
+        let layout = BoardLayout::new(RectangleBoard::new(1, 1, 1), 0, 0);
+        let mut canvas = Canvas::new(9, 5);
+        layout.draw_grid(&mut canvas);
+        // Top to right, bottom to left
+        layout.draw_tile(&mut canvas, &Pt2u::origin(), &RegularTile::<4>::new(vec![1, 0, 3, 2]), Color::Reset);
+        assert_eq!(row(&canvas, 0), "┌───────┐");
+        assert_eq!(row(&canvas, 1), "│   a   │");
+        assert_eq!(row(&canvas, 2), "│b     a│");
+        assert_eq!(row(&canvas, 3), "│   b   │");
+        assert_eq!(row(&canvas, 4), "└───────┘");
+    }
+}
@@ -0,0 +1,164 @@
+pub mod tournament;
+
+use std::{fs, path::PathBuf, process};
+
+use common::game_settings::GameSettings;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::tournament::{standings, Contestant, GameResult, Standing, Tournament};
+
+const USAGE: &str = "\
+Plays games between strategies and reports how often each one wins.
+
+Usage: tournament [options] <strategy> <strategy>...
+
+Strategies:
+    easy, medium, hard       bots like the ones in the lobby
+    analysis[:<samples>]     places the tile Monte Carlo analysis rates best
+
+Options:
+    -n, --games <n>          number of games to play (default 1000)
+    -s, --seed <n>           seed of the first game, with later games using the next seeds (default random)
+    -c, --settings <file>    game settings as JSON, like the ones --print-settings prints (default the lobby's defaults)
+    -o, --out <file>         writes the result of every game, as JSON if the file name ends in .json and CSV otherwise
+    -j, --threads <n>        number of threads to play games on (default the number of CPUs)
+        --print-settings     prints the default game settings as JSON, for editing, and exits
+    -h, --help               prints this message and exits";
+
+const DEFAULT_NUM_GAMES: u32 = 1000;
+
+/// What to do, from the command line
+struct Args {
+    num_games: u32,
+    seed: u64,
+    settings: GameSettings,
+    out: Option<PathBuf>,
+    num_threads: u32,
+    contestants: Vec<Contestant>,
+}
+
+/// Everything written to a JSON results file
+#[derive(Serialize)]
+struct Results<'a> {
+    settings: &'a GameSettings,
+    seed: u64,
+    standings: &'a [Standing],
+    games: &'a [GameResult],
+}
+
+fn exit_with_error(message: impl AsRef<str>) -> ! {
+    eprintln!("error: {}\n\n{}", message.as_ref(), USAGE);
+    process::exit(2);
+}
+
+fn parse_args() -> Args {
+    let mut args = Args {
+        num_games: DEFAULT_NUM_GAMES,
+        seed: common::random_seed(),
+        settings: GameSettings::default(),
+        out: None,
+        num_threads: std::thread::available_parallelism().map_or(1, |n| n.get() as u32),
+        contestants: vec![],
+    };
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || iter.next().unwrap_or_else(|| exit_with_error(format!("missing value for {}", arg)));
+        match arg.as_str() {
+            "-n" | "--games" => args.num_games = value().parse()
+                .unwrap_or_else(|_| exit_with_error("invalid number of games")),
+            "-s" | "--seed" => args.seed = value().parse()
+                .unwrap_or_else(|_| exit_with_error("invalid seed")),
+            "-c" | "--settings" => {
+                let path = value();
+                let json = fs::read_to_string(&path)
+                    .unwrap_or_else(|err| exit_with_error(format!("can't read {}: {}", path, err)));
+                args.settings = serde_json::from_str(&json)
+                    .unwrap_or_else(|err| exit_with_error(format!("invalid settings in {}: {}", path, err)));
+            }
+            "-o" | "--out" => args.out = Some(value().into()),
+            "-j" | "--threads" => args.num_threads = value().parse()
+                .unwrap_or_else(|_| exit_with_error("invalid number of threads")),
+            "--print-settings" => {
+                println!("{}", serde_json::to_string_pretty(&GameSettings::default()).unwrap());
+                process::exit(0);
+            }
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with('-') => exit_with_error(format!("unknown option {}", arg)),
+            _ => args.contestants.push(arg.parse().unwrap_or_else(|err| exit_with_error(err))),
+        }
+    }
+    args
+}
+
+fn format_percent(rate: f64) -> String {
+    format!("{:.1}%", rate * 100.0)
+}
+
+fn print_standings(standings: &[Standing], results: &[GameResult]) {
+    let width = standings.iter().map(|standing| standing.contestant().len()).max().unwrap_or(0).max(10);
+    println!("{:<width$}  {:>6}  {:>8}  {:>8}  {:>17}", "Contestant", "Games", "Wins", "Win rate", "95% CI");
+    for standing in standings {
+        let (low, high) = standing.confidence_interval();
+        println!("{:<width$}  {:>6}  {:>8.1}  {:>8}  {:>17}",
+            standing.contestant(),
+            standing.num_games(),
+            standing.wins(),
+            format_percent(standing.win_rate()),
+            format!("[{}, {}]", format_percent(low), format_percent(high)));
+    }
+    let num_turns = results.iter().map(|result| result.num_turns() as f64).sum::<f64>();
+    println!("\nAverage game length: {:.1} tiles", num_turns / results.len().max(1) as f64);
+}
+
+/// One row per game, with the seating and each contestant's share of the win
+fn results_csv(standings: &[Standing], results: &[GameResult]) -> String {
+    let header = ["game", "seed", "tiles placed", "seating"].into_iter()
+        .map(str::to_owned)
+        .chain(standings.iter().map(|standing| standing.contestant().clone()))
+        .join(",");
+    let rows = results.iter().map(|result| [
+        result.index().to_string(),
+        result.seed().to_string(),
+        result.num_turns().to_string(),
+        result.seats().iter().map(|contestant| contestant + 1).join(" "),
+    ].into_iter()
+        .chain(result.win_shares().iter().map(|share| share.to_string()))
+        .join(","));
+    std::iter::once(header).chain(rows).map(|line| line + "\n").collect()
+}
+
+fn main() {
+    let args = parse_args();
+    if args.contestants.is_empty() {
+        exit_with_error("no strategies given");
+    }
+
+    let tournament = Tournament::new(args.settings, args.contestants, args.seed)
+        .unwrap_or_else(|err| exit_with_error(err.to_string()));
+    eprintln!("Playing {} games from seed {}", args.num_games, tournament.seed());
+    let results = tournament.run(args.num_games, args.num_threads);
+    let standings = standings(tournament.contestants(), &results);
+    print_standings(&standings, &results);
+
+    if let Some(path) = args.out {
+        let contents = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::to_string_pretty(&Results {
+                settings: tournament.settings(),
+                seed: tournament.seed(),
+                standings: &standings,
+                games: &results,
+            }).unwrap()
+        } else {
+            results_csv(&standings, &results)
+        };
+        if let Err(err) = fs::write(&path, contents) {
+            eprintln!("error: can't write {}: {}", path.display(), err);
+            process::exit(1);
+        }
+    }
+}
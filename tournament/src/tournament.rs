@@ -0,0 +1,247 @@
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use common::analysis::Analysis;
+use common::bot::{Bot, BotLevel};
+use common::game::BaseGame;
+use common::game_settings::{GameSettings, GameSettingsError};
+use common::pcg64_seeded;
+use common::strategy::{self, Strategy};
+use getset::{CopyGetters, Getters};
+use itertools::Itertools;
+use rand::Rng;
+use serde::Serialize;
+
+/// Something that plays in the tournament
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Contestant {
+    /// A bot of some level, like the ones in the lobby
+    Bot(BotLevel),
+    /// Places the tile that Monte Carlo analysis with `num_samples` samples rates best
+    Analysis{ num_samples: u32 },
+}
+
+impl Contestant {
+    /// A fresh strategy for one seat of one game, with its randomness seeded by `seed`
+    pub fn strategy(&self, seed: u64) -> Box<dyn Strategy + Send> {
+        match self {
+            Self::Bot(level) => Box::new(Bot::new(*level, pcg64_seeded(seed))),
+            Self::Analysis{ num_samples } => Box::new(Analysis::new(seed).with_num_samples(*num_samples)),
+        }
+    }
+}
+
+impl Display for Contestant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Bot(level) => write!(f, "{}", level.to_string().to_lowercase()),
+            Self::Analysis{ num_samples } => write!(f, "analysis:{}", num_samples),
+        }
+    }
+}
+
+impl FromStr for Contestant {
+    type Err = String;
+
+    /// Parses `easy`, `medium`, `hard`, `analysis`, or `analysis:<samples>`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(level) = BotLevel::ALL.into_iter().find(|level| level.to_string().eq_ignore_ascii_case(s)) {
+            return Ok(Self::Bot(level));
+        }
+        match s.split_once(':') {
+            None if s == "analysis" => Ok(Self::Analysis{ num_samples: Analysis::DEFAULT_NUM_SAMPLES }),
+            Some(("analysis", samples)) => samples.parse()
+                .map(|num_samples| Self::Analysis{ num_samples })
+                .map_err(|_| format!("invalid number of samples in {}", s)),
+            _ => Err(format!("unknown strategy {}", s)),
+        }
+    }
+}
+
+/// Why a tournament can't be held
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TournamentError {
+    InvalidSettings(GameSettingsError),
+    /// The settings don't allow that many players
+    WrongNumPlayers{ num: u32, min: u32, max: u32 },
+}
+
+impl Display for TournamentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidSettings(err) => write!(f, "invalid game settings: {}", err),
+            Self::WrongNumPlayers{ num, min, max } =>
+                write!(f, "{} contestants were given, but games take {} to {} players", num, min, max),
+        }
+    }
+}
+
+/// How one game of a tournament went
+#[derive(Clone, Debug, Getters, CopyGetters, Serialize)]
+pub struct GameResult {
+    #[getset(get_copy = "pub")]
+    index: u32,
+    /// The seed the draw pile was shuffled with
+    #[getset(get_copy = "pub")]
+    seed: u64,
+    /// The contestant in each seat, as indices into the contestant list
+    #[getset(get = "pub")]
+    seats: Vec<usize>,
+    /// Each contestant's share of the win. Players who win together split the win.
+    #[getset(get = "pub")]
+    win_shares: Vec<f64>,
+    /// Number of tiles placed
+    #[getset(get_copy = "pub")]
+    num_turns: u32,
+}
+
+/// Runs games between contestants, who take turns sitting in each seat.
+/// Game `i` is shuffled with seed `seed + i`, so any game can be played again on its own.
+#[derive(Clone, Debug, Getters, CopyGetters)]
+pub struct Tournament {
+    #[getset(get = "pub")]
+    settings: GameSettings,
+    game: BaseGame,
+    #[getset(get = "pub")]
+    contestants: Vec<Contestant>,
+    #[getset(get_copy = "pub")]
+    seed: u64,
+}
+
+impl Tournament {
+    pub fn new(settings: GameSettings, contestants: Vec<Contestant>, seed: u64) -> Result<Self, TournamentError> {
+        let game = settings.build().map_err(TournamentError::InvalidSettings)?;
+        let num = contestants.len() as u32;
+        if num < settings.min_players || num > settings.max_players {
+            return Err(TournamentError::WrongNumPlayers{ num, min: settings.min_players, max: settings.max_players });
+        }
+        Ok(Self { settings, game, contestants, seed })
+    }
+
+    /// Plays game `index` of the tournament
+    pub fn play_game(&self, index: u32) -> GameResult {
+        let num_players = self.contestants.len();
+        let seed = self.seed.wrapping_add(index as u64);
+        let seats = (0..num_players).map(|seat| (seat + index as usize) % num_players).collect_vec();
+        let mut rng = pcg64_seeded(seed);
+        let mut strategies = seats.iter()
+            .map(|contestant| self.contestants[*contestant].strategy(rng.gen()))
+            .collect_vec();
+        let mut state = self.game.new_state_seeded(num_players as u32, seed);
+        let num_turns = strategy::play_out(&self.game, &mut state, &mut strategies);
+
+        let mut win_shares = vec![0.0; num_players];
+        let winners = state.winners();
+        for winner in &winners {
+            win_shares[seats[*winner as usize]] += 1.0 / winners.len() as f64;
+        }
+        GameResult { index, seed, seats, win_shares, num_turns }
+    }
+
+    /// Plays games `0..num_games` on `num_threads` threads. The results are in game order
+    /// and don't depend on the number of threads.
+    pub fn run(&self, num_games: u32, num_threads: u32) -> Vec<GameResult> {
+        let num_threads = num_threads.max(1);
+        std::thread::scope(|scope| {
+            let handles = (0..num_threads)
+                .map(|thread| scope.spawn(move || (thread..num_games).step_by(num_threads as usize)
+                    .map(|index| self.play_game(index))
+                    .collect_vec()))
+                .collect_vec();
+            handles.into_iter()
+                .flat_map(|handle| handle.join().expect("Game thread panicked"))
+                .sorted_by_key(|result| result.index)
+                .collect()
+        })
+    }
+}
+
+/// How a contestant did over a tournament
+#[derive(Clone, Debug, Getters, CopyGetters, Serialize)]
+pub struct Standing {
+    #[getset(get = "pub")]
+    contestant: String,
+    #[getset(get_copy = "pub")]
+    num_games: u32,
+    /// Wins, where a split win counts as a fraction
+    #[getset(get_copy = "pub")]
+    wins: f64,
+    #[getset(get_copy = "pub")]
+    win_rate: f64,
+    /// 95% confidence interval of the win rate
+    #[getset(get_copy = "pub")]
+    confidence_interval: (f64, f64),
+}
+
+/// z-score of a 95% confidence interval
+const Z_95: f64 = 1.96;
+
+/// The Wilson score interval of a rate over `n` trials.
+/// Unlike the normal approximation, it stays within [0, 1] and doesn't collapse when the rate is 0 or 1.
+fn wilson_interval(rate: f64, n: f64) -> (f64, f64) {
+    if n == 0.0 {
+        return (0.0, 1.0);
+    }
+    let z2 = Z_95 * Z_95;
+    let center = (rate + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half_width = Z_95 * (rate * (1.0 - rate) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+    ((center - half_width).max(0.0), (center + half_width).min(1.0))
+}
+
+/// Each contestant's standing, in contestant order.
+/// Contestants are labeled with their position in the list, since the same strategy can play more than once.
+pub fn standings(contestants: &[Contestant], results: &[GameResult]) -> Vec<Standing> {
+    contestants.iter().enumerate()
+        .map(|(i, contestant)| {
+            let num_games = results.len() as u32;
+            let wins = results.iter().map(|result| result.win_shares[i]).sum::<f64>();
+            let win_rate = if num_games == 0 { 0.0 } else { wins / num_games as f64 };
+            Standing {
+                contestant: format!("#{} {}", i + 1, contestant),
+                num_games,
+                wins,
+                win_rate,
+                confidence_interval: wilson_interval(win_rate, num_games as f64),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contestant_from_str() {
+        assert_eq!("hard".parse(), Ok(Contestant::Bot(BotLevel::Hard)));
+        assert_eq!("analysis:8".parse(), Ok(Contestant::Analysis{ num_samples: 8 }));
+        assert!("analysis:x".parse::<Contestant>().is_err());
+        assert!("grandmaster".parse::<Contestant>().is_err());
+        for contestant in [Contestant::Bot(BotLevel::Easy), Contestant::Analysis{ num_samples: 3 }] {
+            assert_eq!(contestant.to_string().parse(), Ok(contestant));
+        }
+    }
+
+    #[test]
+    fn test_tournament() {
+        let contestants = vec![Contestant::Bot(BotLevel::Easy), Contestant::Bot(BotLevel::Hard)];
+        let tournament = Tournament::new(GameSettings::default(), contestants.clone(), 10).unwrap();
+        let results = tournament.run(12, 3);
+        assert_eq!(results.iter().map(|result| result.index()).collect_vec(), (0..12).collect_vec());
+        assert!(results.iter().all(|result| (result.win_shares().iter().sum::<f64>() - 1.0).abs() < 1e-9));
+
+        // Games replay the same on their own
+        let again = tournament.play_game(5);
+        assert_eq!((again.seats(), again.win_shares()), (results[5].seats(), results[5].win_shares()));
+
+        let standings = standings(&contestants, &results);
+        assert_eq!(standings[1].contestant(), "#2 hard");
+        let (low, high) = standings[1].confidence_interval();
+        assert!(low <= standings[1].win_rate() && standings[1].win_rate() <= high);
+
+        assert_eq!(
+            Tournament::new(GameSettings::default(), vec![Contestant::Bot(BotLevel::Easy)], 0).unwrap_err(),
+            TournamentError::WrongNumPlayers{ num: 1, min: 2, max: 8 },
+        );
+    }
+}